@@ -0,0 +1,252 @@
+use crate::helpers::audit::{AuditFilter, AuditResult};
+use crate::models::report::{RelocateRequest, RunRef};
+use crate::services::admin_service;
+use axum::{Json, extract::Query, response::IntoResponse};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/dedup",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Content-addressed store statistics", body = crate::helpers::dedup::DedupStats),
+        (status = 500, description = "Internal Server Error")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn dedup_stats() -> impl IntoResponse {
+    admin_service::dedup_stats().await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/dedup/gc",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Unreferenced blobs removed from the store"),
+        (status = 500, description = "Internal Server Error")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn dedup_gc() -> impl IntoResponse {
+    admin_service::dedup_gc().await
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ArchiveQuery {
+    /// Overrides ARCHIVE_AFTER_DAYS for this pass
+    pub older_than_days: Option<u64>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/archive",
+    tag = "admin",
+    params(ArchiveQuery),
+    responses(
+        (status = 200, description = "Old runs packed into per-run archives", body = crate::helpers::archive::ArchiveSummary),
+        (status = 400, description = "No age configured"),
+        (status = 500, description = "Internal Server Error")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn archive_now(Query(query): Query<ArchiveQuery>) -> impl IntoResponse {
+    admin_service::archive_now(query.older_than_days).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/archive/rehydrate",
+    tag = "admin",
+    request_body = RunRef,
+    responses(
+        (status = 200, description = "Archived run extracted again"),
+        (status = 400, description = "Bad Request"),
+        (status = 404, description = "Run not archived"),
+        (status = 500, description = "Internal Server Error")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn rehydrate(Json(run): Json<RunRef>) -> impl IntoResponse {
+    admin_service::rehydrate(run).await
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct UsageQuery {
+    /// Only include this project
+    pub project: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/usage",
+    tag = "admin",
+    params(UsageQuery),
+    responses(
+        (status = 200, description = "Bytes and file counts per project, branch, report and run", body = crate::helpers::usage::UsageReport)
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn usage(Query(query): Query<UsageQuery>) -> impl IntoResponse {
+    admin_service::usage(query.project).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/usage/rescan",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Usage index rebuilt from disk", body = crate::helpers::usage::Usage),
+        (status = 500, description = "Internal Server Error")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn usage_rescan() -> impl IntoResponse {
+    admin_service::usage_rescan().await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/relocate",
+    tag = "admin",
+    request_body = RelocateRequest,
+    responses(
+        (status = 200, description = "Directory moved or copied", body = crate::helpers::relocate::RelocateResult),
+        (status = 400, description = "Bad Request"),
+        (status = 503, description = "Timed out waiting for a history lock of the source"),
+        (status = 500, description = "Internal Server Error")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn relocate(Json(request): Json<RelocateRequest>) -> impl IntoResponse {
+    admin_service::relocate(request).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/redirects",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Redirects kept for moved directories", body = [crate::helpers::relocate::Redirect])
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn list_redirects() -> impl IntoResponse {
+    admin_service::list_redirects().await
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct RedirectQuery {
+    /// Old location relative to DATA_DIR, e.g. `my-project/old-branch`
+    pub from: String,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/admin/redirects",
+    tag = "admin",
+    params(RedirectQuery),
+    responses(
+        (status = 200, description = "Redirect removed"),
+        (status = 404, description = "No such redirect"),
+        (status = 500, description = "Internal Server Error")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn delete_redirect(Query(query): Query<RedirectQuery>) -> impl IntoResponse {
+    admin_service::delete_redirect(query.from).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/keys",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Keys of API_KEYS_FILE with secret fingerprints and expiry", body = Vec<crate::helpers::api_keys::KeyInfo>)
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn list_keys() -> impl IntoResponse {
+    admin_service::list_keys().await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/keys/reload",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Key store file reloaded"),
+        (status = 400, description = "Invalid key file or API_KEYS_FILE not configured; previous keys stay active")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn reload_keys() -> impl IntoResponse {
+    admin_service::reload_keys().await
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AuditQuery {
+    /// Only operations of this key (or token rule)
+    pub key_id: Option<String>,
+    /// Substring of the action, e.g. `DELETE` or `/history/`
+    pub action: Option<String>,
+    /// Prefix of the target path
+    pub target: Option<String>,
+    pub result: Option<AuditResult>,
+    /// Earliest timestamp, epoch milliseconds
+    pub since: Option<i64>,
+    /// Latest timestamp, epoch milliseconds
+    pub until: Option<i64>,
+    /// Most recent entries to return (default 100, max 1000)
+    pub limit: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/audit",
+    tag = "admin",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "Matching audit log entries, newest first", body = Vec<crate::helpers::audit::AuditEntry>),
+        (status = 404, description = "Audit log disabled"),
+        (status = 500, description = "Internal Server Error")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn audit_log(Query(query): Query<AuditQuery>) -> impl IntoResponse {
+    admin_service::audit_log(AuditFilter {
+        key_id: query.key_id,
+        action: query.action,
+        target: query.target,
+        result: query.result,
+        since: query.since,
+        until: query.until,
+        limit: query.limit,
+    })
+    .await
+}
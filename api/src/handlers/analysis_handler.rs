@@ -0,0 +1,253 @@
+use crate::helpers::api_keys::Principal;
+use crate::helpers::compare::{Comparison, DurationThreshold};
+use crate::helpers::quality_gate::GateVerdict;
+use crate::helpers::test_history::TestHistory;
+use crate::services::analysis_service::{self, RunSelector};
+use axum::{
+    Extension,
+    extract::{Path, Query},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TrendQuery {
+    /// Only runs finished at or after this time (epoch milliseconds)
+    pub from: Option<i64>,
+    /// Only runs finished at or before this time (epoch milliseconds)
+    pub to: Option<i64>,
+    /// Keep only the last N points
+    pub limit: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/projects/{project}/branches/{branch}/reports/{report}/trend",
+    tag = "analysis",
+    params(
+        ("project" = String, Path, description = "Project name"),
+        ("branch" = String, Path, description = "Branch name"),
+        ("report" = String, Path, description = "Report name"),
+        TrendQuery
+    ),
+    responses(
+        (status = 200, description = "One point per run, oldest first"),
+        (status = 400, description = "Bad Request"),
+        (status = 404, description = "Report not found")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn trend(
+    Path((project, branch, report)): Path<(String, String, String)>,
+    Query(query): Query<TrendQuery>,
+) -> impl IntoResponse {
+    analysis_service::trend(project, branch, report, query.from, query.to, query.limit).await
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct FlakyQuery {
+    /// Number of latest runs to analyse (default FLAKY_WINDOW, 10)
+    pub runs: Option<usize>,
+    /// Pass/fail switches needed to count as flaky (default 2)
+    pub min_flips: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/projects/{project}/branches/{branch}/reports/{report}/flaky",
+    tag = "analysis",
+    params(
+        ("project" = String, Path, description = "Project name"),
+        ("branch" = String, Path, description = "Branch name"),
+        ("report" = String, Path, description = "Report name"),
+        FlakyQuery
+    ),
+    responses(
+        (status = 200, description = "Tests switching between pass and fail, most flaky first"),
+        (status = 400, description = "Bad Request"),
+        (status = 404, description = "Report not found")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn flaky_tests(
+    Path((project, branch, report)): Path<(String, String, String)>,
+    Query(query): Query<FlakyQuery>,
+) -> impl IntoResponse {
+    analysis_service::flaky_tests(project, branch, report, query.runs, query.min_flips).await
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TestHistoryQuery {
+    /// Allure `historyId` of the test
+    pub history_id: Option<String>,
+    /// Full name of the test, used when `history_id` is not given
+    pub full_name: Option<String>,
+    /// Limit to one report of the branch
+    pub report_name: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/projects/{project}/branches/{branch}/tests/history",
+    tag = "analysis",
+    params(
+        ("project" = String, Path, description = "Project name"),
+        ("branch" = String, Path, description = "Branch name"),
+        TestHistoryQuery
+    ),
+    responses(
+        (status = 200, description = "Every run of the test, oldest first", body = TestHistory),
+        (status = 400, description = "Bad Request"),
+        (status = 404, description = "Branch or test not found")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn test_history(
+    Path((project, branch)): Path<(String, String)>,
+    Query(query): Query<TestHistoryQuery>,
+) -> impl IntoResponse {
+    analysis_service::test_history(
+        project,
+        branch,
+        query.report_name,
+        query.history_id,
+        query.full_name,
+    )
+    .await
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct CompareQuery {
+    /// Branch of the base run, e.g. `main`
+    pub base_branch: String,
+    /// Report of the base run
+    pub base_report: String,
+    /// Base run id. Defaults to the latest run, or the run before head when
+    /// both sides are the same report
+    pub base_run: Option<u32>,
+    /// Branch of the head run (default: `base_branch`)
+    pub head_branch: Option<String>,
+    /// Report of the head run (default: `base_report`)
+    pub head_report: Option<String>,
+    /// Head run id (default: latest)
+    pub head_run: Option<u32>,
+    /// Minimum relative duration change to report, in percent (default 50)
+    pub min_duration_change: Option<f64>,
+    /// Minimum absolute duration change to report, in milliseconds (default 1000)
+    pub min_duration_delta_ms: Option<i64>,
+    /// `json` (default) or `markdown`
+    pub format: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/projects/{project}/compare",
+    tag = "analysis",
+    params(
+        ("project" = String, Path, description = "Project name"),
+        CompareQuery
+    ),
+    responses(
+        (status = 200, description = "Differences between the two runs", body = Comparison),
+        (status = 400, description = "Bad Request"),
+        (status = 403, description = "Key not allowed on one of the branches"),
+        (status = 404, description = "Report or run not found")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn compare_runs(
+    Extension(principal): Extension<Principal>,
+    Path(project): Path<String>,
+    Query(query): Query<CompareQuery>,
+) -> impl IntoResponse {
+    let branches = [Some(&query.base_branch), query.head_branch.as_ref()];
+    if branches
+        .into_iter()
+        .flatten()
+        .any(|branch| !principal.allows_branch(branch))
+    {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let head = RunSelector {
+        branch: query
+            .head_branch
+            .unwrap_or_else(|| query.base_branch.clone()),
+        report: query
+            .head_report
+            .unwrap_or_else(|| query.base_report.clone()),
+        run_id: query.head_run,
+    };
+    let base = RunSelector {
+        branch: query.base_branch,
+        report: query.base_report,
+        run_id: query.base_run,
+    };
+    let threshold = DurationThreshold {
+        min_change: query.min_duration_change.unwrap_or(50.0) / 100.0,
+        min_delta_ms: query.min_duration_delta_ms.unwrap_or(1000),
+    };
+    let markdown = query.format.as_deref() == Some("markdown");
+    analysis_service::compare_runs(project, base, head, threshold, markdown)
+        .await
+        .into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/projects/{project}/branches/{branch}/reports/{report}/runs/{run_id}/clusters",
+    tag = "analysis",
+    params(
+        ("project" = String, Path, description = "Project name"),
+        ("branch" = String, Path, description = "Branch name"),
+        ("report" = String, Path, description = "Report name"),
+        ("run_id" = u32, Path, description = "Run id")
+    ),
+    responses(
+        (status = 200, description = "Failures grouped by normalized message and trace, largest first"),
+        (status = 400, description = "Bad Request"),
+        (status = 404, description = "Run or test outcomes not found")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn failure_clusters(
+    Path((project, branch, report, run_id)): Path<(String, String, String, u32)>,
+) -> impl IntoResponse {
+    analysis_service::failure_clusters(project, branch, report, run_id).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/projects/{project}/branches/{branch}/reports/{report}/runs/{run_id}/gate",
+    tag = "analysis",
+    params(
+        ("project" = String, Path, description = "Project name"),
+        ("branch" = String, Path, description = "Branch name"),
+        ("report" = String, Path, description = "Report name"),
+        ("run_id" = u32, Path, description = "Run id")
+    ),
+    responses(
+        (status = 200, description = "Quality gate verdict with reasons", body = GateVerdict),
+        (status = 400, description = "Bad Request"),
+        (status = 404, description = "Run or test outcomes not found")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn quality_gate(
+    Path((project, branch, report, run_id)): Path<(String, String, String, u32)>,
+) -> impl IntoResponse {
+    analysis_service::quality_gate(project, branch, report, run_id).await
+}
@@ -0,0 +1,143 @@
+use crate::models::history::{RemoveHistoryEntriesRequest, TrimHistoryRequest};
+use crate::services::history_service;
+use axum::{
+    Json,
+    extract::{Path, Query},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct HistoryQuery {
+    /// Include the full history entries, not only their summary
+    #[serde(default)]
+    pub full: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/projects/{project}/branches/{branch}/reports/{report}/history",
+    tag = "history",
+    params(
+        ("project" = String, Path, description = "Project name"),
+        ("branch" = String, Path, description = "Branch name"),
+        ("report" = String, Path, description = "Report name"),
+        HistoryQuery
+    ),
+    responses(
+        (status = 200, description = "History entries, oldest first"),
+        (status = 400, description = "Bad Request"),
+        (status = 404, description = "Report not found")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn list_history(
+    Path((project, branch, report)): Path<(String, String, String)>,
+    Query(query): Query<HistoryQuery>,
+) -> impl IntoResponse {
+    history_service::list_history(project, branch, report, query.full).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/projects/{project}/branches/{branch}/reports/{report}/history/trim",
+    tag = "history",
+    params(
+        ("project" = String, Path, description = "Project name"),
+        ("branch" = String, Path, description = "Branch name"),
+        ("report" = String, Path, description = "Report name")
+    ),
+    request_body = TrimHistoryRequest,
+    responses(
+        (status = 200, description = "History trimmed to the last N entries"),
+        (status = 400, description = "Bad Request"),
+        (status = 404, description = "Report not found")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn trim_history(
+    Path((project, branch, report)): Path<(String, String, String)>,
+    Json(body): Json<TrimHistoryRequest>,
+) -> impl IntoResponse {
+    history_service::trim_history(project, branch, report, body.keep).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/projects/{project}/branches/{branch}/reports/{report}/history/remove",
+    tag = "history",
+    params(
+        ("project" = String, Path, description = "Project name"),
+        ("branch" = String, Path, description = "Branch name"),
+        ("report" = String, Path, description = "Report name")
+    ),
+    request_body = RemoveHistoryEntriesRequest,
+    responses(
+        (status = 200, description = "Matching history entries removed"),
+        (status = 400, description = "Bad Request"),
+        (status = 404, description = "Report not found")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn remove_history_entries(
+    Path((project, branch, report)): Path<(String, String, String)>,
+    Json(body): Json<RemoveHistoryEntriesRequest>,
+) -> impl IntoResponse {
+    history_service::remove_history_entries(project, branch, report, body.uuids).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/projects/{project}/branches/{branch}/reports/{report}/history",
+    tag = "history",
+    params(
+        ("project" = String, Path, description = "Project name"),
+        ("branch" = String, Path, description = "Branch name"),
+        ("report" = String, Path, description = "Report name")
+    ),
+    responses(
+        (status = 200, description = "History deleted"),
+        (status = 400, description = "Bad Request"),
+        (status = 404, description = "Report not found")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn reset_history(
+    Path((project, branch, report)): Path<(String, String, String)>,
+) -> impl IntoResponse {
+    history_service::reset_history(project, branch, report).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/projects/{project}/branches/{branch}/reports/{report}/history/rebuild",
+    tag = "history",
+    params(
+        ("project" = String, Path, description = "Project name"),
+        ("branch" = String, Path, description = "Branch name"),
+        ("report" = String, Path, description = "Report name")
+    ),
+    responses(
+        (status = 200, description = "History regenerated from retained results"),
+        (status = 400, description = "Bad Request"),
+        (status = 404, description = "Report not found"),
+        (status = 500, description = "Internal Server Error")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn rebuild_history(
+    Path((project, branch, report)): Path<(String, String, String)>,
+) -> impl IntoResponse {
+    history_service::rebuild_history(project, branch, report).await
+}
@@ -0,0 +1,126 @@
+use crate::helpers::viewer_auth::{ViewerAuth, now_secs, safe_next};
+use axum::{
+    Form,
+    extract::{Query, State},
+    http::{HeaderValue, StatusCode, header},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+pub struct LoginQuery {
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginForm {
+    pub username: String,
+    pub password: String,
+    pub next: Option<String>,
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn login_html(next: &str, error: Option<&str>) -> Html<String> {
+    let error = error
+        .map(|e| format!("<p class=\"error\">{}</p>", escape_html(e)))
+        .unwrap_or_default();
+    Html(format!(
+        r#"<!doctype html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Sign in - Test Reports</title>
+<style>
+body {{ font-family: system-ui, sans-serif; background: #f4f5f7; display: flex; justify-content: center; padding-top: 12vh; }}
+form {{ background: #fff; padding: 2rem; border-radius: 8px; box-shadow: 0 1px 4px rgba(0,0,0,.15); width: 18rem; }}
+label {{ display: block; margin-top: 1rem; font-size: .9rem; }}
+input {{ width: 100%; box-sizing: border-box; padding: .5rem; margin-top: .25rem; }}
+button {{ margin-top: 1.5rem; width: 100%; padding: .6rem; }}
+.error {{ color: #b00020; }}
+</style>
+</head>
+<body>
+<form method="post" action="/login">
+<h1>Test Reports</h1>
+{error}
+<input type="hidden" name="next" value="{next}">
+<label>Username<input name="username" autocomplete="username" required autofocus></label>
+<label>Password<input name="password" type="password" autocomplete="current-password" required></label>
+<button type="submit">Sign in</button>
+</form>
+</body>
+</html>
+"#,
+        error = error,
+        next = escape_html(next),
+    ))
+}
+
+/// Login page for report viewers
+pub async fn login_page(
+    State(viewer): State<Option<Arc<ViewerAuth>>>,
+    Query(query): Query<LoginQuery>,
+) -> Response {
+    if viewer.is_none() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    login_html(&safe_next(query.next.as_deref()), None).into_response()
+}
+
+/// Checks the credentials and starts a session
+pub async fn login(
+    State(viewer): State<Option<Arc<ViewerAuth>>>,
+    Form(form): Form<LoginForm>,
+) -> Response {
+    let Some(viewer) = viewer else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let next = safe_next(form.next.as_deref());
+
+    let check = viewer.clone();
+    let username = form.username.clone();
+    let valid =
+        tokio::task::spawn_blocking(move || check.verify_password(&form.username, &form.password))
+            .await
+            .unwrap_or(false);
+
+    if !valid {
+        tracing::warn!("viewer_auth: failed login for '{}'", username);
+        return (
+            StatusCode::UNAUTHORIZED,
+            login_html(&next, Some("Invalid username or password.")),
+        )
+            .into_response();
+    }
+
+    let cookie = viewer.session_cookie(&viewer.issue(&username, now_secs()));
+    // safe_next only passes valid header values; never panic if that changes
+    let location = HeaderValue::try_from(next).unwrap_or(HeaderValue::from_static("/"));
+    (
+        StatusCode::SEE_OTHER,
+        [(header::SET_COOKIE, cookie)],
+        [(header::LOCATION, location)],
+    )
+        .into_response()
+}
+
+/// Ends the session
+pub async fn logout(State(viewer): State<Option<Arc<ViewerAuth>>>) -> Response {
+    let Some(viewer) = viewer else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    (
+        [(header::SET_COOKIE, viewer.clear_cookie())],
+        Redirect::to("/login"),
+    )
+        .into_response()
+}
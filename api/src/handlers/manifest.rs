@@ -1,16 +1,46 @@
-use axum::{Json, response::IntoResponse};
+use axum::{
+    Extension, Json,
+    extract::State,
+    http::{Extensions, HeaderMap, header},
+    response::IntoResponse,
+};
 use serde_json::json;
+use std::path::PathBuf;
+use std::sync::Arc;
 
-/// Get manifest of all projects, branches, and reports
-pub async fn get_manifest() -> impl IntoResponse {
+use crate::helpers::access_control::{AccessControl, Peer};
+use crate::helpers::viewer_auth::ViewerSession;
+
+/// What the manifest lists from, and whom for.
+#[derive(Clone)]
+pub struct ManifestState {
+    pub access: Arc<AccessControl>,
+    pub data_dir: PathBuf,
+}
+
+/// Get manifest of the projects, branches, and reports the client may view
+pub async fn get_manifest(
+    State(ManifestState { access, data_dir }): State<ManifestState>,
+    session: Option<Extension<ViewerSession>>,
+    extensions: Extensions,
+    headers: HeaderMap,
+) -> impl IntoResponse {
     use std::collections::BTreeMap;
 
-    let data_dir = std::env::var("DATA_DIR").unwrap_or_else(|_| "../data".to_string());
-    let data_path = std::path::PathBuf::from(&data_dir);
+    let authorization = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok());
+    // Projects are listed only to clients their access rules let in, and to
+    // logged-in viewers whose groups may view them
+    let peer = Peer::of(&extensions);
+    let may_list = |project: &str| {
+        peer.is_some_and(|peer| access.may_list(project, peer, &headers, authorization))
+            && session.as_ref().is_none_or(|s| s.may_view(project))
+    };
 
     let mut projects: BTreeMap<String, BTreeMap<String, Vec<serde_json::Value>>> = BTreeMap::new();
 
-    if let Ok(project_dirs) = std::fs::read_dir(&data_path) {
+    if let Ok(project_dirs) = std::fs::read_dir(&data_dir) {
         for project_entry in project_dirs.flatten() {
             let project_path = project_entry.path();
             if !project_path.is_dir() {
@@ -18,6 +48,11 @@ pub async fn get_manifest() -> impl IntoResponse {
             }
 
             let project_name = project_entry.file_name().to_string_lossy().to_string();
+
+            // Skip internal directories such as the dedup store
+            if project_name.starts_with('.') || !may_list(&project_name) {
+                continue;
+            }
             let mut branches: BTreeMap<String, Vec<serde_json::Value>> = BTreeMap::new();
 
             if let Ok(branch_dirs) = std::fs::read_dir(&project_path) {
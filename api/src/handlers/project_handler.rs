@@ -0,0 +1,114 @@
+use crate::models::project::ProjectSettings;
+use crate::models::quarantine::{AddQuarantineRequest, QuarantineEntry};
+use crate::services::project_service;
+use axum::{Json, extract::Path, response::IntoResponse};
+
+#[utoipa::path(
+    get,
+    path = "/api/projects/{project}/settings",
+    tag = "projects",
+    params(
+        ("project" = String, Path, description = "Project name")
+    ),
+    responses(
+        (status = 200, description = "Project settings", body = ProjectSettings),
+        (status = 400, description = "Bad Request")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn get_settings(Path(project): Path<String>) -> impl IntoResponse {
+    project_service::get_settings(project).await
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/projects/{project}/settings",
+    tag = "projects",
+    params(
+        ("project" = String, Path, description = "Project name")
+    ),
+    request_body(
+        content = ProjectSettings,
+        description = "Fields to change. Omitted fields keep their stored value, nested \
+                       `quality_gate` rules merge and `null` clears a field."
+    ),
+    responses(
+        (status = 200, description = "Project settings after the update", body = ProjectSettings),
+        (status = 400, description = "Bad Request"),
+        (status = 500, description = "Internal Server Error")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn put_settings(
+    Path(project): Path<String>,
+    Json(update): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    project_service::put_settings(project, update).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/projects/{project}/quarantine",
+    tag = "projects",
+    params(
+        ("project" = String, Path, description = "Project name")
+    ),
+    responses(
+        (status = 200, description = "Quarantine entries, each with an `expired` flag"),
+        (status = 400, description = "Bad Request")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn list_quarantine(Path(project): Path<String>) -> impl IntoResponse {
+    project_service::list_quarantine(project).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/projects/{project}/quarantine",
+    tag = "projects",
+    params(
+        ("project" = String, Path, description = "Project name")
+    ),
+    request_body = AddQuarantineRequest,
+    responses(
+        (status = 201, description = "Entry added", body = QuarantineEntry),
+        (status = 400, description = "Bad Request")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn add_quarantine(
+    Path(project): Path<String>,
+    Json(request): Json<AddQuarantineRequest>,
+) -> impl IntoResponse {
+    project_service::add_quarantine(project, request).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/projects/{project}/quarantine/{id}",
+    tag = "projects",
+    params(
+        ("project" = String, Path, description = "Project name"),
+        ("id" = String, Path, description = "Quarantine entry id")
+    ),
+    responses(
+        (status = 200, description = "Entry removed"),
+        (status = 400, description = "Bad Request"),
+        (status = 404, description = "Entry not found")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn remove_quarantine(Path((project, id)): Path<(String, String)>) -> impl IntoResponse {
+    project_service::remove_quarantine(project, id).await
+}
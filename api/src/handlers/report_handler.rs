@@ -1,6 +1,8 @@
+use crate::helpers::api_keys::Principal;
+use crate::helpers::fs_helper::StorageOptions;
 use crate::models::report::FileUploadRequest;
 use crate::services::report_service;
-use axum::{extract::Multipart, response::IntoResponse};
+use axum::{Extension, extract::Multipart, response::IntoResponse};
 
 #[utoipa::path(
     post,
@@ -10,12 +12,17 @@ use axum::{extract::Multipart, response::IntoResponse};
     responses(
         (status = 200, description = "Files uploaded successfully"),
         (status = 400, description = "Bad Request"),
+        (status = 403, description = "Key not allowed to upload to this project or branch"),
+        (status = 429, description = "Upload rate limit of the key exceeded, see Retry-After"),
         (status = 500, description = "Internal Server Error")
     ),
     security(
         ("api_key" = [])
     )
 )]
-pub async fn upload_report(multipart: Multipart) -> impl IntoResponse {
-    report_service::upload_report(multipart).await
+pub async fn upload_report(
+    Extension(principal): Extension<Principal>,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    report_service::upload_report(principal, multipart, StorageOptions::from_env()).await
 }
@@ -0,0 +1,90 @@
+use crate::helpers::run_summary::RunSummary;
+use crate::services::run_service;
+use axum::{
+    extract::{Path, Query},
+    http::{HeaderMap, header},
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+#[utoipa::path(
+    get,
+    path = "/api/projects/{project}/branches/{branch}/reports/{report}/runs/{run_id}/summary",
+    tag = "runs",
+    params(
+        ("project" = String, Path, description = "Project name"),
+        ("branch" = String, Path, description = "Branch name"),
+        ("report" = String, Path, description = "Report name"),
+        ("run_id" = u32, Path, description = "Run id")
+    ),
+    responses(
+        (status = 200, description = "Run summary", body = RunSummary),
+        (status = 400, description = "Bad Request"),
+        (status = 404, description = "Run or summary not found")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn get_run_summary(
+    Path((project, branch, report, run_id)): Path<(String, String, String, u32)>,
+) -> impl IntoResponse {
+    run_service::get_run_summary(project, branch, report, run_id).await
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ShareQuery {
+    /// Target a run uploaded with `type=raw`
+    #[serde(default)]
+    pub raw: bool,
+    /// Link lifetime in hours (default 24, at most 720)
+    pub expires_in_hours: Option<u64>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/projects/{project}/branches/{branch}/reports/{report}/runs/{run_id}/share",
+    tag = "runs",
+    params(
+        ("project" = String, Path, description = "Project name"),
+        ("branch" = String, Path, description = "Branch name"),
+        ("report" = String, Path, description = "Report name"),
+        ("run_id" = u32, Path, description = "Run id"),
+        ShareQuery
+    ),
+    responses(
+        (status = 200, description = "Signed link to the run, valid until `expires_at` (epoch seconds)"),
+        (status = 400, description = "Bad Request"),
+        (status = 404, description = "Run not found")
+    ),
+    security(
+        ("api_key" = [])
+    )
+)]
+pub async fn share_run(
+    Path((project, branch, report, run_id)): Path<(String, String, String, u32)>,
+    Query(query): Query<ShareQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .map(|host| {
+            let scheme = headers
+                .get("x-forwarded-proto")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("http");
+            format!("{}://{}", scheme, host)
+        });
+    run_service::share_run(
+        project,
+        branch,
+        report,
+        run_id,
+        query.raw,
+        query.expires_in_hours,
+        host,
+    )
+    .await
+}
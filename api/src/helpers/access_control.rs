@@ -1,12 +1,155 @@
-use axum::http::StatusCode;
-use axum::{extract::State, http::Request, middleware::Next, response::Response};
+use argon2::PasswordHash;
+use axum::http::{Extensions, HeaderMap, HeaderValue, StatusCode, header};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::Request,
+    middleware::Next,
+    response::Response,
+};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
 use ipnet::IpNet;
-use std::{net::IpAddr, sync::Arc};
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+};
+
+use crate::helpers::api_keys::{constant_time_eq, hash_secret};
+use crate::helpers::proxy_protocol::ProxiedFor;
+use crate::helpers::quarantine::now_ms;
+use crate::helpers::share_link::{self, SHARE_PREFIX, ShareError};
+use crate::helpers::viewer_auth::verify_password_hash;
+
+/// A viewer account for HTTP Basic authentication on one project.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ViewerCredential {
+    pub username: String,
+    /// Argon2 PHC string, e.g. `$argon2id$v=19$m=19456,t=2,p=1$...`
+    pub password_hash: String,
+}
+
+/// Access rules of one project in the PROJECT_ACCESS_FILE.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProjectAccessConfig {
+    /// Replaces ALLOWED_IPS for this project when not empty
+    #[serde(default)]
+    pub allowed_ips: Vec<String>,
+    /// When not empty, viewers must also log in with one of these
+    #[serde(default)]
+    pub viewers: Vec<ViewerCredential>,
+    /// With viewer login enabled, only members of these groups may view
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ProjectAccessFile {
+    #[serde(default)]
+    projects: HashMap<String, ProjectAccessConfig>,
+}
+
+#[derive(Debug)]
+struct ProjectAccess {
+    allowed_ips: Vec<IpNet>,
+    viewers: Vec<ViewerCredential>,
+    groups: Vec<String>,
+}
+
+/// Reads the per-project access rules, keyed by project name.
+pub fn load_project_access(path: &Path) -> Result<HashMap<String, ProjectAccessConfig>, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let file: ProjectAccessFile = serde_json::from_slice(&bytes)
+        .map_err(|e| format!("Invalid project access file {:?}: {}", path, e))?;
+    for (project, config) in &file.projects {
+        for viewer in &config.viewers {
+            if PasswordHash::new(&viewer.password_hash).is_err() {
+                return Err(format!(
+                    "project '{}', viewer '{}': password_hash must be an argon2 PHC string",
+                    project, viewer.username
+                ));
+            }
+        }
+    }
+    Ok(file.projects)
+}
+
+/// Where a request came from: the connection's peer and, when a trusted
+/// balancer sent a PROXY protocol header, the client it named.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Peer {
+    pub addr: IpAddr,
+    pub proxied_for: Option<IpAddr>,
+}
+
+impl From<IpAddr> for Peer {
+    fn from(addr: IpAddr) -> Self {
+        Self {
+            addr,
+            proxied_for: None,
+        }
+    }
+}
+
+impl Peer {
+    /// The peer of a request, from its `ConnectInfo` and `ProxiedFor` extensions.
+    pub fn of(extensions: &Extensions) -> Option<Self> {
+        Some(Self {
+            addr: extensions.get::<ConnectInfo<SocketAddr>>()?.0.ip(),
+            proxied_for: extensions
+                .get::<ProxiedFor>()
+                .and_then(|p| p.0)
+                .map(|a| a.ip()),
+        })
+    }
+
+    /// The client of the connection, before any forwarding headers.
+    pub fn source(&self) -> IpAddr {
+        self.proxied_for.unwrap_or(self.addr)
+    }
+}
+
+/// The one header trusted proxies write the client address to
+/// (FORWARDED_HEADER). Others are passed through from the client, so they are
+/// never read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ForwardedHeader {
+    #[default]
+    XForwardedFor,
+    /// RFC 7239 `Forwarded`, its `for=` parameters
+    Forwarded,
+    XRealIp,
+}
+
+impl std::str::FromStr for ForwardedHeader {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "x-forwarded-for" => Ok(Self::XForwardedFor),
+            "forwarded" => Ok(Self::Forwarded),
+            "x-real-ip" => Ok(Self::XRealIp),
+            _ => Err(format!(
+                "FORWARDED_HEADER must be x-forwarded-for, forwarded or x-real-ip, got '{}'",
+                s
+            )),
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct AccessControl {
     allowed_proxies: Arc<Vec<IpNet>>,
     allowed_ips: Arc<Vec<IpNet>>,
+    projects: Arc<HashMap<String, ProjectAccess>>,
+    trusted_hops: usize,
+    forwarded_header: ForwardedHeader,
+    /// SHA-256 of the last password verified per project and viewer, so Basic
+    /// credentials sent with every asset request skip argon2 after the first
+    verified: Arc<Mutex<HashMap<(String, String), String>>>,
 }
 
 impl AccessControl {
@@ -14,10 +157,44 @@ impl AccessControl {
         Self {
             allowed_proxies: Arc::new(Self::compile_nets(allowed_proxies)),
             allowed_ips: Arc::new(Self::compile_nets(allowed_ips)),
+            projects: Arc::new(HashMap::new()),
+            trusted_hops: 0,
+            forwarded_header: ForwardedHeader::default(),
+            verified: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
-    fn compile_nets(entries: Vec<String>) -> Vec<IpNet> {
+    /// Limits how many proxies are skipped when resolving the client address.
+    /// Without ALLOWED_PROXY_IPS, the last `hops` entries are trusted by count.
+    pub fn with_trusted_hops(mut self, hops: usize) -> Self {
+        self.trusted_hops = hops;
+        self
+    }
+
+    /// Sets the header the client address is read from.
+    pub fn with_forwarded_header(mut self, header: ForwardedHeader) -> Self {
+        self.forwarded_header = header;
+        self
+    }
+
+    /// Adds per-project rules, applied by the first path segment.
+    pub fn with_projects(mut self, projects: HashMap<String, ProjectAccessConfig>) -> Self {
+        let projects = projects
+            .into_iter()
+            .map(|(name, config)| {
+                let access = ProjectAccess {
+                    allowed_ips: Self::compile_nets(config.allowed_ips),
+                    viewers: config.viewers,
+                    groups: config.groups,
+                };
+                (name, access)
+            })
+            .collect();
+        self.projects = Arc::new(projects);
+        self
+    }
+
+    pub fn compile_nets(entries: Vec<String>) -> Vec<IpNet> {
         entries
             .into_iter()
             .filter_map(|s| {
@@ -45,7 +222,7 @@ impl AccessControl {
             .collect()
     }
 
-    fn ip_in_nets(ip: IpAddr, nets: &[IpNet]) -> bool {
+    pub fn ip_in_nets(ip: IpAddr, nets: &[IpNet]) -> bool {
         if nets.iter().any(|n| n.contains(&ip)) {
             return true;
         }
@@ -58,7 +235,7 @@ impl AccessControl {
     }
 
     /// Normalizes IPv4-mapped IPv6 to plain IPv4.
-    fn normalize_ip(ip: IpAddr) -> IpAddr {
+    pub fn normalize_ip(ip: IpAddr) -> IpAddr {
         match ip {
             IpAddr::V6(v6) => {
                 if let Some(v4) = v6.to_ipv4_mapped() {
@@ -71,46 +248,203 @@ impl AccessControl {
         }
     }
 
-    pub fn is_allowed(&self, remote: IpAddr, xff: Option<&str>) -> (bool, &'static str) {
-        let remote = Self::normalize_ip(remote);
+    pub fn is_allowed(&self, peer: impl Into<Peer>, headers: &HeaderMap) -> (bool, &'static str) {
+        self.check(peer.into(), headers, &self.allowed_ips)
+    }
 
-        if self.allowed_proxies.is_empty() {
-            if self.allowed_ips.is_empty() {
-                return (
-                    true,
-                    "no proxies configured and no allowed_ips -> allow all",
-                );
-            }
-            if Self::ip_in_nets(remote, &self.allowed_ips) {
-                return (true, "remote addr in allowed_ips");
-            }
-            return (false, "remote addr not in allowed_ips");
-        }
+    /// Like [`Self::is_allowed`], with the project's own list when it has one.
+    pub fn is_allowed_for(
+        &self,
+        project: Option<&str>,
+        peer: impl Into<Peer>,
+        headers: &HeaderMap,
+    ) -> (bool, &'static str) {
+        let nets = project
+            .and_then(|p| self.projects.get(p))
+            .filter(|access| !access.allowed_ips.is_empty())
+            .map(|access| access.allowed_ips.as_slice())
+            .unwrap_or(&self.allowed_ips);
+        self.check(peer.into(), headers, nets)
+    }
 
-        if !Self::ip_in_nets(remote, &self.allowed_proxies) {
-            return (false, "request did not come from trusted proxy");
-        }
+    /// Whether the client is in `nets`, resolved like for `ALLOWED_IPS`. An
+    /// empty list admits nobody.
+    pub fn is_listed(&self, peer: impl Into<Peer>, headers: &HeaderMap, nets: &[IpNet]) -> bool {
+        !nets.is_empty() && self.check(peer.into(), headers, nets).0
+    }
 
-        let client_ip: Option<IpAddr> = xff
-            .and_then(|v| v.split(',').next())
-            .and_then(|s| s.trim().parse().ok())
-            .map(Self::normalize_ip);
+    /// Whether viewing a project depends on the client: an IP allowlist,
+    /// viewer credentials or groups apply to it.
+    pub fn is_protected(&self, project: Option<&str>) -> bool {
+        !self.allowed_ips.is_empty()
+            || project
+                .and_then(|p| self.projects.get(p))
+                .is_some_and(|access| {
+                    !access.allowed_ips.is_empty()
+                        || !access.viewers.is_empty()
+                        || !access.groups.is_empty()
+                })
+    }
 
-        if self.allowed_ips.is_empty() {
-            return (
-                true,
-                "Trusted proxy -> No allowed_ips configured -> allow all",
-            );
+    /// Whether a logged-in viewer with these groups may see a project.
+    pub fn groups_allowed(&self, project: Option<&str>, groups: &[String]) -> bool {
+        project
+            .and_then(|p| self.projects.get(p))
+            .is_none_or(|access| {
+                access.groups.is_empty() || access.groups.iter().any(|g| groups.contains(g))
+            })
+    }
+
+    /// Checks an `Authorization: Basic` header against the project's viewers.
+    pub fn viewer_allowed(&self, project: Option<&str>, authorization: Option<&str>) -> bool {
+        let Some(access) = project.and_then(|p| self.projects.get(p)) else {
+            return true;
+        };
+        if access.viewers.is_empty() {
+            return true;
         }
+        let credentials = authorization
+            .and_then(|v| v.strip_prefix("Basic "))
+            .and_then(|v| STANDARD.decode(v.trim()).ok())
+            .and_then(|v| String::from_utf8(v).ok());
+        let Some((username, password)) = credentials.as_deref().and_then(|c| c.split_once(':'))
+        else {
+            return false;
+        };
+        let project = project.unwrap_or_default().to_string();
+        let key = (project, username.to_string());
+        let digest = hash_secret(password);
+        if let Some(known) = self.verified.lock().unwrap().get(&key)
+            && constant_time_eq(known.as_bytes(), digest.as_bytes())
+        {
+            return true;
+        }
+        let phc = access
+            .viewers
+            .iter()
+            .find(|viewer| viewer.username == username)
+            .map(|viewer| viewer.password_hash.as_str());
+        if !verify_password_hash(phc, password) {
+            return false;
+        }
+        self.verified.lock().unwrap().insert(key, digest);
+        true
+    }
+
+    /// Whether a project may be listed to a client: its IP rules and, when it
+    /// has viewers, the request's Basic credentials allow it.
+    pub fn may_list(
+        &self,
+        project: &str,
+        peer: impl Into<Peer>,
+        headers: &HeaderMap,
+        authorization: Option<&str>,
+    ) -> bool {
+        self.is_allowed_for(Some(project), peer, headers).0
+            && self.viewer_allowed(Some(project), authorization)
+    }
 
-        if let Some(ip) = client_ip {
-            if Self::ip_in_nets(ip, &self.allowed_ips) {
-                return (true, "client ip from header in allowed_ips");
+    /// The client address of a request. Walks the forwarding chain from the
+    /// right, skipping trusted proxies (or, without ALLOWED_PROXY_IPS, a fixed
+    /// number of hops); `None` when the chain ends in an unusable entry.
+    pub fn client_ip(&self, peer: impl Into<Peer>, headers: &HeaderMap) -> Option<IpAddr> {
+        // The PROXY header source stands in for the balancer, which was
+        // checked against PROXY_PROTOCOL_TRUSTED when it connected
+        let remote = Self::normalize_ip(peer.into().source());
+        let max_hops = match (self.allowed_proxies.is_empty(), self.trusted_hops) {
+            (true, 0) => return Some(remote),
+            (false, 0) => usize::MAX,
+            (_, hops) => hops,
+        };
+
+        let mut chain = forwarded_chain(headers, self.forwarded_header);
+        chain.push(Some(remote));
+        let mut skipped = 0;
+        for entry in chain.iter().rev() {
+            let ip = Self::normalize_ip((*entry)?);
+            let trusted =
+                self.allowed_proxies.is_empty() || Self::ip_in_nets(ip, &self.allowed_proxies);
+            if trusted && skipped < max_hops {
+                skipped += 1;
+                continue;
             }
-            return (false, "client ip not allowed");
+            return Some(ip);
         }
+        None
+    }
 
-        (false, "client ip not allowed")
+    fn check(
+        &self,
+        peer: Peer,
+        headers: &HeaderMap,
+        allowed_ips: &[IpNet],
+    ) -> (bool, &'static str) {
+        if !self.allowed_proxies.is_empty()
+            && !Self::ip_in_nets(Self::normalize_ip(peer.addr), &self.allowed_proxies)
+        {
+            return (false, "request did not come from trusted proxy");
+        }
+        if allowed_ips.is_empty() {
+            return (true, "no allowed_ips configured -> allow all");
+        }
+        match self.client_ip(peer, headers) {
+            Some(ip) if Self::ip_in_nets(ip, allowed_ips) => (true, "client ip in allowed_ips"),
+            Some(_) => (false, "client ip not in allowed_ips"),
+            None => (false, "client ip unknown"),
+        }
+    }
+}
+
+/// Strips quotes, brackets and ports: `"[2001:db8::1]:4711"`, `192.0.2.1:80`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
+    }
+    node.parse().ok().or_else(|| {
+        let (host, port) = node.rsplit_once(':')?;
+        port.parse::<u16>().ok()?;
+        host.parse().ok()
+    })
+}
+
+/// Addresses the request was forwarded for according to `header`, client
+/// first. Entries that are not IP addresses (`unknown`, obfuscated ids) are
+/// `None`.
+pub fn forwarded_chain(headers: &HeaderMap, header: ForwardedHeader) -> Vec<Option<IpAddr>> {
+    let joined = |name: &str| -> String {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+
+    match header {
+        ForwardedHeader::Forwarded => joined("forwarded")
+            .split(',')
+            .filter(|element| !element.trim().is_empty())
+            .map(|element| {
+                element.split(';').find_map(|pair| {
+                    let (key, value) = pair.split_once('=')?;
+                    key.trim()
+                        .eq_ignore_ascii_case("for")
+                        .then(|| parse_node(value))
+                        .flatten()
+                })
+            })
+            .collect(),
+        ForwardedHeader::XForwardedFor => joined("x-forwarded-for")
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(parse_node)
+            .collect(),
+        ForwardedHeader::XRealIp => headers
+            .get("x-real-ip")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| vec![parse_node(v)])
+            .unwrap_or_default(),
     }
 }
 
@@ -119,33 +453,55 @@ pub async fn access_control(
     req: Request<axum::body::Body>,
     next: Next,
 ) -> Response {
-    let remote_addr = req
-        .extensions()
-        .get::<axum::extract::ConnectInfo<std::net::SocketAddr>>()
-        .map(|info| info.0.ip());
-
-    let xff = req
-        .headers()
-        .get("x-forwarded-for")
-        .and_then(|v| v.to_str().ok());
-
-    match remote_addr {
-        Some(remote) => {
-            let (allowed, reason) = ac.is_allowed(remote, xff);
+    if req.uri().path().starts_with(&format!("/{}/", SHARE_PREFIX)) {
+        return serve_shared(req, next).await;
+    }
+
+    let peer = Peer::of(req.extensions());
+
+    let project = req
+        .uri()
+        .path()
+        .split('/')
+        .find(|s| !s.is_empty())
+        .map(|s| percent_decode_str(s).decode_utf8_lossy().into_owned());
+    let project = project.as_deref();
+
+    match peer {
+        Some(peer) => {
+            let (allowed, reason) = ac.is_allowed_for(project, peer, req.headers());
+            let client = ac.client_ip(peer, req.headers());
             tracing::debug!(
-                "access_control: remote={} xff={:?} allowed={} reason={}",
-                remote,
-                xff,
+                "access_control: remote={} client={:?} allowed={} reason={}",
+                peer.addr,
+                client,
                 allowed,
                 reason
             );
             if allowed {
+                let authorization = req
+                    .headers()
+                    .get(header::AUTHORIZATION)
+                    .and_then(|v| v.to_str().ok());
+                if !ac.viewer_allowed(project, authorization) {
+                    let realm = format!("Basic realm=\"{}\"", project.unwrap_or_default());
+                    let mut response = Response::builder()
+                        .status(StatusCode::UNAUTHORIZED)
+                        .body(axum::body::Body::from("Unauthorized"))
+                        .unwrap();
+                    if let Ok(value) = HeaderValue::from_str(&realm) {
+                        response
+                            .headers_mut()
+                            .insert(header::WWW_AUTHENTICATE, value);
+                    }
+                    return response;
+                }
                 next.run(req).await
             } else {
                 tracing::warn!(
-                    "access_control: DENY remote={} xff={:?} reason={}",
-                    remote,
-                    xff,
+                    "access_control: DENY remote={} client={:?} reason={}",
+                    peer.addr,
+                    client,
                     reason
                 );
                 Response::builder()
@@ -163,3 +519,35 @@ pub async fn access_control(
         }
     }
 }
+
+fn forbidden(message: &'static str) -> Response {
+    Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .body(axum::body::Body::from(message))
+        .unwrap()
+}
+
+/// A valid share link replaces the IP and viewer checks for its run only:
+/// the request is rewritten to the run's real path and marked with [`share_link::SharedRun`].
+async fn serve_shared(mut req: Request<axum::body::Body>, next: Next) -> Response {
+    match share_link::verify(req.uri().path(), now_ms() / 1000) {
+        Ok(shared) => {
+            let uri = match req.uri().query() {
+                Some(query) => format!("{}?{}", shared.path, query),
+                None => shared.path.clone(),
+            };
+            let Ok(uri) = uri.parse() else {
+                return forbidden("Invalid share link");
+            };
+            tracing::debug!("access_control: share link for {}", shared.run_path);
+            *req.uri_mut() = uri;
+            req.extensions_mut().insert(shared);
+            next.run(req).await
+        }
+        Err(ShareError::Expired) => forbidden("Share link expired"),
+        Err(e) => {
+            tracing::warn!("access_control: DENY share link {:?}", e);
+            forbidden("Invalid share link")
+        }
+    }
+}
@@ -0,0 +1,449 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, RwLock};
+use std::time::{Duration, SystemTime};
+use utoipa::ToSchema;
+
+use crate::helpers::glob::glob_match;
+use crate::helpers::quarantine::{DAY_MS, now_ms, parse_date};
+
+/// Key id reported for the legacy `API_SECRET` key.
+pub const LEGACY_KEY_ID: &str = "api_secret";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Upload,
+    Read,
+    Delete,
+    Admin,
+}
+
+/// One secret of a key; a key holds several while it is being rotated.
+#[derive(Debug, Clone, Deserialize)]
+pub struct KeySecret {
+    /// Hex SHA-256 of the secret, optionally prefixed with `sha256:`
+    pub hash: String,
+    /// Last day (`YYYY-MM-DD`, UTC) the secret is accepted
+    #[serde(default)]
+    pub expires: Option<String>,
+}
+
+/// One key of the key store file. Only SHA-256 hashes of secrets are stored.
+#[derive(Debug, Clone, Deserialize)]
+pub struct KeyEntry {
+    pub id: String,
+    /// Shorthand for a single entry in `secrets`
+    #[serde(default)]
+    pub hash: Option<String>,
+    #[serde(default)]
+    pub expires: Option<String>,
+    #[serde(default)]
+    pub secrets: Vec<KeySecret>,
+    #[serde(default)]
+    pub scopes: Vec<Scope>,
+    /// Project name globs the key may touch; empty means every project
+    #[serde(default)]
+    pub projects: Vec<String>,
+    /// Branch name globs the key may touch; empty means every branch
+    #[serde(default)]
+    pub branches: Vec<String>,
+}
+
+/// Maps the claims of a verified bearer token to scopes and allowed projects.
+/// `claims` values are globs; `projects` and `branches` may use `{claim}`
+/// placeholders, plus `{ref_name}` for `ref` without `refs/heads/` or `refs/tags/`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenRule {
+    pub id: String,
+    pub claims: BTreeMap<String, String>,
+    #[serde(default)]
+    pub scopes: Vec<Scope>,
+    #[serde(default)]
+    pub projects: Vec<String>,
+    #[serde(default)]
+    pub branches: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct KeyFile {
+    #[serde(default)]
+    keys: Vec<KeyEntry>,
+    #[serde(default)]
+    tokens: Vec<TokenRule>,
+}
+
+#[derive(Debug, Clone)]
+struct StoredSecret {
+    hash: [u8; 32],
+    /// Epoch milliseconds from which the secret is rejected
+    expires_at: Option<i64>,
+    expires: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct StoredKey {
+    id: String,
+    secrets: Vec<StoredSecret>,
+    scopes: Vec<Scope>,
+    projects: Vec<String>,
+    branches: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct KeyStore {
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+    keys: Vec<StoredKey>,
+    tokens: Vec<TokenRule>,
+}
+
+/// A secret as listed by the admin API: a short fingerprint, never the hash.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SecretInfo {
+    /// First 8 hex digits of the secret's SHA-256
+    pub fingerprint: String,
+    pub expires: Option<String>,
+    pub expired: bool,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct KeyInfo {
+    pub id: String,
+    pub scopes: Vec<Scope>,
+    pub projects: Vec<String>,
+    pub branches: Vec<String>,
+    pub secrets: Vec<SecretInfo>,
+}
+
+/// The authenticated key of a request, stored as a request extension.
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub key_id: String,
+    pub scopes: Vec<Scope>,
+    pub projects: Vec<String>,
+    pub branches: Vec<String>,
+    /// `sub` claim of a bearer token
+    pub subject: Option<String>,
+}
+
+impl Principal {
+    /// `admin` grants every other scope.
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope) || self.scopes.contains(&Scope::Admin)
+    }
+
+    /// Whether the key is limited to some projects or branches.
+    pub fn is_restricted(&self) -> bool {
+        !self.projects.is_empty() || !self.branches.is_empty()
+    }
+
+    pub fn allows_project(&self, project: &str) -> bool {
+        self.projects.is_empty() || self.projects.iter().any(|p| glob_match(p, project))
+    }
+
+    pub fn allows_branch(&self, branch: &str) -> bool {
+        self.branches.is_empty() || self.branches.iter().any(|b| glob_match(b, branch))
+    }
+
+    pub fn allows(&self, project: &str, branch: Option<&str>) -> bool {
+        self.allows_project(project) && branch.is_none_or(|b| self.allows_branch(b))
+    }
+}
+
+static KEYS: LazyLock<RwLock<KeyStore>> = LazyLock::new(|| RwLock::new(KeyStore::default()));
+
+fn digest(secret: &str) -> [u8; 32] {
+    Sha256::digest(secret.as_bytes()).into()
+}
+
+/// Hex SHA-256 of a secret, the form keys are stored in.
+pub fn hash_secret(secret: &str) -> String {
+    digest(secret)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Compares without an early exit, so timing does not reveal matching prefixes.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn parse_hash(id: &str, hash: &str) -> Result<[u8; 32], String> {
+    let hex = hash.trim().trim_start_matches("sha256:");
+    let bytes: Option<Vec<u8>> = (hex.len() == 64)
+        .then(|| {
+            (0..64)
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+                .collect()
+        })
+        .flatten();
+    bytes
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| format!("key '{}': hash must be a hex SHA-256 digest", id))
+}
+
+fn parse_secret(id: &str, hash: &str, expires: Option<String>) -> Result<StoredSecret, String> {
+    let expires_at = match &expires {
+        Some(date) => Some(
+            parse_date(date)
+                .map(|start| start + DAY_MS)
+                .ok_or_else(|| format!("key '{}': expires must be YYYY-MM-DD", id))?,
+        ),
+        None => None,
+    };
+    Ok(StoredSecret {
+        hash: parse_hash(id, hash)?,
+        expires_at,
+        expires,
+    })
+}
+
+fn parse_keys(bytes: &[u8]) -> Result<(Vec<StoredKey>, Vec<TokenRule>), String> {
+    let file: KeyFile = serde_json::from_slice(bytes).map_err(|e| e.to_string())?;
+    let mut keys: Vec<StoredKey> = Vec::new();
+    for (i, entry) in file.keys.into_iter().enumerate() {
+        if entry.id.trim().is_empty() {
+            return Err(format!("key #{} has no id", i + 1));
+        }
+        if keys.iter().any(|k| k.id == entry.id) {
+            return Err(format!("duplicate key id '{}'", entry.id));
+        }
+        if entry.scopes.is_empty() {
+            return Err(format!("key '{}' has no scopes", entry.id));
+        }
+        let mut secrets = Vec::new();
+        if let Some(hash) = &entry.hash {
+            secrets.push(parse_secret(&entry.id, hash, entry.expires.clone())?);
+        }
+        for secret in entry.secrets {
+            secrets.push(parse_secret(&entry.id, &secret.hash, secret.expires)?);
+        }
+        if secrets.is_empty() {
+            return Err(format!("key '{}' has no secrets", entry.id));
+        }
+        keys.push(StoredKey {
+            id: entry.id,
+            secrets,
+            scopes: entry.scopes,
+            projects: entry.projects,
+            branches: entry.branches,
+        });
+    }
+    for (i, rule) in file.tokens.iter().enumerate() {
+        if rule.id.trim().is_empty() {
+            return Err(format!("token rule #{} has no id", i + 1));
+        }
+        if file.tokens[..i].iter().any(|r| r.id == rule.id) {
+            return Err(format!("duplicate token rule id '{}'", rule.id));
+        }
+        if rule.claims.is_empty() {
+            return Err(format!("token rule '{}' matches no claims", rule.id));
+        }
+        if rule.scopes.is_empty() {
+            return Err(format!("token rule '{}' has no scopes", rule.id));
+        }
+    }
+    Ok((keys, file.tokens))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Loads the key store file into memory and returns the number of keys and
+/// token rules. The path is remembered for [`reload_keys`].
+pub fn load_keys(path: &Path) -> Result<usize, String> {
+    let mtime = modified(path);
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let (keys, tokens) =
+        parse_keys(&bytes).map_err(|e| format!("Invalid key file {:?}: {}", path, e))?;
+    let count = keys.len() + tokens.len();
+    let mut store = KEYS.write().unwrap();
+    store.path = Some(path.to_path_buf());
+    store.modified = mtime;
+    store.keys = keys;
+    store.tokens = tokens;
+    Ok(count)
+}
+
+/// Re-reads the key store file. On error the keys in memory stay active.
+pub fn reload_keys() -> Result<usize, String> {
+    let path = KEYS.read().unwrap().path.clone();
+    match path {
+        Some(path) => load_keys(&path),
+        None => Err("API_KEYS_FILE is not configured".to_string()),
+    }
+}
+
+/// Reloads the key store file when it changed on disk since the last load.
+pub fn reload_if_changed() -> Option<Result<usize, String>> {
+    let (path, loaded) = {
+        let store = KEYS.read().unwrap();
+        (store.path.clone()?, store.modified)
+    };
+    let current = modified(&path);
+    (current.is_some() && current != loaded).then(|| load_keys(&path))
+}
+
+/// Seconds between checks of the key store file for changes (API_KEYS_RELOAD_SECS,
+/// default 30, 0 disables polling).
+pub fn reload_interval() -> Option<Duration> {
+    let secs = env::var("API_KEYS_RELOAD_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(30);
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// Keys of the store file with their secrets' fingerprints and expiry.
+pub fn list_keys() -> Vec<KeyInfo> {
+    let now = now_ms();
+    KEYS.read()
+        .unwrap()
+        .keys
+        .iter()
+        .map(|k| KeyInfo {
+            id: k.id.clone(),
+            scopes: k.scopes.clone(),
+            projects: k.projects.clone(),
+            branches: k.branches.clone(),
+            secrets: k
+                .secrets
+                .iter()
+                .map(|s| SecretInfo {
+                    fingerprint: s.hash[..4].iter().map(|b| format!("{:02x}", b)).collect(),
+                    expires: s.expires.clone(),
+                    expired: s.expires_at.is_some_and(|at| now >= at),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// The legacy single secret, which acts as an unrestricted admin key.
+fn legacy_secret() -> Option<String> {
+    env::var("API_SECRET").ok().filter(|s| !s.is_empty())
+}
+
+/// Whether any key can authenticate at all.
+pub fn has_keys() -> bool {
+    let store = KEYS.read().unwrap();
+    legacy_secret().is_some() || !store.keys.is_empty() || !store.tokens.is_empty()
+}
+
+/// Resolves a presented secret to its key. Every stored hash is compared in
+/// constant time; expired secrets are rejected.
+pub fn authenticate(secret: &str) -> Option<Principal> {
+    if secret.is_empty() {
+        return None;
+    }
+    let presented = digest(secret);
+    if legacy_secret().is_some_and(|s| constant_time_eq(&digest(&s), &presented)) {
+        return Some(Principal {
+            key_id: LEGACY_KEY_ID.to_string(),
+            scopes: vec![Scope::Admin],
+            projects: Vec::new(),
+            branches: Vec::new(),
+            subject: None,
+        });
+    }
+
+    let now = now_ms();
+    let store = KEYS.read().unwrap();
+    let mut found: Option<&StoredKey> = None;
+    for key in &store.keys {
+        for stored in &key.secrets {
+            if constant_time_eq(&stored.hash, &presented) && found.is_none() {
+                if stored.expires_at.is_some_and(|at| now >= at) {
+                    tracing::warn!("auth: rejected expired secret of key '{}'", key.id);
+                } else {
+                    found = Some(key);
+                }
+            }
+        }
+    }
+    found.map(|k| Principal {
+        key_id: k.id.clone(),
+        scopes: k.scopes.clone(),
+        projects: k.projects.clone(),
+        branches: k.branches.clone(),
+        subject: None,
+    })
+}
+
+fn claim_str(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Replaces `{claim}` placeholders; `None` when a claim is missing.
+fn substitute(pattern: &str, claims: &BTreeMap<String, String>) -> Option<String> {
+    let mut out = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        let end = start + rest[start..].find('}')?;
+        out.push_str(&rest[..start]);
+        out.push_str(claims.get(&rest[start + 1..end])?);
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Some(out)
+}
+
+/// Resolves the claims of a verified token through the first matching rule.
+pub fn authorize_claims(claims: &Map<String, Value>) -> Option<Principal> {
+    let mut values: BTreeMap<String, String> = claims
+        .iter()
+        .filter_map(|(k, v)| claim_str(v).map(|v| (k.clone(), v)))
+        .collect();
+    if let Some(git_ref) = values.get("ref") {
+        let name = git_ref
+            .strip_prefix("refs/heads/")
+            .or_else(|| git_ref.strip_prefix("refs/tags/"))
+            .unwrap_or(git_ref)
+            .to_string();
+        values.insert("ref_name".to_string(), name);
+    }
+
+    let store = KEYS.read().unwrap();
+    let rule = store.tokens.iter().find(|rule| {
+        rule.claims.iter().all(|(claim, pattern)| {
+            values
+                .get(claim)
+                .is_some_and(|value| glob_match(pattern, value))
+        })
+    })?;
+    // A pattern whose placeholder is missing matches nothing rather than everything
+    let resolve = |patterns: &[String]| -> Vec<String> {
+        if patterns.is_empty() {
+            return Vec::new();
+        }
+        let resolved: Vec<String> = patterns
+            .iter()
+            .filter_map(|p| substitute(p, &values))
+            .collect();
+        if resolved.is_empty() {
+            vec![String::new()]
+        } else {
+            resolved
+        }
+    };
+    Some(Principal {
+        key_id: rule.id.clone(),
+        scopes: rule.scopes.clone(),
+        projects: resolve(&rule.projects),
+        branches: resolve(&rule.branches),
+        subject: values.get("sub").cloned(),
+    })
+}
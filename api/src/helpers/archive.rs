@@ -0,0 +1,423 @@
+use axum::{
+    body::Body,
+    http::{HeaderValue, Request, StatusCode, header},
+    middleware::Next,
+    response::Response,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio_util::io::{ReaderStream, SyncIoBridge};
+use utoipa::ToSchema;
+use zip::{CompressionMethod, ZipArchive, ZipWriter, write::SimpleFileOptions};
+
+use crate::helpers::dedup::dedup_dir;
+use crate::helpers::fs_helper::{StorageOptions, data_dir, list_run_dirs};
+use crate::helpers::precompress::{is_generated_sibling, precompress_dir};
+use crate::helpers::usage;
+
+/// Archive written inside the run directory in place of its files.
+/// Keeping the (now nearly empty) run directory around means id allocation
+/// and the manifest keep seeing the run. Hidden entries (run metadata) are
+/// left in place so APIs reading them don't need to open the archive.
+pub const ARCHIVE_FILE_NAME: &str = ".archive.zip";
+
+#[derive(Debug, Default, Serialize, ToSchema, Clone)]
+pub struct ArchiveSummary {
+    pub runs_archived: u64,
+    pub files_archived: u64,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub errors: Vec<String>,
+}
+
+/// Age after which runs are archived, from ARCHIVE_AFTER_DAYS. `None` disables archiving.
+pub fn archive_after() -> Option<Duration> {
+    std::env::var("ARCHIVE_AFTER_DAYS")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|days| *days > 0)
+        .map(|days| Duration::from_secs(days * 24 * 60 * 60))
+}
+
+/// How often the background archiver runs, from ARCHIVE_INTERVAL_MINUTES (default 60).
+pub fn archive_interval() -> Duration {
+    let minutes = std::env::var("ARCHIVE_INTERVAL_MINUTES")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|m| *m > 0)
+        .unwrap_or(60);
+    Duration::from_secs(minutes * 60)
+}
+
+pub fn is_archived(run_dir: &Path) -> bool {
+    run_dir.join(ARCHIVE_FILE_NAME).is_file()
+}
+
+/// Files of a run below its non-hidden top-level entries. Hidden top-level
+/// entries are run metadata and stay outside the archive; hidden files deeper
+/// down are report content and are archived like any other.
+fn collect_files(root: &Path, dir: &Path, out: &mut Vec<(String, PathBuf)>) {
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if dir == root && entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            let Ok(ft) = entry.file_type() else { continue };
+            if ft.is_dir() {
+                collect_files(root, &path, out);
+            } else if ft.is_file()
+                && let Ok(rel) = path.strip_prefix(root)
+            {
+                let name = rel
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().to_string())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                out.push((name, path));
+            }
+        }
+    }
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    let mut files = Vec::new();
+    collect_files(dir, dir, &mut files);
+    files
+        .iter()
+        .filter_map(|(_, p)| std::fs::metadata(p).ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+fn write_archive(
+    tmp_path: &Path,
+    files: &[(String, PathBuf)],
+    storage: StorageOptions,
+) -> Result<u64, String> {
+    let file = std::fs::File::create(tmp_path)
+        .map_err(|e| format!("Failed to create archive {:?}: {}", tmp_path, e))?;
+    let mut writer = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut count = 0;
+    for (name, path) in files {
+        // Precompressed siblings are regenerated on rehydration; without
+        // precompression they would be lost, so they are kept then
+        if storage.precompress && is_generated_sibling(path) {
+            continue;
+        }
+        let mut source =
+            std::fs::File::open(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        writer
+            .start_file(name.as_str(), options)
+            .map_err(|e| format!("Failed to add {} to archive: {}", name, e))?;
+        std::io::copy(&mut source, &mut writer)
+            .map_err(|e| format!("Failed to write {} to archive: {}", name, e))?;
+        count += 1;
+    }
+    writer
+        .finish()
+        .map_err(|e| format!("Failed to finish archive: {}", e))?;
+    Ok(count)
+}
+
+/// Packs every file of `run_dir` into a single deflated zip and removes the originals.
+/// Returns the number of files archived and the size of the archive.
+pub fn archive_run(run_dir: &Path, storage: StorageOptions) -> Result<(u64, u64), String> {
+    if is_archived(run_dir) {
+        return Ok((0, 0));
+    }
+
+    let mut files = Vec::new();
+    collect_files(run_dir, run_dir, &mut files);
+    files.sort();
+
+    let tmp_path = run_dir.join(format!("{}.tmp", ARCHIVE_FILE_NAME));
+    let archive_path = run_dir.join(ARCHIVE_FILE_NAME);
+    let written = write_archive(&tmp_path, &files, storage).and_then(|count| {
+        std::fs::rename(&tmp_path, &archive_path)
+            .map(|_| count)
+            .map_err(|e| format!("Failed to move archive into place: {}", e))
+    });
+    let count = match written {
+        Ok(count) => count,
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp_path);
+            return Err(e);
+        }
+    };
+
+    // The archive now answers requests, the loose files can go.
+    let entries = std::fs::read_dir(run_dir).map_err(|e| e.to_string())?;
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let res = if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+        if let Err(e) = res {
+            tracing::warn!("archive: failed to remove {:?}: {}", path, e);
+        }
+    }
+
+    let size = std::fs::metadata(&archive_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
+    Ok((count, size))
+}
+
+/// Extracts an archived run back into its directory and drops the archive,
+/// then stores its files as `storage` asks.
+pub fn rehydrate_run(
+    data_dir: &Path,
+    run_dir: &Path,
+    storage: StorageOptions,
+) -> Result<u64, String> {
+    let archive_path = run_dir.join(ARCHIVE_FILE_NAME);
+    let file = std::fs::File::open(&archive_path)
+        .map_err(|e| format!("Run is not archived ({:?}): {}", archive_path, e))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| e.to_string())?;
+
+    let mut count = 0;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(|e| e.to_string())?;
+        let Some(rel) = entry.enclosed_name() else {
+            continue;
+        };
+        let out_path = run_dir.join(rel);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&out_path).map_err(|e| e.to_string())?;
+            continue;
+        }
+        if let Some(parent) = out_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let mut out = std::fs::File::create(&out_path).map_err(|e| e.to_string())?;
+        std::io::copy(&mut entry, &mut out).map_err(|e| e.to_string())?;
+        count += 1;
+    }
+
+    std::fs::remove_file(&archive_path)
+        .map_err(|e| format!("Failed to remove archive {:?}: {}", archive_path, e))?;
+
+    if storage.precompress
+        && let Err(e) = precompress_dir(run_dir)
+    {
+        tracing::warn!("archive: precompression after rehydration failed: {}", e);
+    }
+    if storage.dedup
+        && let Err(e) = dedup_dir(data_dir, run_dir)
+    {
+        tracing::warn!("archive: dedup after rehydration failed: {}", e);
+    }
+
+    Ok(count)
+}
+
+/// Archives every run whose directory was last modified more than `max_age` ago.
+pub fn archive_old_runs(
+    data_dir: &Path,
+    max_age: Duration,
+    storage: StorageOptions,
+) -> ArchiveSummary {
+    let mut summary = ArchiveSummary::default();
+    let now = SystemTime::now();
+
+    for run in list_run_dirs(data_dir) {
+        if is_archived(&run.path) {
+            continue;
+        }
+        let modified = match std::fs::metadata(&run.path).and_then(|m| m.modified()) {
+            Ok(t) => t,
+            Err(_) => continue,
+        };
+        if now.duration_since(modified).unwrap_or_default() < max_age {
+            continue;
+        }
+
+        let before = dir_size(&run.path);
+        match archive_run(&run.path, storage) {
+            Ok((files, size)) => {
+                usage::record_run(data_dir, &run);
+                summary.runs_archived += 1;
+                summary.files_archived += files;
+                summary.bytes_before += before;
+                summary.bytes_after += size;
+            }
+            Err(e) => summary.errors.push(format!("{:?}: {}", run.path, e)),
+        }
+    }
+
+    summary
+}
+
+/// Splits a static request path into the run directory it targets and the
+/// path of the requested file inside that run.
+fn split_run_path(data_dir: &Path, uri_path: &str) -> Option<(PathBuf, String)> {
+    let segments: Vec<String> = uri_path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            percent_encoding::percent_decode_str(s)
+                .decode_utf8_lossy()
+                .to_string()
+        })
+        .collect();
+    if segments
+        .iter()
+        .any(|s| s == ".." || s.contains('/') || s.contains('\\'))
+    {
+        return None;
+    }
+
+    let run_len = if segments.len() >= 5 && segments[3] == "raw" {
+        5
+    } else {
+        4
+    };
+    if segments.len() < run_len || segments[run_len - 1].parse::<u32>().is_err() {
+        return None;
+    }
+
+    let mut run_dir = data_dir.to_path_buf();
+    for s in &segments[..run_len] {
+        run_dir.push(s);
+    }
+    Some((run_dir, segments[run_len..].join("/")))
+}
+
+/// Read handle on an archive file with its own position, so clones of a
+/// cached [`ZipArchive`] can read concurrently.
+#[derive(Clone)]
+struct ArchiveReader {
+    file: Arc<std::fs::File>,
+    pos: u64,
+}
+
+impl Read for ArchiveReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.file.read_at(buf, self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for ArchiveReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let base = match pos {
+            SeekFrom::Start(offset) => {
+                self.pos = offset;
+                return Ok(offset);
+            }
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            SeekFrom::End(offset) => self.file.metadata()?.len().checked_add_signed(offset),
+        };
+        self.pos = base.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "seek before start")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+/// Archives served recently, with the central directory already parsed.
+const ARCHIVE_CACHE_SIZE: usize = 256;
+
+type CachedArchive = (SystemTime, ZipArchive<ArchiveReader>);
+
+static ARCHIVES: LazyLock<Mutex<HashMap<PathBuf, CachedArchive>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The archive of a run, parsed once and reused while its mtime is unchanged.
+fn open_archive(run_dir: &Path) -> Option<ZipArchive<ArchiveReader>> {
+    let path = run_dir.join(ARCHIVE_FILE_NAME);
+    let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+    if let Some((at, archive)) = ARCHIVES.lock().unwrap().get(&path)
+        && *at == modified
+    {
+        return Some(archive.clone());
+    }
+
+    let reader = ArchiveReader {
+        file: Arc::new(std::fs::File::open(&path).ok()?),
+        pos: 0,
+    };
+    let archive = ZipArchive::new(reader).ok()?;
+    let mut cache = ARCHIVES.lock().unwrap();
+    if cache.len() >= ARCHIVE_CACHE_SIZE {
+        cache.clear();
+    }
+    cache.insert(path, (modified, archive.clone()));
+    Some(archive)
+}
+
+/// Middleware serving files of archived runs straight from their zip, so cold
+/// runs stay reachable at their original URLs without being extracted again.
+pub async fn serve_archived(req: Request<Body>, next: Next) -> Response {
+    let data_dir = data_dir();
+    let uri_path = req.uri().path().to_string();
+    let Some((run_dir, mut entry)) = split_run_path(&data_dir, &uri_path) else {
+        return next.run(req).await;
+    };
+    if !is_archived(&run_dir) {
+        return next.run(req).await;
+    }
+
+    if entry.is_empty() && !uri_path.ends_with('/') {
+        return Response::builder()
+            .status(StatusCode::PERMANENT_REDIRECT)
+            .header(header::LOCATION, format!("{}/", uri_path))
+            .body(Body::empty())
+            .unwrap();
+    }
+    if entry.is_empty() || uri_path.ends_with('/') {
+        entry = if entry.is_empty() {
+            "index.html".to_string()
+        } else {
+            format!("{}/index.html", entry)
+        };
+    }
+
+    let mime = mime_guess::from_path(&entry)
+        .first_or_octet_stream()
+        .to_string();
+    let lookup = tokio::task::spawn_blocking(move || {
+        let mut archive = open_archive(&run_dir)?;
+        let size = archive.by_name(&entry).ok()?.size();
+        Some((archive, entry, size))
+    });
+    let Ok(Some((mut archive, entry, size))) = lookup.await else {
+        return Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())
+            .unwrap();
+    };
+
+    // Stream the entry instead of holding it in memory
+    let (reader, writer) = tokio::io::duplex(64 * 1024);
+    let mut writer = SyncIoBridge::new(writer);
+    tokio::task::spawn_blocking(move || {
+        if let Ok(mut file) = archive.by_name(&entry)
+            && let Err(e) = std::io::copy(&mut file, &mut writer)
+        {
+            tracing::debug!("archive: streaming {} stopped: {}", entry, e);
+        }
+    });
+
+    let mut response = Response::new(Body::from_stream(ReaderStream::new(reader)));
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&mime) {
+        headers.insert(header::CONTENT_TYPE, value);
+    }
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(size));
+    response
+}
@@ -0,0 +1,224 @@
+use axum::{
+    extract::{MatchedPath, OriginalUri, Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use utoipa::ToSchema;
+
+use crate::helpers::access_control::{AccessControl, Peer};
+use crate::helpers::api_keys::Principal;
+use crate::helpers::env_helper::env_flag;
+use crate::helpers::quarantine::now_ms;
+
+/// Default log file in DATA_DIR; hidden, so never served.
+pub const AUDIT_FILE_NAME: &str = ".audit.jsonl";
+
+/// `key_id` of requests `auth` refused before a key was known, e.g. a wrong
+/// API key or an invalid bearer token.
+pub const UNKNOWN_KEY_ID: &str = "-";
+
+pub const DEFAULT_QUERY_LIMIT: usize = 100;
+pub const MAX_QUERY_LIMIT: usize = 1000;
+
+/// Log file, once configured. The lock also keeps appended lines whole.
+static LOG: LazyLock<Mutex<Option<PathBuf>>> = LazyLock::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditResult {
+    Ok,
+    /// Refused for scope, project restrictions or rate limits
+    Denied,
+    Failed,
+}
+
+impl AuditResult {
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            200..=399 => AuditResult::Ok,
+            401 | 403 | 429 => AuditResult::Denied,
+            _ => AuditResult::Failed,
+        }
+    }
+}
+
+/// One line of the audit log.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    /// Epoch milliseconds
+    pub timestamp: i64,
+    pub key_id: String,
+    /// `sub` claim, for bearer tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    pub client_ip: Option<String>,
+    /// Method and route, e.g. `DELETE /api/projects/:project_name`
+    pub action: String,
+    /// Path operated on; the stored run for uploads
+    pub target: String,
+    pub status: u16,
+    pub result: AuditResult,
+}
+
+/// Response extension naming what an operation changed when the request path
+/// does not, e.g. the run an upload created.
+#[derive(Debug, Clone)]
+pub struct AuditTarget(pub String);
+
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub key_id: Option<String>,
+    /// Substring of the action, e.g. `DELETE` or `/history/`
+    pub action: Option<String>,
+    /// Prefix of the target path
+    pub target: Option<String>,
+    pub result: Option<AuditResult>,
+    /// Epoch milliseconds, inclusive
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.key_id.as_ref().is_none_or(|k| *k == entry.key_id)
+            && self
+                .action
+                .as_ref()
+                .is_none_or(|a| entry.action.contains(a.as_str()))
+            && self
+                .target
+                .as_ref()
+                .is_none_or(|t| entry.target.starts_with(t.as_str()))
+            && self.result.is_none_or(|r| r == entry.result)
+            && self.since.is_none_or(|s| entry.timestamp >= s)
+            && self.until.is_none_or(|u| entry.timestamp <= u)
+    }
+}
+
+/// Log file from AUDIT_LOG_FILE, else `.audit.jsonl` in `data_dir`.
+/// `None` when AUDIT_LOG_ENABLED is false.
+pub fn path_from_env(data_dir: &Path) -> Option<PathBuf> {
+    if !env_flag("AUDIT_LOG_ENABLED", true) {
+        return None;
+    }
+    match std::env::var("AUDIT_LOG_FILE") {
+        Ok(file) if !file.trim().is_empty() => Some(PathBuf::from(file.trim())),
+        _ => Some(data_dir.join(AUDIT_FILE_NAME)),
+    }
+}
+
+pub fn configure(path: Option<PathBuf>) {
+    *LOG.lock().unwrap() = path;
+}
+
+pub fn log_path() -> Option<PathBuf> {
+    LOG.lock().unwrap().clone()
+}
+
+/// Whether GET and HEAD requests are recorded too (AUDIT_READS).
+fn record_reads() -> bool {
+    env_flag("AUDIT_READS", false)
+}
+
+/// Appends an entry to `path` as one JSON line.
+pub fn append_to(path: &Path, entry: &AuditEntry) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    let _guard = LOG.lock().unwrap();
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(&line)
+}
+
+/// Matching entries of the log at `path`, newest first.
+pub fn query(path: &Path, filter: &AuditFilter) -> std::io::Result<Vec<AuditEntry>> {
+    let limit = filter
+        .limit
+        .unwrap_or(DEFAULT_QUERY_LIMIT)
+        .clamp(1, MAX_QUERY_LIMIT);
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut newest = VecDeque::with_capacity(limit);
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        // A torn last line after a crash is skipped, not fatal
+        let Ok(entry) = serde_json::from_str::<AuditEntry>(&line) else {
+            continue;
+        };
+        if filter.matches(&entry) {
+            if newest.len() == limit {
+                newest.pop_front();
+            }
+            newest.push_back(entry);
+        }
+    }
+    Ok(newest.into_iter().rev().collect())
+}
+
+/// Middleware recording authenticated API operations. Wraps `auth`, which
+/// hands the key back as a response extension, so refusals are logged too;
+/// requests it rejected without a key are logged as `UNKNOWN_KEY_ID`.
+pub async fn audit(
+    State(access): State<Arc<AccessControl>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(path) = log_path() else {
+        return next.run(request).await;
+    };
+    let method = request.method().clone();
+    if (method == Method::GET || method == Method::HEAD) && !record_reads() {
+        return next.run(request).await;
+    }
+
+    let full_path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|uri| uri.path().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| full_path.clone());
+    let client_ip = Peer::of(request.extensions()).map(|peer| {
+        access
+            .client_ip(peer, request.headers())
+            .unwrap_or(peer.source())
+            .to_string()
+    });
+
+    let response = next.run(request).await;
+    let principal = response.extensions().get::<Principal>();
+    let status = response.status().as_u16();
+    let entry = AuditEntry {
+        timestamp: now_ms(),
+        key_id: principal.map_or(UNKNOWN_KEY_ID.to_string(), |p| p.key_id.clone()),
+        subject: principal.and_then(|p| p.subject.clone()),
+        client_ip,
+        action: format!("{} {}", method, route),
+        target: response
+            .extensions()
+            .get::<AuditTarget>()
+            .map(|t| t.0.clone())
+            .unwrap_or(full_path),
+        status,
+        result: AuditResult::from_status(status),
+    };
+    match tokio::task::spawn_blocking(move || append_to(&path, &entry)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => tracing::error!("audit: failed to write log: {}", e),
+        Err(e) => tracing::error!("audit: write panicked: {}", e),
+    }
+    response
+}
@@ -0,0 +1,177 @@
+use axum::{
+    extract::{OriginalUri, Request, State},
+    http::{HeaderMap, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ipnet::IpNet;
+use percent_encoding::percent_decode_str;
+use std::sync::Arc;
+
+use crate::helpers::access_control::{AccessControl, Peer};
+use crate::helpers::api_keys::{self, Principal, Scope};
+use crate::helpers::jwt;
+
+/// Scope an API request needs, from its method and path below `/api`.
+pub fn required_scope(method: &Method, path: &str) -> Scope {
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    match segments.as_slice() {
+        ["admin", ..] | ["usage"] => Scope::Admin,
+        ["reports", "upload"] => Scope::Upload,
+        ["projects", _, "settings"] if method != Method::GET => Scope::Admin,
+        ["projects", _, "quarantine", ..] if method != Method::GET => Scope::Admin,
+        [.., "history", "trim" | "remove" | "rebuild"] => Scope::Delete,
+        [.., "runs", _, "share"] => Scope::Read,
+        _ if method == Method::DELETE => Scope::Delete,
+        _ if method == Method::GET || method == Method::HEAD => Scope::Read,
+        _ => Scope::Admin,
+    }
+}
+
+/// Project and branch a path below `/api` is about, when it names them.
+pub fn path_target(path: &str) -> Option<(String, Option<String>)> {
+    let segments: Vec<String> = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(|s| percent_decode_str(s).decode_utf8_lossy().into_owned())
+        .collect();
+    match segments.as_slice() {
+        [first, project, rest @ ..] if first == "projects" => {
+            let branch = match rest {
+                [b, branch, ..] if b == "branches" => Some(branch.clone()),
+                _ => None,
+            };
+            Some((project.clone(), branch))
+        }
+        _ => None,
+    }
+}
+
+/// Whether a key may perform a request; upload targets are checked by the handler.
+pub fn authorize(principal: &Principal, method: &Method, path: &str) -> bool {
+    let scope = required_scope(method, path);
+    if !principal.has_scope(scope) {
+        return false;
+    }
+    match path_target(path) {
+        Some((project, branch)) => principal.allows(&project, branch.as_deref()),
+        // Server-wide endpoints would reach beyond the key's projects
+        None => scope == Scope::Upload || !principal.is_restricted(),
+    }
+}
+
+/// Resolves an `Authorization: Bearer` token or `X-API-Key` to a key.
+async fn authenticate(headers: &HeaderMap) -> Result<Principal, StatusCode> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+    match bearer {
+        Some(token) if jwt::is_enabled() => {
+            match tokio::task::spawn_blocking(move || jwt::authenticate(&token)).await {
+                Ok(Ok(principal)) => Ok(principal),
+                Ok(Err(e)) => {
+                    tracing::warn!("auth: bearer token rejected: {}", e);
+                    Err(StatusCode::UNAUTHORIZED)
+                }
+                Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+            }
+        }
+        _ => {
+            let secret = headers
+                .get("x-api-key")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default();
+            api_keys::authenticate(secret).ok_or(StatusCode::UNAUTHORIZED)
+        }
+    }
+}
+
+/// Middleware resolving `Authorization: Bearer` tokens or `X-API-Key` to a key, checking its scope and project
+/// restrictions and storing it as a [`Principal`] request extension. The principal is also
+/// attached to the response for [`audit`](crate::helpers::audit::audit).
+pub async fn auth(mut request: Request, next: Next) -> Result<Response, StatusCode> {
+    let principal = authenticate(request.headers()).await?;
+
+    let full_path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|uri| uri.path().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let path = full_path.strip_prefix("/api").unwrap_or(&full_path);
+    if !authorize(&principal, request.method(), path) {
+        tracing::warn!(
+            "auth: key '{}' denied {} {}",
+            principal.key_id,
+            request.method(),
+            full_path
+        );
+        let mut response = StatusCode::FORBIDDEN.into_response();
+        response.extensions_mut().insert(principal);
+        return Ok(response);
+    }
+
+    let method = request.method().clone();
+    let key_id = principal.key_id.clone();
+    let subject = principal.subject.clone();
+    request.extensions_mut().insert(principal.clone());
+    let mut response = next.run(request).await;
+    // Handed back for the audit log
+    response.extensions_mut().insert(principal);
+    match subject {
+        Some(sub) => tracing::info!(
+            "api: {} {} -> {} (token rule '{}', sub '{}')",
+            method,
+            full_path,
+            response.status().as_u16(),
+            key_id,
+            sub
+        ),
+        None => tracing::info!(
+            "api: {} {} -> {} (key '{}')",
+            method,
+            full_path,
+            response.status().as_u16(),
+            key_id
+        ),
+    }
+    Ok(response)
+}
+
+/// Who may scrape `/metrics`, which names every project: unrestricted admin
+/// keys, and clients in METRICS_ALLOWED_IPS.
+pub struct MetricsAccess {
+    pub access: Arc<AccessControl>,
+    pub allowed_ips: Vec<IpNet>,
+}
+
+impl MetricsAccess {
+    pub fn new(access: Arc<AccessControl>, allowed_ips: Vec<String>) -> Self {
+        Self {
+            access,
+            allowed_ips: AccessControl::compile_nets(allowed_ips),
+        }
+    }
+}
+
+/// Middleware guarding `/metrics` with [`MetricsAccess`].
+pub async fn metrics_auth(
+    State(metrics): State<Arc<MetricsAccess>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let listed = Peer::of(request.extensions()).is_some_and(|peer| {
+        metrics
+            .access
+            .is_listed(peer, request.headers(), &metrics.allowed_ips)
+    });
+    if !listed {
+        let principal = authenticate(request.headers()).await?;
+        if !principal.has_scope(Scope::Admin) || principal.is_restricted() {
+            tracing::warn!("auth: key '{}' denied /metrics", principal.key_id);
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+use crate::helpers::test_results::TestOutcome;
+
+/// Stack trace lines that take part in a failure's signature.
+const TRACE_LINES: usize = 5;
+
+/// Example tests kept per cluster.
+const EXAMPLES: usize = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ClusterExample {
+    pub history_id: String,
+    pub name: String,
+    /// Deep link into the run's report
+    pub url: String,
+}
+
+/// Failures sharing the same normalized message and top of stack trace.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FailureCluster {
+    /// Stable id of the signature, the same across runs
+    pub id: String,
+    /// Normalized message and trace the failures share
+    pub signature: String,
+    /// Message of the first failure, as reported
+    pub message: Option<String>,
+    pub count: usize,
+    pub examples: Vec<ClusterExample>,
+}
+
+fn is_id_token(token: &str) -> bool {
+    let compact: String = token.chars().filter(|c| *c != '-' && *c != '_').collect();
+    let digits = compact.chars().filter(|c| c.is_ascii_digit()).count();
+    if digits == 0 {
+        return false;
+    }
+    compact.to_ascii_lowercase().starts_with("0x")
+        || (compact.len() >= 8 && compact.chars().all(|c| c.is_ascii_hexdigit()))
+        || (compact.len() >= 12 && digits * 4 >= compact.len())
+}
+
+/// Replaces numbers, ids (hex, uuids, random tokens) and therefore timestamps
+/// with placeholders and collapses whitespace, so the same failure reads the
+/// same in every test and every run.
+pub fn normalize(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut token = String::new();
+    let flush = |token: &mut String, out: &mut String| {
+        if token.is_empty() {
+            return;
+        }
+        if is_id_token(token) {
+            out.push_str("<id>");
+        } else {
+            let mut in_digits = false;
+            for c in token.chars() {
+                if c.is_ascii_digit() {
+                    if !in_digits {
+                        out.push_str("<n>");
+                    }
+                    in_digits = true;
+                } else {
+                    out.push(c);
+                    in_digits = false;
+                }
+            }
+        }
+        token.clear();
+    };
+
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+            token.push(c);
+        } else {
+            flush(&mut token, &mut out);
+            out.push(c);
+        }
+    }
+    flush(&mut token, &mut out);
+
+    // UUIDs become `<id>-<id>-...` and dates `<n>-<n>-<n>`; fold both
+    for (from, to) in [
+        ("<id>-<id>", "<id>"),
+        ("<n>-<n>", "<n>"),
+        ("<n>:<n>", "<n>"),
+    ] {
+        while out.contains(from) {
+            out = out.replace(from, to);
+        }
+    }
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn signature(outcome: &TestOutcome) -> String {
+    let message = outcome
+        .message
+        .as_deref()
+        .map(normalize)
+        .unwrap_or_else(|| "(no message)".to_string());
+    let trace: Vec<String> = outcome
+        .trace
+        .as_deref()
+        .unwrap_or_default()
+        .lines()
+        .map(normalize)
+        .filter(|l| !l.is_empty())
+        .take(TRACE_LINES)
+        .collect();
+    if trace.is_empty() {
+        message
+    } else {
+        format!("{}\n{}", message, trace.join("\n"))
+    }
+}
+
+/// Groups the failed and broken tests of a run, largest cluster first.
+pub fn cluster_failures(outcomes: &[TestOutcome], run_url: &str) -> Vec<FailureCluster> {
+    let mut clusters: Vec<FailureCluster> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for outcome in outcomes.iter().filter(|o| o.is_failure()) {
+        let signature = signature(outcome);
+        let i = *index.entry(signature.clone()).or_insert_with(|| {
+            let id = Sha256::digest(signature.as_bytes());
+            clusters.push(FailureCluster {
+                id: id.iter().take(6).map(|b| format!("{:02x}", b)).collect(),
+                message: outcome.message.clone(),
+                signature,
+                count: 0,
+                examples: Vec::new(),
+            });
+            clusters.len() - 1
+        });
+        let cluster = &mut clusters[i];
+        cluster.count += 1;
+        if cluster.examples.len() < EXAMPLES {
+            cluster.examples.push(ClusterExample {
+                history_id: outcome.history_id.clone(),
+                name: outcome
+                    .full_name
+                    .clone()
+                    .unwrap_or_else(|| outcome.name.clone()),
+                url: outcome.link(run_url),
+            });
+        }
+    }
+    clusters.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.id.cmp(&b.id)));
+    clusters
+}
@@ -0,0 +1,276 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use utoipa::ToSchema;
+
+use crate::helpers::fs_helper::report_run_dirs;
+use crate::helpers::quarantine::Quarantine;
+use crate::helpers::test_results::{TestOutcome, load_outcomes};
+
+/// A run taking part in a comparison.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ComparedRun {
+    pub branch: String,
+    pub report_name: String,
+    pub run_id: u32,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ComparedTest {
+    pub history_id: String,
+    pub name: String,
+    pub full_name: Option<String>,
+    pub base_status: Option<String>,
+    pub head_status: Option<String>,
+    /// Failure message in the head run
+    pub message: Option<String>,
+    /// Deep link into the head run, or the base run for removed tests
+    pub url: String,
+    /// The test is on the project's quarantine list
+    pub quarantined: bool,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DurationChange {
+    pub history_id: String,
+    pub name: String,
+    pub base_ms: i64,
+    pub head_ms: i64,
+    /// Relative change, e.g. `1.5` for +150%
+    pub change: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Comparison {
+    pub base: ComparedRun,
+    pub head: ComparedRun,
+    /// Failing in head, not failing in base
+    pub new_failures: Vec<ComparedTest>,
+    /// Failing in base, passing in head
+    pub fixed: Vec<ComparedTest>,
+    pub still_failing: Vec<ComparedTest>,
+    /// Only in head
+    pub added: Vec<ComparedTest>,
+    /// Only in base
+    pub removed: Vec<ComparedTest>,
+    /// Largest relative changes first
+    pub duration_changes: Vec<DurationChange>,
+}
+
+/// Thresholds for reporting a duration change.
+pub struct DurationThreshold {
+    /// Minimum relative change, e.g. `0.5` for 50%
+    pub min_change: f64,
+    /// Minimum absolute change in milliseconds
+    pub min_delta_ms: i64,
+}
+
+/// Picks `run_id` or, when `None`, the latest run with test outcomes (older
+/// than `before` if given).
+pub fn select_run(
+    report_dir: &Path,
+    run_id: Option<u32>,
+    before: Option<u32>,
+) -> Result<(u32, Vec<TestOutcome>), String> {
+    let runs = report_run_dirs(report_dir);
+    match run_id {
+        Some(id) => {
+            let (_, dir) = runs
+                .iter()
+                .find(|(r, _)| *r == id)
+                .ok_or_else(|| format!("Run {} not found", id))?;
+            load_outcomes(dir)
+                .map(|o| (id, o))
+                .ok_or_else(|| format!("Run {} has no stored test outcomes", id))
+        }
+        None => runs
+            .iter()
+            .rev()
+            .filter(|(id, _)| before.is_none_or(|b| *id < b))
+            .find_map(|(id, dir)| load_outcomes(dir).map(|o| (*id, o)))
+            .ok_or_else(|| "No run with stored test outcomes to compare".to_string()),
+    }
+}
+
+fn compared(
+    base: Option<&TestOutcome>,
+    head: Option<&TestOutcome>,
+    base_run: &ComparedRun,
+    head_run: &ComparedRun,
+) -> ComparedTest {
+    let any = head.or(base).expect("test present in at least one run");
+    let url = match head {
+        Some(h) => h.link(&head_run.url),
+        None => any.link(&base_run.url),
+    };
+    ComparedTest {
+        history_id: any.history_id.clone(),
+        name: any.name.clone(),
+        full_name: any.full_name.clone(),
+        base_status: base.map(|t| t.status.clone()),
+        head_status: head.map(|t| t.status.clone()),
+        message: head.and_then(|t| t.message.clone()),
+        url,
+        quarantined: false,
+    }
+}
+
+pub fn compare(
+    base_run: ComparedRun,
+    base: &[TestOutcome],
+    head_run: ComparedRun,
+    head: &[TestOutcome],
+    threshold: &DurationThreshold,
+) -> Comparison {
+    let base_tests: BTreeMap<&str, &TestOutcome> =
+        base.iter().map(|t| (t.history_id.as_str(), t)).collect();
+    let head_tests: BTreeMap<&str, &TestOutcome> =
+        head.iter().map(|t| (t.history_id.as_str(), t)).collect();
+
+    let mut out = Comparison {
+        new_failures: Vec::new(),
+        fixed: Vec::new(),
+        still_failing: Vec::new(),
+        added: Vec::new(),
+        removed: Vec::new(),
+        duration_changes: Vec::new(),
+        base: base_run,
+        head: head_run,
+    };
+
+    for (id, h) in &head_tests {
+        let Some(b) = base_tests.get(id) else {
+            out.added
+                .push(compared(None, Some(h), &out.base, &out.head));
+            continue;
+        };
+        let test = || compared(Some(b), Some(h), &out.base, &out.head);
+        match (b.is_failure(), h.is_failure()) {
+            (false, true) => out.new_failures.push(test()),
+            (true, true) => out.still_failing.push(test()),
+            (true, false) if h.status == "passed" => out.fixed.push(test()),
+            _ => {}
+        }
+
+        if b.status == "passed"
+            && h.status == "passed"
+            && let (Some(base_ms), Some(head_ms)) = (b.duration_ms(), h.duration_ms())
+            && base_ms > 0
+        {
+            let change = (head_ms - base_ms) as f64 / base_ms as f64;
+            if change.abs() >= threshold.min_change
+                && (head_ms - base_ms).abs() >= threshold.min_delta_ms
+            {
+                out.duration_changes.push(DurationChange {
+                    history_id: h.history_id.clone(),
+                    name: h.name.clone(),
+                    base_ms,
+                    head_ms,
+                    change,
+                });
+            }
+        }
+    }
+    for (id, b) in &base_tests {
+        if !head_tests.contains_key(id) {
+            out.removed
+                .push(compared(Some(b), None, &out.base, &out.head));
+        }
+    }
+    out.duration_changes
+        .sort_by(|a, b| b.change.abs().total_cmp(&a.change.abs()));
+    out
+}
+
+fn markdown_section(out: &mut String, title: &str, tests: &[ComparedTest]) {
+    if tests.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "\n**{}** ({})\n", title, tests.len());
+    for test in tests {
+        let name = match &test.full_name {
+            Some(full_name) if test.quarantined => format!("{} (quarantined)", full_name),
+            Some(full_name) => full_name.clone(),
+            None if test.quarantined => format!("{} (quarantined)", test.name),
+            None => test.name.clone(),
+        };
+        match &test.message {
+            Some(message) => {
+                let first_line = message.lines().next().unwrap_or_default();
+                let _ = writeln!(out, "- [{}]({}): {}", name, test.url, first_line);
+            }
+            None => {
+                let _ = writeln!(out, "- [{}]({})", name, test.url);
+            }
+        }
+    }
+}
+
+impl Comparison {
+    /// Marks the tests on the project's quarantine list.
+    pub fn mark_quarantined(&mut self, quarantine: &Quarantine) {
+        for test in self
+            .new_failures
+            .iter_mut()
+            .chain(self.fixed.iter_mut())
+            .chain(self.still_failing.iter_mut())
+            .chain(self.added.iter_mut())
+            .chain(self.removed.iter_mut())
+        {
+            test.quarantined = quarantine
+                .find(&test.history_id, test.full_name.as_deref(), &test.name)
+                .is_some();
+        }
+    }
+
+    /// Renders the comparison for a CI comment. Links are relative to the server.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "### [{}/{} #{}]({}) vs [{}/{} #{}]({})",
+            self.head.branch,
+            self.head.report_name,
+            self.head.run_id,
+            self.head.url,
+            self.base.branch,
+            self.base.report_name,
+            self.base.run_id,
+            self.base.url
+        );
+        let _ = writeln!(
+            out,
+            "\n{} new failures, {} fixed, {} still failing, {} added, {} removed",
+            self.new_failures.len(),
+            self.fixed.len(),
+            self.still_failing.len(),
+            self.added.len(),
+            self.removed.len()
+        );
+        markdown_section(&mut out, "New failures", &self.new_failures);
+        markdown_section(&mut out, "Fixed", &self.fixed);
+        markdown_section(&mut out, "Still failing", &self.still_failing);
+        markdown_section(&mut out, "Added", &self.added);
+        markdown_section(&mut out, "Removed", &self.removed);
+        if !self.duration_changes.is_empty() {
+            let _ = writeln!(
+                out,
+                "\n**Duration changes** ({})\n",
+                self.duration_changes.len()
+            );
+            for change in &self.duration_changes {
+                let _ = writeln!(
+                    out,
+                    "- {}: {} ms → {} ms ({:+.0}%)",
+                    change.name,
+                    change.base_ms,
+                    change.head_ms,
+                    change.change * 100.0
+                );
+            }
+        }
+        out
+    }
+}
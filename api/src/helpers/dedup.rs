@@ -0,0 +1,203 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use utoipa::ToSchema;
+
+use crate::helpers::env_helper::env_flag;
+
+/// Name of the content-addressed store directory inside DATA_DIR.
+/// It must live on the same volume as the reports so hardlinks work.
+pub const STORE_DIR_NAME: &str = ".cas";
+
+#[derive(Debug, Default, Serialize, ToSchema, Clone)]
+pub struct DedupResult {
+    pub files_scanned: u64,
+    pub files_linked: u64,
+    pub bytes_saved: u64,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema, Clone)]
+pub struct DedupStats {
+    /// Distinct blobs held in the store
+    pub entries: u64,
+    /// Bytes actually occupied by the store blobs
+    pub stored_bytes: u64,
+    /// Bytes the report files would occupy without deduplication
+    pub referenced_bytes: u64,
+    /// Bytes avoided by sharing blobs between report files
+    pub saved_bytes: u64,
+    /// Blobs no longer referenced by any report (removed by gc)
+    pub orphaned_entries: u64,
+}
+
+/// Returns true unless DEDUP_ENABLED is explicitly set to a false value.
+pub fn dedup_enabled() -> bool {
+    env_flag("DEDUP_ENABLED", true)
+}
+
+pub fn store_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join(STORE_DIR_NAME)
+}
+
+fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn blob_path(store: &Path, hash: &str) -> PathBuf {
+    store.join(&hash[..2]).join(&hash[2..])
+}
+
+/// Replaces `path` with a hardlink to `blob` without a window where `path` is missing.
+fn relink(blob: &Path, path: &Path) -> std::io::Result<()> {
+    let tmp = path.with_file_name(format!(
+        ".{}.cas-tmp",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let _ = std::fs::remove_file(&tmp);
+    std::fs::hard_link(blob, &tmp)?;
+    std::fs::rename(&tmp, path)
+}
+
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let Ok(ft) = entry.file_type() else { continue };
+            if ft.is_dir() {
+                collect_files(&entry.path(), out);
+            } else if ft.is_file() {
+                out.push(entry.path());
+            }
+        }
+    }
+}
+
+/// Hardlinks every file under `run_dir` against the content-addressed store.
+///
+/// The link count of a blob is its reference count: the store holds one link
+/// and every report file sharing the content holds another. Deleting a run
+/// therefore only drops its own links, and `gc` later removes blobs whose
+/// count fell back to one.
+///
+/// Files in a deduplicated run are shared inodes, so they must never be
+/// rewritten in place; replace them with a new file and rename instead.
+pub fn dedup_dir(data_dir: &Path, run_dir: &Path) -> Result<DedupResult, String> {
+    let store = store_dir(data_dir);
+    std::fs::create_dir_all(&store)
+        .map_err(|e| format!("Failed to create dedup store {:?}: {}", store, e))?;
+
+    let mut files = Vec::new();
+    collect_files(run_dir, &mut files);
+
+    let mut result = DedupResult::default();
+    for path in files {
+        let meta = match std::fs::metadata(&path) {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+        if meta.len() == 0 {
+            continue;
+        }
+        result.files_scanned += 1;
+
+        let hash = hash_file(&path).map_err(|e| format!("Failed to hash {:?}: {}", path, e))?;
+        let blob = blob_path(&store, &hash);
+
+        match std::fs::metadata(&blob) {
+            Ok(blob_meta) => {
+                if blob_meta.ino() == meta.ino() && blob_meta.dev() == meta.dev() {
+                    continue;
+                }
+                if blob_meta.len() != meta.len() {
+                    tracing::warn!("dedup: size mismatch for blob {}, skipping", hash);
+                    continue;
+                }
+                match relink(&blob, &path) {
+                    Ok(_) => {
+                        result.files_linked += 1;
+                        result.bytes_saved += meta.len();
+                    }
+                    Err(e) => tracing::warn!("dedup: failed to link {:?}: {}", path, e),
+                }
+            }
+            Err(_) => {
+                if let Some(parent) = blob.parent() {
+                    std::fs::create_dir_all(parent)
+                        .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+                }
+                match std::fs::hard_link(&path, &blob) {
+                    Ok(_) => {}
+                    // Another upload stored the same content concurrently; link to it next time.
+                    Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+                    Err(e) => {
+                        return Err(format!(
+                            "Failed to add {:?} to dedup store (is {:?} on the same volume?): {}",
+                            path, store, e
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+fn collect_blobs(store: &Path) -> Vec<(PathBuf, std::fs::Metadata)> {
+    let mut blobs = Vec::new();
+    if let Ok(prefixes) = std::fs::read_dir(store) {
+        for prefix in prefixes.flatten() {
+            if let Ok(entries) = std::fs::read_dir(prefix.path()) {
+                for entry in entries.flatten() {
+                    if let Ok(meta) = entry.metadata()
+                        && meta.is_file()
+                    {
+                        blobs.push((entry.path(), meta));
+                    }
+                }
+            }
+        }
+    }
+    blobs
+}
+
+/// Summarizes the store: how many bytes it holds and how many it saves.
+pub fn stats(data_dir: &Path) -> DedupStats {
+    let mut stats = DedupStats::default();
+    for (_, meta) in collect_blobs(&store_dir(data_dir)) {
+        let refs = meta.nlink().saturating_sub(1);
+        if refs == 0 {
+            stats.orphaned_entries += 1;
+        }
+        stats.entries += 1;
+        stats.stored_bytes += meta.len();
+        stats.referenced_bytes += meta.len() * refs;
+        stats.saved_bytes += meta.len() * refs.saturating_sub(1);
+    }
+    stats
+}
+
+/// Removes blobs that are no longer referenced by any report file.
+/// Returns the number of blobs and bytes freed.
+pub fn gc(data_dir: &Path) -> (u64, u64) {
+    let mut removed = 0;
+    let mut freed = 0;
+    for (path, meta) in collect_blobs(&store_dir(data_dir)) {
+        if meta.nlink() <= 1 && std::fs::remove_file(&path).is_ok() {
+            removed += 1;
+            freed += meta.len();
+        }
+    }
+    (removed, freed)
+}
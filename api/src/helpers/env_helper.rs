@@ -0,0 +1,14 @@
+/// Reads a boolean flag from the environment, falling back to `default`
+/// when the variable is unset or not recognized.
+pub fn env_flag(name: &str, default: bool) -> bool {
+    match std::env::var(name)
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+        .as_str()
+    {
+        "1" | "true" | "yes" | "on" => true,
+        "0" | "false" | "no" | "off" => false,
+        _ => default,
+    }
+}
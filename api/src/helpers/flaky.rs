@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use utoipa::ToSchema;
+
+use crate::helpers::fs_helper::report_run_dirs;
+use crate::helpers::quarantine::Quarantine;
+use crate::helpers::test_results::load_outcomes;
+
+/// Outcomes kept per flaky test, newest last.
+const LAST_OUTCOMES: usize = 5;
+
+/// Pass/fail switches needed before a test counts as flaky. A single switch
+/// is a regression or a fix, not flakiness.
+pub const DEFAULT_MIN_FLIPS: usize = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RunOutcome {
+    pub run_id: u32,
+    pub status: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FlakyTest {
+    pub history_id: String,
+    pub name: String,
+    pub full_name: Option<String>,
+    /// Runs in the window where the test passed or failed
+    pub runs: usize,
+    pub failures: usize,
+    /// Switches between passing and failing (failed or broken)
+    pub flips: usize,
+    /// Flips over the number of possible switches (`runs - 1`)
+    pub flip_rate: f64,
+    /// Latest outcomes, oldest first
+    pub last_outcomes: Vec<RunOutcome>,
+    /// The test is on the project's quarantine list
+    #[serde(default)]
+    pub quarantined: bool,
+}
+
+/// Number of latest runs analysed, from FLAKY_WINDOW (default 10).
+pub fn flaky_window() -> usize {
+    std::env::var("FLAKY_WINDOW")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|n| *n > 1)
+        .unwrap_or(10)
+}
+
+struct Observed {
+    name: String,
+    full_name: Option<String>,
+    outcomes: Vec<RunOutcome>,
+}
+
+/// Finds tests that switched between pass and fail at least `min_flips` times
+/// over the last `window` runs of a report that have test outcomes, most
+/// flaky first. Skipped and unknown outcomes are ignored; quarantined tests
+/// are listed but marked.
+pub fn detect(
+    report_dir: &Path,
+    window: usize,
+    min_flips: usize,
+    quarantine: &Quarantine,
+) -> Vec<FlakyTest> {
+    let runs: Vec<_> = report_run_dirs(report_dir)
+        .into_iter()
+        .rev()
+        .filter_map(|(id, dir)| load_outcomes(&dir).map(|o| (id, o)))
+        .take(window)
+        .collect();
+
+    let mut tests: BTreeMap<String, Observed> = BTreeMap::new();
+    for (run_id, outcomes) in runs.into_iter().rev() {
+        for outcome in outcomes {
+            if outcome.status != "passed" && !outcome.is_failure() {
+                continue;
+            }
+            let observed = tests
+                .entry(outcome.history_id.clone())
+                .or_insert_with(|| Observed {
+                    name: outcome.name.clone(),
+                    full_name: outcome.full_name.clone(),
+                    outcomes: Vec::new(),
+                });
+            observed.outcomes.push(RunOutcome {
+                run_id,
+                status: outcome.status,
+            });
+        }
+    }
+
+    let mut flaky: Vec<FlakyTest> = tests
+        .into_iter()
+        .filter_map(|(history_id, observed)| {
+            let failed: Vec<bool> = observed
+                .outcomes
+                .iter()
+                .map(|o| o.status != "passed")
+                .collect();
+            let flips = failed.windows(2).filter(|w| w[0] != w[1]).count();
+            if flips < min_flips.max(1) {
+                return None;
+            }
+            let runs = failed.len();
+            let skip = runs.saturating_sub(LAST_OUTCOMES);
+            Some(FlakyTest {
+                quarantined: quarantine
+                    .find(&history_id, observed.full_name.as_deref(), &observed.name)
+                    .is_some(),
+                history_id,
+                name: observed.name,
+                full_name: observed.full_name,
+                runs,
+                failures: failed.iter().filter(|f| **f).count(),
+                flips,
+                flip_rate: flips as f64 / (runs - 1) as f64,
+                last_outcomes: observed.outcomes.into_iter().skip(skip).collect(),
+            })
+        })
+        .collect();
+    flaky.sort_by(|a, b| {
+        b.flip_rate
+            .total_cmp(&a.flip_rate)
+            .then_with(|| a.history_id.cmp(&b.history_id))
+    });
+    flaky
+}
@@ -1,6 +1,23 @@
 use std::path::{Path, PathBuf};
 use tokio::fs;
 
+use crate::helpers::dedup::dedup_enabled;
+use crate::helpers::precompress::precompress_enabled;
+use crate::helpers::share_link::SHARE_PREFIX;
+
+/// Top-level paths the server answers itself; projects named like them could
+/// be stored but never viewed.
+pub const RESERVED_PROJECT_NAMES: &[&str] = &[
+    "api",
+    "api-docs",
+    "login",
+    "logout",
+    "manifest.json",
+    "metrics",
+    SHARE_PREFIX,
+    "swagger-ui",
+];
+
 /// Recursively finds the directory containing allure result JSON files.
 /// Handles cases where zip contains nested folders like allure-results/allure-results/*.json
 pub async fn find_results_dir(dir: &PathBuf) -> PathBuf {
@@ -129,6 +146,11 @@ pub fn validate_path_segment(input: &str, field: &str) -> Result<String, String>
         return Err(format!("{} must not contain '..'", field));
     }
 
+    // Names starting with a dot are the server's own, e.g. the dedup store
+    if trimmed.starts_with('.') {
+        return Err(format!("{} must not start with '.'", field));
+    }
+
     let allowed = |c: char| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-';
     if !trimmed.chars().all(allowed) {
         return Err(format!(
@@ -140,6 +162,18 @@ pub fn validate_path_segment(input: &str, field: &str) -> Result<String, String>
     Ok(trimmed.to_string())
 }
 
+/// Validates the name of a project being created, by upload or relocation.
+pub fn validate_project_name(input: &str) -> Result<String, String> {
+    let name = validate_path_segment(input, "project_name")?;
+    if RESERVED_PROJECT_NAMES.contains(&name.as_str()) {
+        return Err(format!(
+            "project_name '{}' is reserved for the server's own paths",
+            name
+        ));
+    }
+    Ok(name)
+}
+
 /// Atomically allocates the next sequential ID by creating the directory.
 /// Retries on conflict to avoid race conditions.
 pub async fn allocate_next_id_dir(parent_dir: &PathBuf) -> Result<(u32, PathBuf), String> {
@@ -157,3 +191,161 @@ pub async fn allocate_next_id_dir(parent_dir: &PathBuf) -> Result<(u32, PathBuf)
         }
     }
 }
+
+/// Hidden directory inside a run holding data the service keeps about the run
+/// (retained results, summaries). Not served statically and not archived.
+pub const RUN_META_DIR: &str = ".meta";
+
+/// Returns the configured DATA_DIR where reports are stored.
+pub fn data_dir() -> PathBuf {
+    PathBuf::from(std::env::var("DATA_DIR").unwrap_or_else(|_| "../data".to_string()))
+}
+
+/// How files of a run are stored once written, by uploads and rehydration.
+#[derive(Debug, Clone, Copy)]
+pub struct StorageOptions {
+    /// Write .gz/.br siblings (PRECOMPRESS_ENABLED)
+    pub precompress: bool,
+    /// Hardlink identical files against the store (DEDUP_ENABLED)
+    pub dedup: bool,
+}
+
+impl StorageOptions {
+    pub fn from_env() -> Self {
+        Self {
+            precompress: precompress_enabled(),
+            dedup: dedup_enabled(),
+        }
+    }
+}
+
+/// A single uploaded run on disk: `{project}/{branch}/{report}[/raw]/{id}`.
+#[derive(Debug, Clone)]
+pub struct RunLocation {
+    pub project: String,
+    pub branch: String,
+    pub report: String,
+    pub raw: bool,
+    pub id: u32,
+    pub path: PathBuf,
+}
+
+fn visible_subdirs(dir: &Path) -> Vec<(String, PathBuf)> {
+    let mut out = Vec::new();
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') {
+                continue;
+            }
+            if entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false) {
+                out.push((name, entry.path()));
+            }
+        }
+    }
+    out
+}
+
+fn numeric_subdirs(dir: &Path) -> Vec<(u32, PathBuf)> {
+    visible_subdirs(dir)
+        .into_iter()
+        .filter_map(|(name, path)| name.parse::<u32>().ok().map(|id| (id, path)))
+        .collect()
+}
+
+/// Lists every run directory under `data_dir`, including raw runs.
+/// Hidden directories (dedup store, metadata) are skipped.
+pub fn list_run_dirs(data_dir: &Path) -> Vec<RunLocation> {
+    let mut runs = Vec::new();
+    for (project, project_path) in visible_subdirs(data_dir) {
+        for (branch, branch_path) in visible_subdirs(&project_path) {
+            for (report, report_path) in visible_subdirs(&branch_path) {
+                if report.parse::<u32>().is_ok() {
+                    continue;
+                }
+                for (id, path) in numeric_subdirs(&report_path) {
+                    runs.push(RunLocation {
+                        project: project.clone(),
+                        branch: branch.clone(),
+                        report: report.clone(),
+                        raw: false,
+                        id,
+                        path,
+                    });
+                }
+                for (id, path) in numeric_subdirs(&report_path.join("raw")) {
+                    runs.push(RunLocation {
+                        project: project.clone(),
+                        branch: branch.clone(),
+                        report: report.clone(),
+                        raw: true,
+                        id,
+                        path,
+                    });
+                }
+            }
+        }
+    }
+    runs
+}
+
+impl RunLocation {
+    /// Builds the location of a run after validating every path segment.
+    pub fn resolve(
+        data_dir: &Path,
+        project: &str,
+        branch: &str,
+        report: &str,
+        raw: bool,
+        id: u32,
+    ) -> Result<Self, String> {
+        let project = validate_path_segment(project, "project_name")?;
+        let branch = validate_path_segment(branch, "branch")?;
+        let report = validate_path_segment(report, "report_name")?;
+
+        let mut path = data_dir.join(&project).join(&branch).join(&report);
+        if raw {
+            path.push("raw");
+        }
+        path.push(id.to_string());
+
+        Ok(Self {
+            project,
+            branch,
+            report,
+            raw,
+            id,
+            path,
+        })
+    }
+}
+
+/// Builds a report directory (`{project}/{branch}/{report}`) after validating every segment.
+pub fn resolve_report_dir(
+    data_dir: &Path,
+    project: &str,
+    branch: &str,
+    report: &str,
+) -> Result<PathBuf, String> {
+    Ok(data_dir
+        .join(validate_path_segment(project, "project_name")?)
+        .join(validate_path_segment(branch, "branch")?)
+        .join(validate_path_segment(report, "report_name")?))
+}
+
+/// Report directories of a branch directory, by name.
+pub fn branch_report_dirs(branch_dir: &Path) -> Vec<(String, PathBuf)> {
+    let mut reports: Vec<_> = visible_subdirs(branch_dir)
+        .into_iter()
+        .filter(|(name, _)| name.parse::<u32>().is_err())
+        .collect();
+    reports.sort();
+    reports
+}
+
+/// Allure runs of a report directory, oldest (lowest id) first.
+pub fn report_run_dirs(report_dir: &Path) -> Vec<(u32, PathBuf)> {
+    let mut runs = numeric_subdirs(report_dir);
+    runs.sort_by_key(|(id, _)| *id);
+    runs
+}
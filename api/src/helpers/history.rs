@@ -0,0 +1,287 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use utoipa::ToSchema;
+
+use crate::helpers::allure_generator::generate_report;
+use crate::helpers::env_helper::env_flag;
+use crate::helpers::fs_helper::RUN_META_DIR;
+use crate::helpers::quarantine::untag_results;
+
+pub const HISTORY_FILE_NAME: &str = "history.jsonl";
+
+/// Subdirectory of a run's metadata directory holding the uploaded allure-results.
+pub const RESULTS_DIR_NAME: &str = "results";
+
+/// Scratch directory used while rebuilding history.
+const REBUILD_DIR_NAME: &str = ".history-rebuild";
+
+/// One line of `history.jsonl`, summarized.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HistoryEntrySummary {
+    /// Zero-based position in the file, oldest first
+    pub index: usize,
+    pub uuid: Option<String>,
+    pub name: Option<String>,
+    /// Epoch milliseconds
+    pub timestamp: Option<i64>,
+    pub url: Option<String>,
+    pub tests: usize,
+    /// Test count per status (passed, failed, broken, skipped, unknown)
+    pub statuses: BTreeMap<String, usize>,
+    /// Full entry, only included when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry: Option<Value>,
+}
+
+pub fn retain_results_enabled() -> bool {
+    env_flag("RETAIN_RESULTS", true)
+}
+
+pub fn history_path(report_dir: &Path) -> PathBuf {
+    report_dir.join(HISTORY_FILE_NAME)
+}
+
+pub fn retained_results_dir(run_dir: &Path) -> PathBuf {
+    run_dir.join(RUN_META_DIR).join(RESULTS_DIR_NAME)
+}
+
+/// Reads `history.jsonl` as raw lines. Lines that are not valid JSON are kept
+/// as-is so rewriting the file never loses data it doesn't understand.
+pub fn read_lines(report_dir: &Path) -> Result<Vec<String>, String> {
+    match std::fs::read_to_string(history_path(report_dir)) {
+        Ok(content) => Ok(content
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| l.to_string())
+            .collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("Failed to read history: {}", e)),
+    }
+}
+
+/// Replaces `history.jsonl` atomically. Callers must hold the report's history lock.
+pub fn write_lines(report_dir: &Path, lines: &[String]) -> Result<(), String> {
+    let path = history_path(report_dir);
+    let tmp = report_dir.join(format!(".{}.tmp", HISTORY_FILE_NAME));
+    let mut content = lines.join("\n");
+    if !content.is_empty() {
+        content.push('\n');
+    }
+    std::fs::write(&tmp, content)
+        .and_then(|_| std::fs::rename(&tmp, &path))
+        .map_err(|e| format!("Failed to write history: {}", e))
+}
+
+pub fn summarize(index: usize, line: &str, full: bool) -> HistoryEntrySummary {
+    let value: Option<Value> = serde_json::from_str(line).ok();
+    let get_str = |key: &str| {
+        value
+            .as_ref()
+            .and_then(|v| v.get(key))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+    };
+
+    let mut statuses = BTreeMap::new();
+    let mut tests = 0;
+    if let Some(results) = value
+        .as_ref()
+        .and_then(|v| v.get("testResults"))
+        .and_then(|v| v.as_object())
+    {
+        for result in results.values() {
+            tests += 1;
+            let status = result
+                .get("status")
+                .and_then(|s| s.as_str())
+                .unwrap_or("unknown");
+            *statuses.entry(status.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    HistoryEntrySummary {
+        index,
+        uuid: get_str("uuid"),
+        name: get_str("name"),
+        timestamp: value
+            .as_ref()
+            .and_then(|v| v.get("timestamp"))
+            .and_then(|v| v.as_i64()),
+        url: get_str("url"),
+        tests,
+        statuses,
+        entry: if full { value } else { None },
+    }
+}
+
+fn line_uuid(line: &str) -> Option<String> {
+    serde_json::from_str::<Value>(line)
+        .ok()?
+        .get("uuid")?
+        .as_str()
+        .map(|s| s.to_string())
+}
+
+/// Keeps only the last `keep` entries. Returns how many were removed.
+pub fn trim(report_dir: &Path, keep: usize) -> Result<usize, String> {
+    let lines = read_lines(report_dir)?;
+    if lines.len() <= keep {
+        return Ok(0);
+    }
+    let removed = lines.len() - keep;
+    write_lines(report_dir, &lines[removed..])?;
+    Ok(removed)
+}
+
+/// Removes the entries whose `uuid` is listed. Returns how many were removed.
+pub fn remove_entries(report_dir: &Path, uuids: &[String]) -> Result<usize, String> {
+    let lines = read_lines(report_dir)?;
+    let kept: Vec<String> = lines
+        .iter()
+        .filter(|l| !line_uuid(l).is_some_and(|u| uuids.contains(&u)))
+        .cloned()
+        .collect();
+    let removed = lines.len() - kept.len();
+    if removed > 0 {
+        write_lines(report_dir, &kept)?;
+    }
+    Ok(removed)
+}
+
+/// Deletes the history file. Returns false if there was none.
+pub fn reset(report_dir: &Path) -> Result<bool, String> {
+    match std::fs::remove_file(history_path(report_dir)) {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(format!("Failed to delete history: {}", e)),
+    }
+}
+
+/// Seeds an empty report's history with a copy of `base_report_dir`'s history.
+/// Only complete JSON lines are copied, so a base file that is being appended to
+/// concurrently is read safely without taking its lock. Callers must hold the
+/// target report's lock. Returns the number of entries copied.
+pub fn seed_from(report_dir: &Path, base_report_dir: &Path) -> Result<usize, String> {
+    let lines: Vec<String> = read_lines(base_report_dir)?
+        .into_iter()
+        .filter(|l| serde_json::from_str::<Value>(l).is_ok())
+        .collect();
+    if lines.is_empty() {
+        return Ok(0);
+    }
+    write_lines(report_dir, &lines)?;
+    Ok(lines.len())
+}
+
+/// Latest `stop` (or `start`) timestamp among the result files, i.e. when the run finished.
+fn results_finished_at(results_dir: &Path) -> Option<i64> {
+    let mut latest: Option<i64> = None;
+    for entry in std::fs::read_dir(results_dir).ok()?.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.ends_with("-result.json") {
+            continue;
+        }
+        let Some(value) = std::fs::read(entry.path())
+            .ok()
+            .and_then(|b| serde_json::from_slice::<Value>(&b).ok())
+        else {
+            continue;
+        };
+        if let Some(ts) = value
+            .get("stop")
+            .or_else(|| value.get("start"))
+            .and_then(|v| v.as_i64())
+        {
+            latest = Some(latest.map_or(ts, |l| l.max(ts)));
+        }
+    }
+    latest
+}
+
+fn copy_dir(src: &Path, dest: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dest)?;
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Rebuilds `history.jsonl` by regenerating every run with retained results,
+/// oldest run first, into a fresh history file. Each new entry gets the time the
+/// run originally finished and the run's URL. Callers must hold the history lock.
+///
+/// `runs` are `(run_id, run_dir, url)` tuples. Returns the number of runs replayed.
+pub fn rebuild(report_dir: &Path, runs: &[(u32, PathBuf, String)]) -> Result<usize, String> {
+    let work = report_dir.join(REBUILD_DIR_NAME);
+    let _ = std::fs::remove_dir_all(&work);
+    std::fs::create_dir_all(&work).map_err(|e| format!("Failed to create {:?}: {}", work, e))?;
+    let abs_work = std::fs::canonicalize(&work).map_err(|e| e.to_string())?;
+    let new_history = abs_work.join(HISTORY_FILE_NAME);
+
+    let config = serde_json::json!({
+        "historyPath": new_history.to_string_lossy(),
+        "appendHistory": true
+    });
+    std::fs::write(
+        abs_work.join("allurerc.json"),
+        serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?,
+    )
+    .map_err(|e| format!("Failed to write rebuild config: {}", e))?;
+
+    let mut sorted: Vec<&(u32, PathBuf, String)> = runs.iter().collect();
+    sorted.sort_by_key(|(id, _, _)| *id);
+
+    let mut replayed = 0;
+    let result = (|| {
+        for (id, run_dir, url) in sorted {
+            let results = retained_results_dir(run_dir);
+            if !results.is_dir() {
+                continue;
+            }
+            let input = abs_work.join("input");
+            let output = abs_work.join("output");
+            let _ = std::fs::remove_dir_all(&input);
+            let _ = std::fs::remove_dir_all(&output);
+            copy_dir(&results, &input).map_err(|e| format!("Failed to stage run {}: {}", id, e))?;
+            untag_results(&input);
+
+            let before = read_lines(&abs_work)?.len();
+            generate_report(&input, &output, &abs_work)
+                .map_err(|e| format!("Run {}: {}", id, e))?;
+
+            let mut lines = read_lines(&abs_work)?;
+            if lines.len() > before
+                && let Some(last) = lines.last_mut()
+                && let Ok(mut value) = serde_json::from_str::<Value>(last)
+                && let Some(obj) = value.as_object_mut()
+            {
+                if let Some(ts) = results_finished_at(&results) {
+                    obj.insert("timestamp".into(), Value::from(ts));
+                }
+                obj.insert("url".into(), Value::from(url.as_str()));
+                *last = value.to_string();
+                write_lines(&abs_work, &lines)?;
+            }
+            replayed += 1;
+        }
+        Ok::<(), String>(())
+    })();
+
+    // Never replace the existing history with nothing
+    let result = result.and_then(|_| {
+        if replayed == 0 {
+            return Err("No runs of this report have retained results".to_string());
+        }
+        write_lines(report_dir, &read_lines(&abs_work)?)
+    });
+    let _ = std::fs::remove_dir_all(&work);
+    result.map(|_| replayed)
+}
@@ -0,0 +1,123 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::helpers::metrics;
+
+/// Lock file created next to `history.jsonl` in the report directory.
+pub const LOCK_FILE_NAME: &str = ".history.lock";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Exclusive lock over a report's `history.jsonl` and `allurerc.json`.
+///
+/// Backed by an OS file lock so it also serializes server processes sharing
+/// the data volume. Released when dropped.
+pub struct HistoryLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl Drop for HistoryLock {
+    fn drop(&mut self) {
+        if let Err(e) = self.file.unlock() {
+            tracing::warn!("history lock: failed to release {:?}: {}", self.path, e);
+        }
+    }
+}
+
+/// Maximum time to wait for the lock, from HISTORY_LOCK_TIMEOUT_SECS (default 600).
+fn lock_timeout() -> Duration {
+    let secs = std::env::var("HISTORY_LOCK_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(600);
+    Duration::from_secs(secs)
+}
+
+pub fn describe_metrics() {
+    metrics::describe(
+        "allure_host_history_lock_waits_total",
+        "counter",
+        "History lock acquisitions that had to wait for another upload",
+    );
+    metrics::describe(
+        "allure_host_history_lock_wait_seconds_total",
+        "counter",
+        "Total time spent waiting for history locks",
+    );
+    metrics::describe(
+        "allure_host_history_lock_timeouts_total",
+        "counter",
+        "History lock acquisitions that gave up after HISTORY_LOCK_TIMEOUT_SECS",
+    );
+    metrics::counter_add("allure_host_history_lock_waits_total", &[], 0.0);
+    metrics::counter_add("allure_host_history_lock_wait_seconds_total", &[], 0.0);
+    metrics::counter_add("allure_host_history_lock_timeouts_total", &[], 0.0);
+}
+
+/// Acquires the history lock of `report_dir`, polling so the wait can be
+/// bounded and never blocks a runtime thread.
+pub async fn acquire(report_dir: &Path) -> Result<HistoryLock, String> {
+    acquire_file(&report_dir.join(LOCK_FILE_NAME)).await
+}
+
+/// Acquires an exclusive lock on the file at `path` the same way, for other
+/// files shared between requests and processes.
+pub async fn acquire_file(path: &Path) -> Result<HistoryLock, String> {
+    let path = path.to_path_buf();
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to open history lock {:?}: {}", path, e))?;
+
+    let started = Instant::now();
+    let timeout = lock_timeout();
+    let mut logged = false;
+
+    loop {
+        match file.try_lock() {
+            Ok(()) => break,
+            Err(TryLockError::WouldBlock) => {
+                if !logged {
+                    tracing::info!(
+                        "history lock: waiting for {:?}, another request holds it",
+                        path
+                    );
+                    logged = true;
+                }
+                if started.elapsed() >= timeout {
+                    metrics::counter_add("allure_host_history_lock_timeouts_total", &[], 1.0);
+                    return Err(format!(
+                        "Timed out after {}s waiting for history lock {:?}",
+                        timeout.as_secs(),
+                        path
+                    ));
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(format!("Failed to lock {:?}: {}", path, e));
+            }
+        }
+    }
+
+    if logged {
+        let waited = started.elapsed();
+        tracing::info!(
+            "history lock: acquired {:?} after waiting {:.2}s",
+            path,
+            waited.as_secs_f64()
+        );
+        metrics::counter_add("allure_host_history_lock_waits_total", &[], 1.0);
+        metrics::counter_add(
+            "allure_host_history_lock_wait_seconds_total",
+            &[],
+            waited.as_secs_f64(),
+        );
+    }
+
+    Ok(HistoryLock { file, path })
+}
@@ -0,0 +1,163 @@
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{DecodingKey, Header, Validation, decode, decode_header};
+use serde_json::{Map, Value};
+use std::env;
+use std::path::PathBuf;
+use std::sync::{LazyLock, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::helpers::api_keys::{Principal, authorize_claims};
+
+/// Unknown `kid`s trigger a JWKS refresh at most this often.
+const REFRESH_COOLDOWN: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub enum JwksSource {
+    File(PathBuf),
+    Url(String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct JwtConfig {
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+}
+
+struct JwksCache {
+    source: Option<JwksSource>,
+    config: JwtConfig,
+    keys: JwkSet,
+    fetched_at: Option<Instant>,
+}
+
+static JWKS: LazyLock<RwLock<JwksCache>> = LazyLock::new(|| {
+    RwLock::new(JwksCache {
+        source: None,
+        config: JwtConfig::default(),
+        keys: JwkSet { keys: Vec::new() },
+        fetched_at: None,
+    })
+});
+
+fn non_empty_var(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// JWKS source from JWT_JWKS_FILE or JWT_JWKS_URL.
+pub fn source_from_env() -> Option<JwksSource> {
+    non_empty_var("JWT_JWKS_FILE")
+        .map(|f| JwksSource::File(PathBuf::from(f)))
+        .or_else(|| non_empty_var("JWT_JWKS_URL").map(JwksSource::Url))
+}
+
+/// Expected `iss` and `aud` from JWT_ISSUER and JWT_AUDIENCE.
+pub fn config_from_env() -> JwtConfig {
+    JwtConfig {
+        issuer: non_empty_var("JWT_ISSUER"),
+        audience: non_empty_var("JWT_AUDIENCE"),
+    }
+}
+
+fn fetch(source: &JwksSource) -> Result<JwkSet, String> {
+    let body = match source {
+        JwksSource::File(path) => std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {:?}: {}", path, e))?,
+        JwksSource::Url(url) => ureq::get(url)
+            .timeout(Duration::from_secs(10))
+            .call()
+            .map_err(|e| format!("Failed to fetch {}: {}", url, e))?
+            .into_string()
+            .map_err(|e| format!("Failed to read {}: {}", url, e))?,
+    };
+    serde_json::from_str(&body).map_err(|e| format!("Invalid JWKS: {}", e))
+}
+
+/// Loads the JWKS and enables bearer authentication; returns the number of keys.
+pub fn configure(source: JwksSource, config: JwtConfig) -> Result<usize, String> {
+    let keys = fetch(&source)?;
+    let count = keys.keys.len();
+    let mut cache = JWKS.write().unwrap();
+    cache.source = Some(source);
+    cache.config = config;
+    cache.keys = keys;
+    cache.fetched_at = Some(Instant::now());
+    Ok(count)
+}
+
+pub fn is_enabled() -> bool {
+    JWKS.read().unwrap().source.is_some()
+}
+
+/// Re-reads the JWKS unless it was fetched within [`REFRESH_COOLDOWN`].
+fn refresh() {
+    let source = {
+        let cache = JWKS.read().unwrap();
+        if cache
+            .fetched_at
+            .is_some_and(|at| at.elapsed() < REFRESH_COOLDOWN)
+        {
+            return;
+        }
+        cache.source.clone()
+    };
+    let Some(source) = source else { return };
+    let result = fetch(&source);
+    let mut cache = JWKS.write().unwrap();
+    cache.fetched_at = Some(Instant::now());
+    match result {
+        Ok(keys) => cache.keys = keys,
+        Err(e) => tracing::error!("jwt: keeping previous JWKS: {}", e),
+    }
+}
+
+fn verify(token: &str, header: &Header) -> Result<Option<Map<String, Value>>, String> {
+    let cache = JWKS.read().unwrap();
+    let jwk = match header.kid.as_deref() {
+        Some(kid) => cache
+            .keys
+            .keys
+            .iter()
+            .find(|k| k.common.key_id.as_deref() == Some(kid)),
+        None if cache.keys.keys.len() == 1 => cache.keys.keys.first(),
+        None => return Err("Token has no kid".to_string()),
+    };
+    let Some(jwk) = jwk else {
+        return Ok(None);
+    };
+    if let Some(alg) = &jwk.common.key_algorithm
+        && format!("{:?}", alg) != format!("{:?}", header.alg)
+    {
+        return Err("Token algorithm does not match its key".to_string());
+    }
+    let key = DecodingKey::from_jwk(jwk).map_err(|e| format!("Unusable JWK: {}", e))?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_required_spec_claims(&["exp"]);
+    if let Some(issuer) = &cache.config.issuer {
+        validation.set_issuer(&[issuer]);
+    }
+    match &cache.config.audience {
+        Some(audience) => validation.set_audience(&[audience]),
+        None => validation.validate_aud = false,
+    }
+    decode::<Map<String, Value>>(token, &key, &validation)
+        .map(|data| Some(data.claims))
+        .map_err(|e| format!("Invalid token: {}", e))
+}
+
+/// Verifies a bearer token against the JWKS and maps its claims to a key.
+/// Blocking: may refetch the JWKS when the token's `kid` is unknown.
+pub fn authenticate(token: &str) -> Result<Principal, String> {
+    let header = decode_header(token).map_err(|e| format!("Malformed token: {}", e))?;
+    let claims = match verify(token, &header)? {
+        Some(claims) => claims,
+        None => {
+            refresh();
+            verify(token, &header)?.ok_or_else(|| "Unknown signing key".to_string())?
+        }
+    };
+    authorize_claims(&claims).ok_or_else(|| "No token rule matches the claims".to_string())
+}
@@ -0,0 +1,96 @@
+use axum::{
+    http::{HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{LazyLock, Mutex};
+
+type Labels = Vec<(String, String)>;
+
+/// Minimal in-process metrics registry rendered in the Prometheus text format.
+/// Series are keyed by metric name and an ordered label set.
+#[derive(Default)]
+struct Registry {
+    help: BTreeMap<&'static str, (&'static str, &'static str)>,
+    series: BTreeMap<&'static str, BTreeMap<Labels, f64>>,
+}
+
+static REGISTRY: LazyLock<Mutex<Registry>> = LazyLock::new(|| Mutex::new(Registry::default()));
+
+fn label_set(labels: &[(&str, &str)]) -> Labels {
+    labels
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+/// Registers the HELP text and TYPE (`counter` or `gauge`) of a metric.
+pub fn describe(name: &'static str, kind: &'static str, help: &'static str) {
+    let mut reg = REGISTRY.lock().unwrap();
+    reg.help.insert(name, (kind, help));
+}
+
+pub fn gauge_set(name: &'static str, labels: &[(&str, &str)], value: f64) {
+    let mut reg = REGISTRY.lock().unwrap();
+    reg.series
+        .entry(name)
+        .or_default()
+        .insert(label_set(labels), value);
+}
+
+pub fn counter_add(name: &'static str, labels: &[(&str, &str)], value: f64) {
+    let mut reg = REGISTRY.lock().unwrap();
+    *reg.series
+        .entry(name)
+        .or_default()
+        .entry(label_set(labels))
+        .or_insert(0.0) += value;
+}
+
+/// Drops every series of a gauge, used before republishing a full snapshot.
+pub fn gauge_reset(name: &'static str) {
+    let mut reg = REGISTRY.lock().unwrap();
+    reg.series.remove(name);
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+pub fn render() -> String {
+    let reg = REGISTRY.lock().unwrap();
+    let mut out = String::new();
+    for (name, series) in &reg.series {
+        if let Some((kind, help)) = reg.help.get(name) {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        }
+        for (labels, value) in series {
+            if labels.is_empty() {
+                let _ = writeln!(out, "{} {}", name, value);
+            } else {
+                let rendered = labels
+                    .iter()
+                    .map(|(k, v)| format!("{}=\"{}\"", k, escape_label(v)))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let _ = writeln!(out, "{}{{{}}} {}", name, rendered, value);
+            }
+        }
+    }
+    out
+}
+
+/// `GET /metrics`
+pub async fn metrics_handler() -> Response {
+    let mut response = (StatusCode::OK, render()).into_response();
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; version=0.0.4"),
+    );
+    response
+}
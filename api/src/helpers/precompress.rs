@@ -0,0 +1,207 @@
+use axum::{
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::Response,
+};
+use percent_encoding::percent_decode_str;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::helpers::access_control::AccessControl;
+use crate::helpers::env_helper::env_flag;
+use crate::helpers::share_link::SharedRun;
+
+/// Files smaller than this are not worth a compressed sibling.
+const MIN_COMPRESS_SIZE: u64 = 1024;
+
+const COMPRESSIBLE_EXTENSIONS: &[&str] = &[
+    "html", "htm", "js", "mjs", "css", "json", "svg", "txt", "xml", "map", "csv", "log",
+];
+
+#[derive(Debug, Default)]
+pub struct PrecompressResult {
+    pub files_compressed: u64,
+    pub original_bytes: u64,
+    pub gzip_bytes: u64,
+    pub brotli_bytes: u64,
+}
+
+pub fn precompress_enabled() -> bool {
+    env_flag("PRECOMPRESS_ENABLED", true)
+}
+
+fn is_compressible(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| COMPRESSIBLE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            // Hidden entries (run metadata) are never served statically
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let Ok(ft) = entry.file_type() else { continue };
+            if ft.is_dir() {
+                collect_files(&entry.path(), out);
+            } else if ft.is_file() && is_compressible(&entry.path()) {
+                out.push(entry.path());
+            }
+        }
+    }
+}
+
+fn gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+fn brotli(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    {
+        let mut writer = brotli::CompressorWriter::new(&mut out, 64 * 1024, 9, 22);
+        writer.write_all(data)?;
+    }
+    Ok(out)
+}
+
+/// Whether `path` is a `.gz` or `.br` sibling that [`precompress_dir`] would
+/// have written, i.e. it sits next to a compressible file large enough to
+/// compress. Other `.gz`/`.br` files are report content.
+pub fn is_generated_sibling(path: &Path) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("gz") | Some("br") => {
+            let original = path.with_extension("");
+            is_compressible(&original)
+                && std::fs::metadata(&original)
+                    .is_ok_and(|m| m.is_file() && m.len() >= MIN_COMPRESS_SIZE)
+        }
+        _ => false,
+    }
+}
+
+/// Writes via a temp file and rename so a reader never sees a partial sibling.
+fn write_sibling(path: &Path, suffix: &str, bytes: &[u8]) -> std::io::Result<()> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    let target = path.with_file_name(&name);
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(&name);
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, target)
+}
+
+/// Writes `.gz` and `.br` siblings next to every compressible file under `dir`,
+/// so `ServeDir` can answer with the precompressed variant the client accepts.
+/// A variant is skipped when it would not be smaller than the original.
+pub fn precompress_dir(dir: &Path) -> Result<PrecompressResult, String> {
+    let mut files = Vec::new();
+    collect_files(dir, &mut files);
+
+    let mut result = PrecompressResult::default();
+    for path in files {
+        let size = std::fs::metadata(&path)
+            .map_err(|e| format!("Failed to stat {:?}: {}", path, e))?
+            .len();
+        if size < MIN_COMPRESS_SIZE {
+            continue;
+        }
+        let data = std::fs::read(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+
+        let gz = gzip(&data).map_err(|e| format!("Failed to gzip {:?}: {}", path, e))?;
+        let br = brotli(&data).map_err(|e| format!("Failed to brotli {:?}: {}", path, e))?;
+
+        let mut written = false;
+        if gz.len() < data.len() {
+            write_sibling(&path, ".gz", &gz)
+                .map_err(|e| format!("Failed to write {:?}.gz: {}", path, e))?;
+            result.gzip_bytes += gz.len() as u64;
+            written = true;
+        }
+        if br.len() < data.len() {
+            write_sibling(&path, ".br", &br)
+                .map_err(|e| format!("Failed to write {:?}.br: {}", path, e))?;
+            result.brotli_bytes += br.len() as u64;
+            written = true;
+        }
+        if written {
+            result.files_compressed += 1;
+            result.original_bytes += data.len() as u64;
+        }
+    }
+
+    Ok(result)
+}
+
+/// Cache-Control for a static path. Run directories
+/// (`/{project}/{branch}/{report}/{id}/...` and the `raw/{id}` variant) never
+/// change once published, everything else (dashboard, listings) must revalidate.
+/// `private` responses are kept out of shared caches.
+pub fn cache_control(path: &str, private: bool) -> &'static str {
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let is_run_path = match segments.as_slice() {
+        [_, _, _, "raw", id, _, ..] | [_, _, _, id, _, ..] => id.parse::<u32>().is_ok(),
+        _ => false,
+    };
+    match (is_run_path, private) {
+        (true, false) => "public, max-age=31536000, immutable",
+        (true, true) => "private, max-age=31536000, immutable",
+        (false, false) => "no-cache",
+        (false, true) => "private, no-cache",
+    }
+}
+
+/// Decides which static responses depend on who is asking.
+#[derive(Clone)]
+pub struct CachePolicy {
+    pub access: Arc<AccessControl>,
+    /// Viewer login is on, so every report needs a session
+    pub viewer_login: bool,
+}
+
+impl CachePolicy {
+    /// Whether responses for `project`, or for a share link, must stay private.
+    pub fn is_private(&self, project: Option<&str>, shared: bool) -> bool {
+        shared || self.viewer_login || self.access.is_protected(project)
+    }
+}
+
+/// Middleware to set Cache-Control on static responses, see [`cache_control`].
+pub async fn cache_headers(
+    State(policy): State<CachePolicy>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let project = request
+        .uri()
+        .path()
+        .split('/')
+        .find(|s| !s.is_empty())
+        .map(|s| percent_decode_str(s).decode_utf8_lossy().into_owned());
+    let shared = request.extensions().get::<SharedRun>().is_some();
+    let private = policy.is_private(project.as_deref(), shared);
+    let value = cache_control(request.uri().path(), private);
+
+    let mut response = next.run(request).await;
+    if response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED {
+        let headers = response.headers_mut();
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(value));
+        // Precompressed variants are picked per request, so caches must key on
+        // it, and on the credentials for protected projects
+        let vary = if private {
+            "accept-encoding, authorization, cookie"
+        } else {
+            "accept-encoding"
+        };
+        headers.insert(header::VARY, HeaderValue::from_static(vary));
+    }
+    response
+}
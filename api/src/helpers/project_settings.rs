@@ -0,0 +1,87 @@
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use crate::helpers::fs_helper::validate_path_segment;
+use crate::models::project::ProjectSettings;
+
+pub const SETTINGS_FILE_NAME: &str = ".project.json";
+
+fn settings_path(data_dir: &Path, project: &str) -> PathBuf {
+    data_dir.join(project).join(SETTINGS_FILE_NAME)
+}
+
+/// Loads a project's settings; a missing or unreadable file yields the defaults.
+pub fn load(data_dir: &Path, project: &str) -> ProjectSettings {
+    let path = settings_path(data_dir, project);
+    match std::fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            tracing::warn!("project settings: ignoring invalid {:?}: {}", path, e);
+            ProjectSettings::default()
+        }),
+        Err(_) => ProjectSettings::default(),
+    }
+}
+
+pub fn validate(settings: &ProjectSettings) -> Result<(), String> {
+    if let Some(branch) = &settings.default_base_branch {
+        validate_path_segment(branch, "default_base_branch")?;
+    }
+    let gate = &settings.quality_gate;
+    if let Some(branch) = &gate.no_new_failures_vs {
+        validate_path_segment(branch, "no_new_failures_vs")?;
+    }
+    if gate
+        .min_pass_rate
+        .is_some_and(|rate| !(0.0..=1.0).contains(&rate))
+    {
+        return Err("min_pass_rate must be between 0 and 1".to_string());
+    }
+    Ok(())
+}
+
+/// Applies `update` to `current` as a JSON merge patch: fields sent replace the
+/// stored ones, nested objects merge, `null` clears a field and omitted fields
+/// are kept.
+pub fn merge(current: &ProjectSettings, update: Value) -> Result<ProjectSettings, String> {
+    if !update.is_object() {
+        return Err("Settings must be a JSON object".to_string());
+    }
+    let mut merged = serde_json::to_value(current).map_err(|e| e.to_string())?;
+    merge_patch(&mut merged, update);
+    let settings: ProjectSettings =
+        serde_json::from_value(merged).map_err(|e| format!("Invalid settings: {}", e))?;
+    validate(&settings)?;
+    Ok(settings)
+}
+
+fn merge_patch(target: &mut Value, patch: Value) {
+    let Value::Object(fields) = patch else {
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    let target = target.as_object_mut().unwrap();
+    for (key, value) in fields {
+        if value.is_null() {
+            target.remove(&key);
+        } else {
+            merge_patch(target.entry(key).or_insert(Value::Null), value);
+        }
+    }
+}
+
+pub fn save(data_dir: &Path, project: &str, settings: &ProjectSettings) -> Result<(), String> {
+    validate(settings)?;
+    let path = settings_path(data_dir, project);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create project directory: {}", e))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    let bytes = serde_json::to_vec_pretty(settings).map_err(|e| e.to_string())?;
+    std::fs::write(&tmp, bytes)
+        .and_then(|_| std::fs::rename(&tmp, &path))
+        .map_err(|e| format!("Failed to write project settings: {}", e))
+}
@@ -0,0 +1,242 @@
+use axum::{Extension, Router, extract::ConnectInfo};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::service::TowerToHyperService;
+use ipnet::IpNet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+use tokio::net::TcpListener;
+use tower::Layer;
+
+use crate::helpers::access_control::AccessControl;
+use crate::helpers::env_helper::env_flag;
+use crate::helpers::metrics;
+
+/// Binary signature opening a version 2 header.
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+const V1_PREFIX: &[u8] = b"PROXY ";
+/// Longest possible version 1 line, CRLF included.
+const V1_MAX_LEN: usize = 107;
+
+/// Time a new connection has to send its header.
+const HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A parsed PROXY header: the original client, when the header names one,
+/// and how many bytes of the stream it took.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyHeader {
+    pub source: Option<SocketAddr>,
+    pub len: usize,
+}
+
+/// Whether the listener expects PROXY headers (PROXY_PROTOCOL).
+pub fn is_enabled() -> bool {
+    env_flag("PROXY_PROTOCOL", false)
+}
+
+/// Request extension with the client a trusted balancer named in its PROXY
+/// header; `None` for LOCAL and UNKNOWN headers. `ConnectInfo` keeps the
+/// balancer's own address.
+#[derive(Debug, Clone, Copy)]
+pub struct ProxiedFor(pub Option<SocketAddr>);
+
+/// Balancers whose PROXY headers are honored: PROXY_PROTOCOL_TRUSTED, else
+/// ALLOWED_PROXY_IPS. Connections from anywhere else are closed.
+pub fn trusted_from_env() -> Vec<IpNet> {
+    let list = |name: &str| {
+        std::env::var(name)
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+    };
+    let trusted = list("PROXY_PROTOCOL_TRUSTED");
+    let trusted = if trusted.is_empty() {
+        list("ALLOWED_PROXY_IPS")
+    } else {
+        trusted
+    };
+    AccessControl::compile_nets(trusted)
+}
+
+pub fn describe_metrics() {
+    metrics::describe(
+        "allure_host_proxy_protocol_rejected_total",
+        "counter",
+        "Connections closed for an untrusted peer or a missing or invalid PROXY header",
+    );
+    metrics::counter_add("allure_host_proxy_protocol_rejected_total", &[], 0.0);
+}
+
+/// Parses a version 1 or 2 PROXY header at the start of `buf`.
+/// `Ok(None)` means more bytes are needed.
+pub fn parse(buf: &[u8]) -> Result<Option<ProxyHeader>, String> {
+    if buf.len() < V2_SIGNATURE.len() && V2_SIGNATURE.starts_with(buf) {
+        return Ok(None);
+    }
+    if buf.starts_with(V2_SIGNATURE) {
+        return parse_v2(buf);
+    }
+    if buf.len() < V1_PREFIX.len() && V1_PREFIX.starts_with(buf) {
+        return Ok(None);
+    }
+    if buf.starts_with(V1_PREFIX) {
+        return parse_v1(buf);
+    }
+    Err("connection did not start with a PROXY header".to_string())
+}
+
+fn parse_v1(buf: &[u8]) -> Result<Option<ProxyHeader>, String> {
+    let Some(end) = buf.windows(2).position(|w| w == b"\r\n") else {
+        return if buf.len() < V1_MAX_LEN {
+            Ok(None)
+        } else {
+            Err("PROXY v1 line too long".to_string())
+        };
+    };
+    let len = end + 2;
+    if len > V1_MAX_LEN {
+        return Err("PROXY v1 line too long".to_string());
+    }
+    let line = std::str::from_utf8(&buf[..end]).map_err(|_| "PROXY v1 line is not ASCII")?;
+    let fields: Vec<&str> = line.split(' ').collect();
+    let source = match fields.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => None,
+        [
+            "PROXY",
+            family @ ("TCP4" | "TCP6"),
+            src,
+            _dst,
+            src_port,
+            _dst_port,
+        ] => {
+            let ip: IpAddr = src
+                .parse()
+                .map_err(|_| format!("invalid PROXY v1 source '{}'", src))?;
+            if ip.is_ipv4() != (*family == "TCP4") {
+                return Err(format!("PROXY v1 source '{}' is not {}", src, family));
+            }
+            let port: u16 = src_port
+                .parse()
+                .map_err(|_| format!("invalid PROXY v1 port '{}'", src_port))?;
+            Some(SocketAddr::new(ip, port))
+        }
+        _ => return Err(format!("malformed PROXY v1 line '{}'", line)),
+    };
+    Ok(Some(ProxyHeader { source, len }))
+}
+
+fn parse_v2(buf: &[u8]) -> Result<Option<ProxyHeader>, String> {
+    if buf.len() < 16 {
+        return Ok(None);
+    }
+    let version = buf[12] >> 4;
+    let command = buf[12] & 0x0f;
+    let family = buf[13] >> 4;
+    let len = 16 + u16::from_be_bytes([buf[14], buf[15]]) as usize;
+    if version != 2 {
+        return Err(format!("unsupported PROXY version {}", version));
+    }
+    if buf.len() < len {
+        return Ok(None);
+    }
+    let addresses = &buf[16..len];
+    let source = match (command, family) {
+        // LOCAL: health checks of the balancer itself
+        (0, _) => None,
+        (1, 1) if addresses.len() >= 12 => {
+            let ip: [u8; 4] = addresses[0..4].try_into().unwrap();
+            let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+            Some(SocketAddr::new(Ipv4Addr::from(ip).into(), port))
+        }
+        (1, 2) if addresses.len() >= 36 => {
+            let ip: [u8; 16] = addresses[0..16].try_into().unwrap();
+            let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+            Some(SocketAddr::new(Ipv6Addr::from(ip).into(), port))
+        }
+        // UNSPEC and UNIX sources carry no IP address
+        (1, 0 | 3) => None,
+        (1, _) => return Err("PROXY v2 address block too short".to_string()),
+        _ => return Err(format!("unsupported PROXY v2 command {}", command)),
+    };
+    Ok(Some(ProxyHeader { source, len }))
+}
+
+/// Reads and consumes the PROXY header, leaving the request bytes buffered.
+pub async fn read_header<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+) -> Result<Option<SocketAddr>, String> {
+    let mut seen = Vec::new();
+    loop {
+        let chunk = reader.fill_buf().await.map_err(|e| e.to_string())?;
+        if chunk.is_empty() {
+            return Err("connection closed before the PROXY header".to_string());
+        }
+        let before = seen.len();
+        let read = chunk.len();
+        seen.extend_from_slice(chunk);
+        match parse(&seen)? {
+            Some(header) => {
+                reader.consume(header.len - before);
+                return Ok(header.source);
+            }
+            None => reader.consume(read),
+        }
+    }
+}
+
+/// Serves `app`, reading a PROXY header on every connection from a `trusted`
+/// balancer and exposing its source as [`ProxiedFor`]. Connections from other
+/// peers or without a valid header are closed.
+pub async fn serve(listener: TcpListener, app: Router, trusted: Vec<IpNet>) {
+    let trusted = Arc::new(trusted);
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                tracing::error!("proxy_protocol: accept failed: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        if !AccessControl::ip_in_nets(AccessControl::normalize_ip(peer.ip()), &trusted) {
+            tracing::warn!("proxy_protocol: closing {}: not a trusted balancer", peer);
+            metrics::counter_add("allure_host_proxy_protocol_rejected_total", &[], 1.0);
+            continue;
+        }
+        let app = app.clone();
+        tokio::spawn(async move {
+            let mut stream = BufReader::new(stream);
+            let source = match tokio::time::timeout(HEADER_TIMEOUT, read_header(&mut stream)).await
+            {
+                Ok(Ok(source)) => source,
+                Ok(Err(e)) => {
+                    tracing::warn!("proxy_protocol: closing {}: {}", peer, e);
+                    metrics::counter_add("allure_host_proxy_protocol_rejected_total", &[], 1.0);
+                    return;
+                }
+                Err(_) => {
+                    tracing::warn!("proxy_protocol: closing {}: no header in time", peer);
+                    metrics::counter_add("allure_host_proxy_protocol_rejected_total", &[], 1.0);
+                    return;
+                }
+            };
+            let app = Extension(ProxiedFor(source)).layer(app);
+            let service = TowerToHyperService::new(Extension(ConnectInfo(peer)).layer(app));
+            if let Err(e) = Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!(
+                    "proxy_protocol: connection from {} ({:?}) ended: {}",
+                    peer,
+                    source,
+                    e
+                );
+            }
+        });
+    }
+}
@@ -0,0 +1,170 @@
+use serde::Serialize;
+use std::path::Path;
+use utoipa::ToSchema;
+
+use crate::helpers::compare::{ComparedRun, DurationThreshold, compare, select_run};
+use crate::helpers::project_settings;
+use crate::helpers::quarantine::Quarantine;
+use crate::helpers::run_summary::build;
+use crate::helpers::test_results::load_outcomes;
+use crate::models::project::QualityGateRules;
+
+/// Failing tests named in a reason before it is cut short.
+const NAMED_TESTS: usize = 5;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GateCheck {
+    /// Rule name as in the project settings
+    pub rule: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GateVerdict {
+    pub run_id: u32,
+    pub passed: bool,
+    /// Details of the checks that failed
+    pub reasons: Vec<String>,
+    pub checks: Vec<GateCheck>,
+}
+
+fn check(rule: &str, passed: bool, detail: String) -> GateCheck {
+    GateCheck {
+        rule: rule.to_string(),
+        passed,
+        detail,
+    }
+}
+
+fn named(names: &[String]) -> String {
+    let mut out = names
+        .iter()
+        .take(NAMED_TESTS)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if names.len() > NAMED_TESTS {
+        out.push_str(&format!(" and {} more", names.len() - NAMED_TESTS));
+    }
+    out
+}
+
+/// Evaluates the project's quality gate against one run of a report.
+pub fn evaluate(
+    data_dir: &Path,
+    project: &str,
+    branch: &str,
+    report: &str,
+    run_id: u32,
+) -> Result<GateVerdict, String> {
+    let rules: QualityGateRules = project_settings::load(data_dir, project).quality_gate;
+    let report_dir = data_dir.join(project).join(branch).join(report);
+    let run_dir = report_dir.join(run_id.to_string());
+    if !run_dir.is_dir() {
+        return Err(format!("Run {} not found", run_id));
+    }
+    let outcomes = load_outcomes(&run_dir)
+        .ok_or_else(|| format!("Run {} has no stored test outcomes", run_id))?;
+    // Quarantined tests never fail the gate
+    let quarantine = Quarantine::load(data_dir, project);
+    let summary = build(run_id, &outcomes, &quarantine);
+    let (head_tests, _) = quarantine.partition(&outcomes);
+    let count = |status: &str| summary.statuses.get(status).copied().unwrap_or(0);
+
+    let mut checks = Vec::new();
+    if let Some(min) = rules.min_pass_rate {
+        let rate = summary.pass_rate.unwrap_or(0.0);
+        checks.push(check(
+            "min_pass_rate",
+            rate >= min,
+            format!(
+                "pass rate {:.2}% (minimum {:.2}%)",
+                rate * 100.0,
+                min * 100.0
+            ),
+        ));
+    }
+    if let Some(max) = rules.max_failed {
+        let failed = count("failed");
+        checks.push(check(
+            "max_failed",
+            failed <= max,
+            format!("{} failed tests (maximum {})", failed, max),
+        ));
+    }
+    if let Some(max) = rules.max_broken {
+        let broken = count("broken");
+        checks.push(check(
+            "max_broken",
+            broken <= max,
+            format!("{} broken tests (maximum {})", broken, max),
+        ));
+    }
+    if let Some(base_branch) = &rules.no_new_failures_vs {
+        let base_dir = data_dir.join(project).join(base_branch).join(report);
+        let before = (base_branch == branch).then_some(run_id);
+        match select_run(&base_dir, None, before) {
+            Ok((base_id, base_tests)) => {
+                let run = |branch: &str, run_id| ComparedRun {
+                    branch: branch.to_string(),
+                    report_name: report.to_string(),
+                    run_id,
+                    url: String::new(),
+                };
+                let threshold = DurationThreshold {
+                    min_change: f64::INFINITY,
+                    min_delta_ms: i64::MAX,
+                };
+                let diff = compare(
+                    run(base_branch, base_id),
+                    &base_tests,
+                    run(branch, run_id),
+                    &head_tests,
+                    &threshold,
+                );
+                // Tests the baseline lacks count too when they fail
+                let added_failures = diff
+                    .added
+                    .iter()
+                    .filter(|t| matches!(t.head_status.as_deref(), Some("failed" | "broken")));
+                let names: Vec<String> = diff
+                    .new_failures
+                    .iter()
+                    .chain(added_failures)
+                    .map(|t| t.full_name.clone().unwrap_or_else(|| t.name.clone()))
+                    .collect();
+                let detail = if names.is_empty() {
+                    format!("no new failures vs {} run {}", base_branch, base_id)
+                } else {
+                    format!(
+                        "{} new failures vs {} run {}: {}",
+                        names.len(),
+                        base_branch,
+                        base_id,
+                        named(&names)
+                    )
+                };
+                checks.push(check("no_new_failures_vs", names.is_empty(), detail));
+            }
+            // Nothing to compare against yet, e.g. the first run of a report
+            Err(e) => checks.push(check(
+                "no_new_failures_vs",
+                true,
+                format!("skipped, no baseline on {}: {}", base_branch, e),
+            )),
+        }
+    }
+
+    let reasons: Vec<String> = checks
+        .iter()
+        .filter(|c| !c.passed)
+        .map(|c| c.detail.clone())
+        .collect();
+    Ok(GateVerdict {
+        run_id,
+        passed: reasons.is_empty(),
+        reasons,
+        checks,
+    })
+}
@@ -0,0 +1,292 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use utoipa::ToSchema;
+
+use crate::helpers::glob::glob_match;
+use crate::helpers::history_lock::{HistoryLock, acquire_file};
+use crate::helpers::test_results::{TestOutcome, parse_result};
+use crate::models::quarantine::{AddQuarantineRequest, QuarantineEntry};
+
+/// Quarantine list stored per project in `{DATA_DIR}/{project}/.quarantine.json`.
+pub const QUARANTINE_FILE_NAME: &str = ".quarantine.json";
+
+/// Lock file serializing changes to the quarantine list.
+pub const QUARANTINE_LOCK_NAME: &str = ".quarantine.lock";
+
+/// Tag added to quarantined results so the Allure report marks them.
+pub const QUARANTINE_TAG: &str = "quarantined";
+
+pub const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// A test of a run left out of its summary and gate because it is quarantined.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QuarantinedTest {
+    pub history_id: String,
+    pub name: String,
+    pub status: String,
+    /// Id of the matching quarantine entry
+    pub entry_id: String,
+}
+
+pub fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Parses `YYYY-MM-DD` into the epoch milliseconds of that day's start (UTC).
+pub fn parse_date(date: &str) -> Option<i64> {
+    let mut parts = date.trim().splitn(3, '-');
+    let y: i64 = parts.next()?.parse().ok()?;
+    let m: i64 = parts.next()?.parse().ok()?;
+    let d: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return None;
+    }
+    // Days from civil date, proleptic Gregorian calendar
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (m + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    Some((era * 146_097 + doe - 719_468) * DAY_MS)
+}
+
+/// Whether an entry still applies at `now`; it lasts through its expiry day.
+pub fn is_active(entry: &QuarantineEntry, now: i64) -> bool {
+    parse_date(&entry.expires).is_some_and(|start| now < start + DAY_MS)
+}
+
+fn file_path(data_dir: &Path, project: &str) -> PathBuf {
+    data_dir.join(project).join(QUARANTINE_FILE_NAME)
+}
+
+/// Every entry of a project, expired ones included.
+pub fn list(data_dir: &Path, project: &str) -> Vec<QuarantineEntry> {
+    std::fs::read(file_path(data_dir, project))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+fn save(data_dir: &Path, project: &str, entries: &[QuarantineEntry]) -> Result<(), String> {
+    let path = file_path(data_dir, project);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    let bytes = serde_json::to_vec_pretty(entries).map_err(|e| e.to_string())?;
+    std::fs::write(&tmp, bytes)
+        .and_then(|_| std::fs::rename(&tmp, &path))
+        .map_err(|e| format!("Failed to write quarantine list: {}", e))
+}
+
+/// Locks the project's quarantine list; hold it around `add` and `remove` so
+/// concurrent changes, from any server process, don't drop each other.
+pub async fn lock(data_dir: &Path, project: &str) -> Result<HistoryLock, String> {
+    let dir = data_dir.join(project);
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| format!("Failed to create project directory: {}", e))?;
+    acquire_file(&dir.join(QUARANTINE_LOCK_NAME)).await
+}
+
+/// Validates and stores a new entry.
+pub fn add(
+    data_dir: &Path,
+    project: &str,
+    request: AddQuarantineRequest,
+) -> Result<QuarantineEntry, String> {
+    let non_empty = |v: Option<String>| v.filter(|s| !s.trim().is_empty());
+    let (history_id, full_name, pattern) = (
+        non_empty(request.history_id),
+        non_empty(request.full_name),
+        non_empty(request.pattern),
+    );
+    let matchers = [&history_id, &full_name, &pattern]
+        .iter()
+        .filter(|m| m.is_some())
+        .count();
+    if matchers != 1 {
+        return Err("Set exactly one of history_id, full_name or pattern".to_string());
+    }
+    if request.owner.trim().is_empty() {
+        return Err("owner is required".to_string());
+    }
+    let expires = request.expires.trim().to_string();
+    let Some(start) = parse_date(&expires) else {
+        return Err("expires must be a date (YYYY-MM-DD)".to_string());
+    };
+    let now = now_ms();
+    if start + DAY_MS <= now {
+        return Err("expires must not be in the past".to_string());
+    }
+
+    let entry = QuarantineEntry {
+        id: uuid::Uuid::new_v4().to_string(),
+        history_id,
+        full_name,
+        pattern,
+        owner: request.owner.trim().to_string(),
+        reason: non_empty(request.reason),
+        expires,
+        created_at: now,
+    };
+    let mut entries = list(data_dir, project);
+    entries.push(entry.clone());
+    save(data_dir, project, &entries)?;
+    Ok(entry)
+}
+
+/// Removes an entry by id. Returns false if there was none.
+pub fn remove(data_dir: &Path, project: &str, id: &str) -> Result<bool, String> {
+    let mut entries = list(data_dir, project);
+    let before = entries.len();
+    entries.retain(|e| e.id != id);
+    if entries.len() == before {
+        return Ok(false);
+    }
+    save(data_dir, project, &entries)?;
+    Ok(true)
+}
+
+/// The entries of a project that apply right now.
+#[derive(Debug, Default)]
+pub struct Quarantine {
+    entries: Vec<QuarantineEntry>,
+}
+
+impl Quarantine {
+    pub fn load(data_dir: &Path, project: &str) -> Self {
+        let now = now_ms();
+        Self {
+            entries: list(data_dir, project)
+                .into_iter()
+                .filter(|e| is_active(e, now))
+                .collect(),
+        }
+    }
+
+    pub fn find(
+        &self,
+        history_id: &str,
+        full_name: Option<&str>,
+        name: &str,
+    ) -> Option<&QuarantineEntry> {
+        let full_name = full_name.unwrap_or(name);
+        self.entries.iter().find(|e| {
+            e.history_id.as_deref() == Some(history_id)
+                || e.full_name.as_deref() == Some(full_name)
+                || e.pattern
+                    .as_deref()
+                    .is_some_and(|p| glob_match(p, full_name))
+        })
+    }
+
+    pub fn find_outcome(&self, outcome: &TestOutcome) -> Option<&QuarantineEntry> {
+        self.find(
+            &outcome.history_id,
+            outcome.full_name.as_deref(),
+            &outcome.name,
+        )
+    }
+
+    /// Splits outcomes into the ones that count and the quarantined ones.
+    pub fn partition(&self, outcomes: &[TestOutcome]) -> (Vec<TestOutcome>, Vec<QuarantinedTest>) {
+        let mut counted = Vec::new();
+        let mut quarantined = Vec::new();
+        for outcome in outcomes {
+            match self.find_outcome(outcome) {
+                Some(entry) => quarantined.push(QuarantinedTest {
+                    history_id: outcome.history_id.clone(),
+                    name: outcome
+                        .full_name
+                        .clone()
+                        .unwrap_or_else(|| outcome.name.clone()),
+                    status: outcome.status.clone(),
+                    entry_id: entry.id.clone(),
+                }),
+                None => counted.push(outcome.clone()),
+            }
+        }
+        (counted, quarantined)
+    }
+
+    /// Adds the quarantine tag to matching result files before generation so
+    /// the report marks them. Returns the number of files tagged.
+    pub fn tag_results(&self, results_dir: &Path) -> usize {
+        if self.entries.is_empty() {
+            return 0;
+        }
+        let mut tagged = 0;
+        let Ok(entries) = std::fs::read_dir(results_dir) else {
+            return 0;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !path.to_string_lossy().ends_with("-result.json") {
+                continue;
+            }
+            let Some(mut value) = std::fs::read(&path)
+                .ok()
+                .and_then(|b| serde_json::from_slice::<serde_json::Value>(&b).ok())
+            else {
+                continue;
+            };
+            if parse_result(&value).is_none_or(|o| self.find_outcome(&o).is_none()) {
+                continue;
+            }
+            let Some(obj) = value.as_object_mut() else {
+                continue;
+            };
+            let labels = obj
+                .entry("labels")
+                .or_insert_with(|| serde_json::Value::Array(Vec::new()));
+            if let Some(labels) = labels.as_array_mut() {
+                labels.push(serde_json::json!({ "name": "tag", "value": QUARANTINE_TAG }));
+            }
+            // Upload extracts are private to this request, rewriting in place is fine
+            if std::fs::write(&path, value.to_string()).is_ok() {
+                tagged += 1;
+            }
+        }
+        tagged
+    }
+}
+
+/// Removes the tag `tag_results` added from the results in `results_dir`, so
+/// retained results replay without the quarantine of their upload time.
+pub fn untag_results(results_dir: &Path) -> usize {
+    let is_tag = |label: &serde_json::Value| {
+        label.get("name").and_then(|n| n.as_str()) == Some("tag")
+            && label.get("value").and_then(|v| v.as_str()) == Some(QUARANTINE_TAG)
+    };
+    let mut untagged = 0;
+    let Ok(entries) = std::fs::read_dir(results_dir) else {
+        return 0;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.to_string_lossy().ends_with("-result.json") {
+            continue;
+        }
+        let Some(mut value) = std::fs::read(&path)
+            .ok()
+            .and_then(|b| serde_json::from_slice::<serde_json::Value>(&b).ok())
+        else {
+            continue;
+        };
+        let Some(labels) = value.get_mut("labels").and_then(|l| l.as_array_mut()) else {
+            continue;
+        };
+        let before = labels.len();
+        labels.retain(|label| !is_tag(label));
+        if labels.len() != before && std::fs::write(&path, value.to_string()).is_ok() {
+            untagged += 1;
+        }
+    }
+    untagged
+}
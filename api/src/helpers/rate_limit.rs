@@ -0,0 +1,288 @@
+use axum::{
+    Extension, Json,
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::helpers::access_control::{AccessControl, Peer};
+use crate::helpers::api_keys::Principal;
+use crate::helpers::metrics;
+
+/// How often full buckets are dropped, see `prune_all`.
+pub const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+static UPLOADS: OnceLock<RateLimiter> = OnceLock::new();
+static STATIC_REQUESTS: OnceLock<RateLimiter> = OnceLock::new();
+
+/// Sustained rate and burst size of a token bucket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limit {
+    pub per_sec: f64,
+    pub burst: f64,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets keyed by API key or client IP.
+pub struct RateLimiter {
+    name: &'static str,
+    limit: Limit,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(name: &'static str, limit: Limit) -> Self {
+        Self {
+            name,
+            limit,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token for `key` and returns the tokens left, or how long until
+    /// one is available.
+    pub fn check_at(&self, key: &str, now: Instant) -> Result<f64, Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.limit.burst,
+            updated: now,
+        });
+        let tokens = refill(bucket, self.limit, now);
+        let result = if tokens >= 1.0 {
+            bucket.tokens = tokens - 1.0;
+            Ok(bucket.tokens)
+        } else {
+            bucket.tokens = tokens;
+            Err(Duration::from_secs_f64((1.0 - tokens) / self.limit.per_sec))
+        };
+        bucket.updated = now;
+
+        let labels = [("limiter", self.name)];
+        metrics::gauge_set(
+            "allure_host_rate_limit_clients",
+            &labels,
+            buckets.len() as f64,
+        );
+        if result.is_err() {
+            metrics::counter_add("allure_host_rate_limited_total", &labels, 1.0);
+        }
+        result
+    }
+
+    pub fn check(&self, key: &str) -> Result<f64, Duration> {
+        self.check_at(key, Instant::now())
+    }
+
+    /// Drops buckets that refilled completely, since they hold no state worth
+    /// keeping. Returns how many clients are still tracked.
+    pub fn prune(&self, now: Instant) -> usize {
+        let mut buckets = self.buckets.lock().unwrap();
+        let limit = self.limit;
+        buckets.retain(|_, b| refill(b, limit, now) < limit.burst);
+        metrics::gauge_set(
+            "allure_host_rate_limit_clients",
+            &[("limiter", self.name)],
+            buckets.len() as f64,
+        );
+        buckets.len()
+    }
+}
+
+/// Prunes the configured limiters; run every `PRUNE_INTERVAL`.
+pub fn prune_all() {
+    let now = Instant::now();
+    for limiter in [UPLOADS.get(), STATIC_REQUESTS.get()].into_iter().flatten() {
+        limiter.prune(now);
+    }
+}
+
+/// Whether any limiter is configured.
+pub fn is_enabled() -> bool {
+    UPLOADS.get().is_some() || STATIC_REQUESTS.get().is_some()
+}
+
+fn refill(bucket: &Bucket, limit: Limit, now: Instant) -> f64 {
+    let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+    (bucket.tokens + elapsed * limit.per_sec).min(limit.burst)
+}
+
+/// Parses a rate like `30/min`, `5/s` or `100/hour` into requests per second.
+pub fn parse_rate(value: &str) -> Option<f64> {
+    let (count, unit) = value.trim().split_once('/')?;
+    let count: f64 = count.trim().parse().ok().filter(|c: &f64| *c > 0.0)?;
+    let secs = match unit.trim() {
+        "s" | "sec" | "second" => 1.0,
+        "m" | "min" | "minute" => 60.0,
+        "h" | "hour" => 3600.0,
+        _ => return None,
+    };
+    Some(count / secs)
+}
+
+/// Reads `{prefix}_RATE_LIMIT` and `{prefix}_RATE_BURST`; the burst defaults
+/// to the count of the rate, e.g. 30 for `30/min`.
+fn limit_from_env(prefix: &str) -> Result<Option<Limit>, String> {
+    let rate_var = format!("{}_RATE_LIMIT", prefix);
+    let rate = match env::var(&rate_var) {
+        Ok(v) if !v.trim().is_empty() => v,
+        _ => return Ok(None),
+    };
+    let per_sec = parse_rate(&rate)
+        .ok_or_else(|| format!("{} must look like 30/min, got '{}'", rate_var, rate))?;
+    let default_burst: f64 = rate
+        .split('/')
+        .next()
+        .unwrap_or("1")
+        .trim()
+        .parse()
+        .unwrap_or(1.0);
+    let burst_var = format!("{}_RATE_BURST", prefix);
+    let burst = match env::var(&burst_var) {
+        Ok(v) if !v.trim().is_empty() => v
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|b| *b >= 1.0)
+            .ok_or_else(|| format!("{} must be a number of at least 1", burst_var))?,
+        _ => default_burst.max(1.0),
+    };
+    Ok(Some(Limit { per_sec, burst }))
+}
+
+pub fn describe_metrics() {
+    metrics::describe(
+        "allure_host_rate_limited_total",
+        "counter",
+        "Requests refused with 429 by a rate limiter",
+    );
+    metrics::describe(
+        "allure_host_rate_limit_clients",
+        "gauge",
+        "API keys or client IPs a rate limiter currently tracks",
+    );
+    metrics::describe(
+        "allure_host_rate_limit_tokens",
+        "gauge",
+        "Uploads an API key may still send without waiting, for its most recent bucket",
+    );
+}
+
+/// Enables the limiters configured by UPLOAD_RATE_LIMIT and STATIC_RATE_LIMIT.
+pub fn configure_from_env() -> Result<(), String> {
+    describe_metrics();
+    if let Some(limit) = limit_from_env("UPLOAD")? {
+        tracing::info!(
+            "rate_limit: uploads {:.3}/s per key, burst {}",
+            limit.per_sec,
+            limit.burst
+        );
+        metrics::counter_add(
+            "allure_host_rate_limited_total",
+            &[("limiter", "upload")],
+            0.0,
+        );
+        let _ = UPLOADS.set(RateLimiter::new("upload", limit));
+    }
+    if let Some(limit) = limit_from_env("STATIC")? {
+        tracing::info!(
+            "rate_limit: static {:.3}/s per IP, burst {}",
+            limit.per_sec,
+            limit.burst
+        );
+        metrics::counter_add(
+            "allure_host_rate_limited_total",
+            &[("limiter", "static")],
+            0.0,
+        );
+        let _ = STATIC_REQUESTS.set(RateLimiter::new("static", limit));
+    }
+    Ok(())
+}
+
+fn retry_after_secs(wait: Duration) -> u64 {
+    wait.as_secs() + u64::from(wait.subsec_nanos() > 0)
+}
+
+/// Middleware limiting uploads per API key. Runs after `auth`.
+pub async fn limit_uploads(
+    Extension(principal): Extension<Principal>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(limiter) = UPLOADS.get() else {
+        return next.run(request).await;
+    };
+    let key = match &principal.subject {
+        Some(sub) => format!("{}:{}", principal.key_id, sub),
+        None => principal.key_id.clone(),
+    };
+    let result = limiter.check(&key);
+    // Labelled by key id alone, so token subjects do not multiply the series
+    metrics::gauge_set(
+        "allure_host_rate_limit_tokens",
+        &[("limiter", "upload"), ("key", principal.key_id.as_str())],
+        result.unwrap_or(0.0),
+    );
+    match result {
+        Ok(_) => next.run(request).await,
+        Err(wait) => {
+            let secs = retry_after_secs(wait);
+            tracing::warn!(
+                "rate_limit: upload by '{}' refused, retry in {}s",
+                key,
+                secs
+            );
+            let mut response = (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(json!({
+                    "error": "Upload rate limit exceeded",
+                    "retry_after_secs": secs
+                })),
+            )
+                .into_response();
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+            response
+        }
+    }
+}
+
+/// Middleware limiting report and manifest requests per client IP, resolved
+/// like `access_control` does.
+pub async fn limit_by_ip(
+    State(access): State<Arc<AccessControl>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(limiter) = STATIC_REQUESTS.get() else {
+        return next.run(request).await;
+    };
+    let Some(peer) = Peer::of(request.extensions()) else {
+        return next.run(request).await;
+    };
+    let client = access
+        .client_ip(peer, request.headers())
+        .unwrap_or(peer.source());
+    match limiter.check(&client.to_string()) {
+        Ok(_) => next.run(request).await,
+        Err(wait) => {
+            let mut response = (StatusCode::TOO_MANY_REQUESTS, "Too many requests").into_response();
+            response.headers_mut().insert(
+                header::RETRY_AFTER,
+                HeaderValue::from(retry_after_secs(wait)),
+            );
+            response
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use axum::{
+    body::Body,
+    extract::State,
+    http::{Request, StatusCode, header},
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, RwLock};
+use utoipa::ToSchema;
+
+use crate::helpers::dedup::{dedup_dir, dedup_enabled};
+use crate::helpers::fs_helper::{list_run_dirs, validate_path_segment, validate_project_name};
+use crate::helpers::history::history_path;
+use crate::helpers::usage;
+use crate::models::report::{RelocateMode, TreeRef};
+
+/// Redirect table persisted at the root of DATA_DIR.
+pub const REDIRECTS_FILE_NAME: &str = ".redirects.json";
+
+/// Report-level Allure config naming the absolute path of `history.jsonl`.
+const CONFIG_FILE_NAME: &str = "allurerc.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Redirect {
+    /// Old location relative to DATA_DIR, e.g. `my-project/old-branch`
+    pub from: String,
+    /// New location relative to DATA_DIR
+    pub to: String,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct RelocateResult {
+    pub from: String,
+    pub to: String,
+    pub runs: u64,
+    pub files_fixed: u64,
+    pub redirect: bool,
+}
+
+static REDIRECTS: LazyLock<RwLock<Vec<Redirect>>> = LazyLock::new(|| RwLock::new(Vec::new()));
+
+/// Validates a tree reference and returns its path relative to DATA_DIR.
+pub fn tree_rel_path(tree: &TreeRef) -> Result<String, String> {
+    let mut parts = vec![validate_path_segment(&tree.project_name, "project_name")?];
+    if let Some(branch) = &tree.branch {
+        parts.push(validate_path_segment(branch, "branch")?);
+    }
+    if let Some(report) = &tree.report_name {
+        if tree.branch.is_none() {
+            return Err("report_name requires branch".to_string());
+        }
+        let report = validate_path_segment(report, "report_name")?;
+        if report.parse::<u32>().is_ok() || report == "raw" {
+            return Err("report_name must not be numeric or 'raw'".to_string());
+        }
+        parts.push(report);
+    }
+    Ok(parts.join("/"))
+}
+
+fn depth(tree: &TreeRef) -> usize {
+    1 + tree.branch.is_some() as usize + tree.report_name.is_some() as usize
+}
+
+/// Loads the redirect table into memory; call once at startup.
+pub fn load_redirects(data_dir: &Path) {
+    let redirects = std::fs::read(data_dir.join(REDIRECTS_FILE_NAME))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<Vec<Redirect>>(&bytes).ok())
+        .unwrap_or_default();
+    *REDIRECTS.write().unwrap() = redirects;
+}
+
+pub fn list_redirects() -> Vec<Redirect> {
+    REDIRECTS.read().unwrap().clone()
+}
+
+fn save_redirects(data_dir: &Path, redirects: &[Redirect]) -> Result<(), String> {
+    let path = data_dir.join(REDIRECTS_FILE_NAME);
+    let tmp = path.with_extension("json.tmp");
+    let bytes = serde_json::to_vec_pretty(redirects).map_err(|e| e.to_string())?;
+    std::fs::write(&tmp, bytes)
+        .and_then(|_| std::fs::rename(&tmp, &path))
+        .map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+fn add_redirect(data_dir: &Path, from: &str, to: &str) -> Result<(), String> {
+    let mut redirects = REDIRECTS.write().unwrap();
+    redirects.retain(|r| r.from != from);
+    // Point earlier redirects at the final location instead of chaining hops.
+    for r in redirects.iter_mut() {
+        if r.to == from {
+            r.to = to.to_string();
+        } else if let Some(rest) = r.to.strip_prefix(&format!("{}/", from)) {
+            r.to = format!("{}/{}", to, rest);
+        }
+    }
+    redirects.push(Redirect {
+        from: from.to_string(),
+        to: to.to_string(),
+    });
+    save_redirects(data_dir, &redirects)
+}
+
+/// Removes the redirect whose source is `from`. Returns false if none existed.
+pub fn remove_redirect(data_dir: &Path, from: &str) -> Result<bool, String> {
+    let mut redirects = REDIRECTS.write().unwrap();
+    let before = redirects.len();
+    redirects.retain(|r| r.from != from);
+    if redirects.len() == before {
+        return Ok(false);
+    }
+    save_redirects(data_dir, &redirects)?;
+    Ok(true)
+}
+
+fn copy_dir_recursive(src: &Path, dest: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dest)?;
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_recursive(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Directories under `root` (inclusive) that hold report-level files such as
+/// `allurerc.json` and `history.jsonl`. Run directories are immutable and skipped.
+fn report_dirs(root: &Path, depth: usize, out: &mut Vec<PathBuf>) {
+    if depth == 3 {
+        out.push(root.to_path_buf());
+        return;
+    }
+    if let Ok(entries) = std::fs::read_dir(root) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') || !entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                continue;
+            }
+            report_dirs(&entry.path(), depth + 1, out);
+        }
+    }
+}
+
+/// Report directories under the source of a relocation, in lock order.
+/// Empty when `from` is invalid; `relocate` reports that error.
+pub fn source_report_dirs(data_dir: &Path, from: &TreeRef) -> Vec<PathBuf> {
+    let Ok(rel) = tree_rel_path(from) else {
+        return Vec::new();
+    };
+    let mut dirs = Vec::new();
+    report_dirs(&data_dir.join(rel), depth(from), &mut dirs);
+    dirs.sort();
+    dirs
+}
+
+/// `url` with its path moved from `/{from_rel}/...` to `/{to_rel}/...`. Absolute
+/// URLs keep their origin; URLs elsewhere are left alone.
+fn relocate_url(url: &str, from_rel: &str, to_rel: &str) -> Option<String> {
+    let path_start = match url.find("://") {
+        Some(i) => i + 3 + url[i + 3..].find('/')?,
+        None => 0,
+    };
+    let (origin, path) = url.split_at(path_start);
+    let rest = path.strip_prefix(&format!("/{}/", from_rel))?;
+    Some(format!("{}/{}/{}", origin, to_rel, rest))
+}
+
+/// Rewrites the string field `key` of `obj` with `rewrite`. Returns whether it changed.
+fn rewrite_field(
+    obj: &mut serde_json::Map<String, Value>,
+    key: &str,
+    rewrite: impl Fn(&str) -> Option<String>,
+) -> bool {
+    match obj.get(key).and_then(|v| v.as_str()).and_then(rewrite) {
+        Some(new) => {
+            obj.insert(key.to_string(), Value::from(new));
+            true
+        }
+        None => false,
+    }
+}
+
+/// Points `historyPath` of `allurerc.json` at the new report directory.
+fn fix_up_config(content: &str, abs_src: &Path, abs_dest: &Path) -> Option<String> {
+    let mut config: Value = serde_json::from_str(content).ok()?;
+    let changed = rewrite_field(config.as_object_mut()?, "historyPath", |path| {
+        let rest = Path::new(path).strip_prefix(abs_src).ok()?;
+        Some(abs_dest.join(rest).to_string_lossy().to_string())
+    });
+    if !changed {
+        return None;
+    }
+    serde_json::to_string_pretty(&config).ok()
+}
+
+/// Moves the run `url` of each history entry, and of its test results, to the
+/// new location. Lines that are not JSON objects are kept as they are.
+fn fix_up_history(content: &str, from_rel: &str, to_rel: &str) -> Option<String> {
+    let move_url = |url: &str| relocate_url(url, from_rel, to_rel);
+    let mut changed = false;
+    let mut lines = Vec::new();
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        let Ok(mut entry) = serde_json::from_str::<Value>(line) else {
+            lines.push(line.to_string());
+            continue;
+        };
+        let Some(obj) = entry.as_object_mut() else {
+            lines.push(line.to_string());
+            continue;
+        };
+        let mut entry_changed = rewrite_field(obj, "url", move_url);
+        if let Some(results) = obj.get_mut("testResults").and_then(|r| r.as_object_mut()) {
+            for result in results.values_mut().filter_map(|r| r.as_object_mut()) {
+                entry_changed |= rewrite_field(result, "url", move_url);
+            }
+        }
+        if entry_changed {
+            changed = true;
+            lines.push(entry.to_string());
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+    changed.then(|| lines.join("\n") + "\n")
+}
+
+/// Rewrites the history path in `allurerc.json` and the run URLs in
+/// `history.jsonl` of every report under `new_root`. Files are replaced via
+/// rename, never rewritten in place.
+fn fix_up_metadata(
+    new_root: &Path,
+    depth: usize,
+    abs_src: &Path,
+    abs_dest: &Path,
+    from_rel: &str,
+    to_rel: &str,
+) -> Result<u64, String> {
+    let mut dirs = Vec::new();
+    report_dirs(new_root, depth, &mut dirs);
+
+    let mut fixed = 0;
+    for dir in dirs {
+        // The source of a report directory is the same path below abs_src
+        let rel = dir.strip_prefix(new_root).unwrap_or(Path::new(""));
+        let (src_dir, dest_dir) = (abs_src.join(rel), abs_dest.join(rel));
+        let config = dir.join(CONFIG_FILE_NAME);
+        let history = history_path(&dir);
+        let updates = [
+            (
+                &config,
+                std::fs::read_to_string(&config)
+                    .ok()
+                    .and_then(|c| fix_up_config(&c, &src_dir, &dest_dir)),
+            ),
+            (
+                &history,
+                std::fs::read_to_string(&history)
+                    .ok()
+                    .and_then(|c| fix_up_history(&c, from_rel, to_rel)),
+            ),
+        ];
+        for (path, updated) in updates {
+            let Some(updated) = updated else {
+                continue;
+            };
+            let tmp = path.with_extension("fixup.tmp");
+            std::fs::write(&tmp, updated)
+                .and_then(|_| std::fs::rename(&tmp, path))
+                .map_err(|e| format!("Failed to rewrite {:?}: {}", path, e))?;
+            fixed += 1;
+        }
+    }
+    Ok(fixed)
+}
+
+/// Renames, moves or copies a project, branch or report directory. Callers
+/// hold the history locks of `source_report_dirs` so no upload or history
+/// rewrite runs against the source meanwhile.
+pub fn relocate(
+    data_dir: &Path,
+    from: &TreeRef,
+    to: &TreeRef,
+    mode: RelocateMode,
+    redirect: bool,
+) -> Result<RelocateResult, String> {
+    let level = depth(from);
+    if level != depth(to) {
+        return Err("from and to must address the same level (project, branch or report)".into());
+    }
+    let from_rel = tree_rel_path(from)?;
+    let to_rel = tree_rel_path(to)?;
+    if to.project_name != from.project_name {
+        validate_project_name(&to.project_name)?;
+    }
+    if from_rel == to_rel || to_rel.starts_with(&format!("{}/", from_rel)) {
+        return Err("Destination must differ from and not be inside the source".into());
+    }
+
+    let src = data_dir.join(&from_rel);
+    let dest = data_dir.join(&to_rel);
+    if !src.is_dir() {
+        return Err(format!("Source '{}' does not exist", from_rel));
+    }
+    if dest.exists() {
+        return Err(format!("Destination '{}' already exists", to_rel));
+    }
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    let abs_src = std::fs::canonicalize(&src).unwrap_or_else(|_| src.clone());
+    match mode {
+        RelocateMode::Move => std::fs::rename(&src, &dest)
+            .map_err(|e| format!("Failed to move '{}': {}", from_rel, e))?,
+        RelocateMode::Copy => copy_dir_recursive(&src, &dest).map_err(|e| {
+            // Never leave a half-written copy behind
+            let _ = std::fs::remove_dir_all(&dest);
+            format!("Failed to copy '{}': {}", from_rel, e)
+        })?,
+    }
+    let abs_dest = std::fs::canonicalize(&dest).unwrap_or_else(|_| dest.clone());
+
+    let files_fixed = fix_up_metadata(&dest, level, &abs_src, &abs_dest, &from_rel, &to_rel)?;
+
+    let mut runs = 0;
+    for run in list_run_dirs(data_dir) {
+        if !run.path.starts_with(&dest) {
+            continue;
+        }
+        runs += 1;
+        if mode == RelocateMode::Copy
+            && dedup_enabled()
+            && let Err(e) = dedup_dir(data_dir, &run.path)
+        {
+            tracing::warn!("relocate: dedup of copied run failed: {}", e);
+        }
+    }
+
+    if mode == RelocateMode::Move {
+        usage::forget_prefix(data_dir, &from_rel);
+    }
+    usage::record_prefix(data_dir, &to_rel);
+
+    let redirect = redirect && mode == RelocateMode::Move;
+    if redirect {
+        add_redirect(data_dir, &from_rel, &to_rel)?;
+    }
+
+    Ok(RelocateResult {
+        from: from_rel,
+        to: to_rel,
+        runs,
+        files_fixed,
+        redirect,
+    })
+}
+
+/// Middleware redirecting requests for moved directories to their new location.
+/// A redirect only applies while the old path does not exist again on disk, so
+/// it is temporary and never cached: recreating the old path must take effect.
+pub async fn redirect_moved(
+    State(data_dir): State<PathBuf>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let path = req.uri().path().trim_start_matches('/').to_string();
+    let target = {
+        let redirects = REDIRECTS.read().unwrap();
+        redirects.iter().find_map(|r| {
+            if path == r.from {
+                Some(r.to.clone())
+            } else {
+                path.strip_prefix(&format!("{}/", r.from))
+                    .map(|rest| format!("{}/{}", r.to, rest))
+            }
+            .filter(|_| !data_dir.join(&r.from).exists())
+        })
+    };
+
+    match target {
+        Some(to) => {
+            let location = match req.uri().query() {
+                Some(q) => format!("/{}?{}", to, q),
+                None => format!("/{}", to),
+            };
+            Response::builder()
+                .status(StatusCode::TEMPORARY_REDIRECT)
+                .header(header::LOCATION, location)
+                .header(header::CACHE_CONTROL, "no-store")
+                .body(Body::empty())
+                .unwrap()
+        }
+        None => next.run(req).await,
+    }
+}
@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use utoipa::ToSchema;
+
+use crate::helpers::clusters::FailureCluster;
+use crate::helpers::flaky::FlakyTest;
+use crate::helpers::fs_helper::RUN_META_DIR;
+use crate::helpers::quarantine::{Quarantine, QuarantinedTest};
+use crate::helpers::test_results::{TestOutcome, load_outcomes};
+
+/// Summary of a run's results, stored in the run's metadata directory.
+pub const SUMMARY_FILE_NAME: &str = "summary.json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct RunSummary {
+    pub run_id: u32,
+    /// Earliest test start, epoch milliseconds
+    pub started_at: Option<i64>,
+    /// Latest test stop, epoch milliseconds
+    pub finished_at: Option<i64>,
+    /// Tests counted, quarantined ones excluded
+    pub total: usize,
+    /// Test count per status (passed, failed, broken, skipped, unknown)
+    pub statuses: BTreeMap<String, usize>,
+    /// Passed tests over tests that were not skipped, between 0 and 1
+    pub pass_rate: Option<f64>,
+    /// Wall-clock time from the first start to the last stop
+    pub duration_ms: Option<i64>,
+    /// Sum of the individual test durations
+    pub tests_duration_ms: i64,
+    /// Tests flipping between pass and fail over the latest runs, up to this one
+    pub flaky: Vec<FlakyTest>,
+    /// Failures grouped by normalized message and trace; `None` until computed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clusters: Option<Vec<FailureCluster>>,
+    /// Tests left out of the counts above because they were quarantined.
+    /// Stored at upload time: later quarantine changes don't alter it.
+    pub quarantined: Vec<QuarantinedTest>,
+}
+
+pub fn summary_path(run_dir: &Path) -> PathBuf {
+    run_dir.join(RUN_META_DIR).join(SUMMARY_FILE_NAME)
+}
+
+pub fn pass_rate(statuses: &BTreeMap<String, usize>) -> Option<f64> {
+    let count = |s: &str| statuses.get(s).copied().unwrap_or(0);
+    let executed = statuses.values().sum::<usize>() - count("skipped");
+    (executed > 0).then(|| count("passed") as f64 / executed as f64)
+}
+
+pub fn build(run_id: u32, outcomes: &[TestOutcome], quarantine: &Quarantine) -> RunSummary {
+    let (counted, quarantined) = quarantine.partition(outcomes);
+    let mut summary = RunSummary {
+        run_id,
+        total: counted.len(),
+        quarantined,
+        ..Default::default()
+    };
+    for outcome in &counted {
+        *summary.statuses.entry(outcome.status.clone()).or_insert(0) += 1;
+        summary.tests_duration_ms += outcome.duration_ms().unwrap_or(0);
+    }
+    // The run's time span covers quarantined tests too
+    for outcome in outcomes {
+        if let Some(start) = outcome.start {
+            summary.started_at = Some(summary.started_at.map_or(start, |s| s.min(start)));
+        }
+        if let Some(stop) = outcome.stop {
+            summary.finished_at = Some(summary.finished_at.map_or(stop, |s| s.max(stop)));
+        }
+    }
+    summary.pass_rate = pass_rate(&summary.statuses);
+    summary.duration_ms = summary
+        .finished_at
+        .zip(summary.started_at)
+        .map(|(stop, start)| stop - start);
+    summary
+}
+
+/// Writes the summary next to the run's other metadata. Replaced via rename,
+/// never rewritten in place.
+pub fn save(run_dir: &Path, summary: &RunSummary) -> Result<(), String> {
+    let path = summary_path(run_dir);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    let bytes = serde_json::to_vec_pretty(summary).map_err(|e| e.to_string())?;
+    std::fs::write(&tmp, bytes)
+        .and_then(|_| std::fs::rename(&tmp, &path))
+        .map_err(|e| format!("Failed to write run summary: {}", e))
+}
+
+pub fn load(run_dir: &Path) -> Option<RunSummary> {
+    std::fs::read(summary_path(run_dir))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+}
+
+/// Loads a run's summary, building and storing it from the stored outcomes or
+/// retained results for runs uploaded before summaries existed.
+pub fn load_or_build(run_id: u32, run_dir: &Path, quarantine: &Quarantine) -> Option<RunSummary> {
+    if let Some(summary) = load(run_dir) {
+        return Some(summary);
+    }
+    let summary = build(run_id, &load_outcomes(run_dir)?, quarantine);
+    if let Err(e) = save(run_dir, &summary) {
+        tracing::warn!("run summary: {:?}: {}", run_dir, e);
+    }
+    Some(summary)
+}
@@ -0,0 +1,100 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::env;
+use std::sync::LazyLock;
+
+use crate::helpers::api_keys::constant_time_eq;
+
+/// First path segment of share links: `/share/{expires}/{signature}/{run path}/...`.
+pub const SHARE_PREFIX: &str = "share";
+
+pub const DEFAULT_SHARE_HOURS: u64 = 24;
+pub const MAX_SHARE_HOURS: u64 = 30 * 24;
+
+/// Signing key from SHARE_LINK_SECRET. Without it a random key is used and
+/// links stop working when the server restarts.
+static SHARE_KEY: LazyLock<Vec<u8>> = LazyLock::new(|| match env::var("SHARE_LINK_SECRET") {
+    Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+    _ => {
+        tracing::warn!("share_link: SHARE_LINK_SECRET not set, links end on restart");
+        format!("{}{}", uuid::Uuid::new_v4(), uuid::Uuid::new_v4()).into_bytes()
+    }
+});
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ShareError {
+    Malformed,
+    BadSignature,
+    Expired,
+}
+
+/// A verified share link: the run it grants and the real path requested.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedRun {
+    pub run_path: String,
+    pub path: String,
+}
+
+fn sign(run_path: &str, expires: i64) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&SHARE_KEY).expect("HMAC takes any key size");
+    mac.update(format!("{}\n{}", run_path, expires).as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Share path for a run path (`project/branch/report/id` or `.../raw/id`)
+/// valid until `expires` (epoch seconds).
+pub fn share_path(run_path: &str, expires: i64) -> String {
+    format!(
+        "/{}/{}/{}/{}/",
+        SHARE_PREFIX,
+        expires,
+        URL_SAFE_NO_PAD.encode(sign(run_path, expires)),
+        run_path
+    )
+}
+
+/// Number of leading segments naming a run, as in the static cache rules.
+fn run_path_len(segments: &[&str]) -> Option<usize> {
+    match segments {
+        [_, _, _, "raw", id, ..] if id.parse::<u32>().is_ok() => Some(5),
+        [_, _, _, id, ..] if id.parse::<u32>().is_ok() => Some(4),
+        _ => None,
+    }
+}
+
+/// Verifies a `/share/...` request path. The granted subtree is the signed run;
+/// `..` segments are refused so the path cannot leave it.
+pub fn verify(path: &str, now_secs: i64) -> Result<SharedRun, ShareError> {
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let [prefix, expires, signature, rest @ ..] = segments.as_slice() else {
+        return Err(ShareError::Malformed);
+    };
+    if *prefix != SHARE_PREFIX || rest.iter().any(|s| *s == ".." || *s == ".") {
+        return Err(ShareError::Malformed);
+    }
+    let expires: i64 = expires.parse().map_err(|_| ShareError::Malformed)?;
+    let len = run_path_len(rest).ok_or(ShareError::Malformed)?;
+    let run_path = rest[..len].join("/");
+
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| ShareError::BadSignature)?;
+    if !constant_time_eq(&sign(&run_path, expires), &signature) {
+        return Err(ShareError::BadSignature);
+    }
+    if expires <= now_secs {
+        return Err(ShareError::Expired);
+    }
+
+    // Keep the trailing slash so directory requests still resolve to index.html
+    let mut real = format!("/{}", rest.join("/"));
+    if path.ends_with('/') {
+        real.push('/');
+    }
+    Ok(SharedRun {
+        run_path,
+        path: real,
+    })
+}
@@ -0,0 +1,97 @@
+use serde::Serialize;
+use std::path::Path;
+use utoipa::ToSchema;
+
+use crate::helpers::fs_helper::{branch_report_dirs, report_run_dirs};
+use crate::helpers::test_results::load_outcomes;
+use crate::models::quarantine::QuarantineEntry;
+
+/// How a test is looked up: by Allure `historyId` or by full name.
+pub enum TestKey {
+    HistoryId(String),
+    FullName(String),
+}
+
+/// One run of a test.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TestRun {
+    pub report_name: String,
+    pub run_id: u32,
+    pub status: String,
+    /// Epoch milliseconds
+    pub start: Option<i64>,
+    pub duration_ms: Option<i64>,
+    pub message: Option<String>,
+    /// Deep link into the run's report
+    pub url: String,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct TestHistory {
+    pub history_id: Option<String>,
+    pub name: Option<String>,
+    pub full_name: Option<String>,
+    /// Oldest first
+    pub runs: Vec<TestRun>,
+    /// First run of the current failing streak, if the latest run failed
+    pub failing_since: Option<TestRun>,
+    /// Quarantine entry currently matching the test
+    pub quarantine: Option<QuarantineEntry>,
+}
+
+/// Collects every run of a test across the reports of a branch, or of a
+/// single report when `report` is given. `url_prefix` is the branch URL.
+pub fn test_history(
+    branch_dir: &Path,
+    report: Option<&str>,
+    url_prefix: &str,
+    key: &TestKey,
+) -> TestHistory {
+    let mut history = TestHistory::default();
+    for (report_name, report_dir) in branch_report_dirs(branch_dir) {
+        if report.is_some_and(|r| r != report_name) {
+            continue;
+        }
+        for (run_id, run_dir) in report_run_dirs(&report_dir) {
+            let Some(outcomes) = load_outcomes(&run_dir) else {
+                continue;
+            };
+            let Some(outcome) = outcomes.into_iter().find(|o| match key {
+                TestKey::HistoryId(id) => o.history_id == *id,
+                TestKey::FullName(name) => o.full_name.as_ref() == Some(name),
+            }) else {
+                continue;
+            };
+            if history.history_id.is_none() {
+                history.history_id = Some(outcome.history_id.clone());
+                history.name = Some(outcome.name.clone());
+                history.full_name = outcome.full_name.clone();
+            }
+            let run_url = format!("{}/{}/{}/index.html", url_prefix, report_name, run_id);
+            history.runs.push(TestRun {
+                report_name: report_name.clone(),
+                run_id,
+                duration_ms: outcome.duration_ms(),
+                url: outcome.link(&run_url),
+                status: outcome.status,
+                start: outcome.start,
+                message: outcome.message,
+            });
+        }
+    }
+
+    // Reports of a branch interleave in time; runs without a start sort last
+    history
+        .runs
+        .sort_by_key(|r| (r.start.unwrap_or(i64::MAX), r.report_name.clone(), r.run_id));
+
+    let is_failure = |r: &TestRun| r.status == "failed" || r.status == "broken";
+    history.failing_since = history
+        .runs
+        .iter()
+        .rev()
+        .take_while(|r| is_failure(r))
+        .last()
+        .cloned();
+    history
+}
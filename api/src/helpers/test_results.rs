@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::helpers::fs_helper::RUN_META_DIR;
+use crate::helpers::history::retained_results_dir;
+
+/// Compact per-test outcomes of a run, stored in the run's metadata directory
+/// so analyses don't depend on the full results being retained.
+pub const TESTS_FILE_NAME: &str = "tests.json";
+
+/// The outcome of one test in a run, read from an Allure `*-result.json` file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestOutcome {
+    /// `uuid` of the result file
+    pub uuid: Option<String>,
+    /// `historyId`, falling back to `fullName` for results without one
+    pub history_id: String,
+    pub name: String,
+    pub full_name: Option<String>,
+    /// passed, failed, broken, skipped or unknown
+    pub status: String,
+    pub start: Option<i64>,
+    pub stop: Option<i64>,
+    pub message: Option<String>,
+    /// Only available while reading result files, not stored in `tests.json`
+    #[serde(skip)]
+    pub trace: Option<String>,
+}
+
+impl TestOutcome {
+    pub fn duration_ms(&self) -> Option<i64> {
+        Some(self.stop? - self.start?).filter(|d| *d >= 0)
+    }
+
+    pub fn is_failure(&self) -> bool {
+        self.status == "failed" || self.status == "broken"
+    }
+
+    /// Deep link to this test's page in the run's generated report.
+    pub fn link(&self, run_url: &str) -> String {
+        match &self.uuid {
+            Some(uuid) => format!("{}#testresult/{}", run_url, uuid),
+            None => run_url.to_string(),
+        }
+    }
+}
+
+/// Reads the outcome of a single parsed result file.
+pub fn parse_result(value: &Value) -> Option<TestOutcome> {
+    let get_str = |key: &str| value.get(key).and_then(|v| v.as_str()).map(str::to_string);
+    let full_name = get_str("fullName");
+    let name = get_str("name").or_else(|| full_name.clone())?;
+    let history_id = get_str("historyId")
+        .or_else(|| full_name.clone())
+        .unwrap_or_else(|| name.clone());
+    let details = value.get("statusDetails");
+    let detail = |key: &str| {
+        details
+            .and_then(|d| d.get(key))
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+    };
+
+    Some(TestOutcome {
+        uuid: get_str("uuid"),
+        history_id,
+        name,
+        full_name,
+        status: get_str("status").unwrap_or_else(|| "unknown".to_string()),
+        start: value.get("start").and_then(|v| v.as_i64()),
+        stop: value.get("stop").and_then(|v| v.as_i64()),
+        message: detail("message"),
+        trace: detail("trace"),
+    })
+}
+
+/// Reads every `*-result.json` in `results_dir`. Retries of a test share its
+/// `historyId`; only the latest attempt is kept, as in the Allure report.
+pub fn read_results(results_dir: &Path) -> Vec<TestOutcome> {
+    let mut latest: HashMap<String, TestOutcome> = HashMap::new();
+    let Ok(entries) = std::fs::read_dir(results_dir) else {
+        return Vec::new();
+    };
+    for entry in entries.flatten() {
+        if !entry
+            .file_name()
+            .to_string_lossy()
+            .ends_with("-result.json")
+        {
+            continue;
+        }
+        let Some(outcome) = std::fs::read(entry.path())
+            .ok()
+            .and_then(|b| serde_json::from_slice::<Value>(&b).ok())
+            .as_ref()
+            .and_then(parse_result)
+        else {
+            continue;
+        };
+        match latest.get(&outcome.history_id) {
+            Some(existing) if existing.start >= outcome.start => {}
+            _ => {
+                latest.insert(outcome.history_id.clone(), outcome);
+            }
+        }
+    }
+
+    let mut outcomes: Vec<TestOutcome> = latest.into_values().collect();
+    outcomes.sort_by(|a, b| a.history_id.cmp(&b.history_id));
+    outcomes
+}
+
+pub fn tests_path(run_dir: &Path) -> PathBuf {
+    run_dir.join(RUN_META_DIR).join(TESTS_FILE_NAME)
+}
+
+pub fn save_outcomes(run_dir: &Path, outcomes: &[TestOutcome]) -> Result<(), String> {
+    let path = tests_path(run_dir);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    let bytes = serde_json::to_vec(outcomes).map_err(|e| e.to_string())?;
+    std::fs::write(&tmp, bytes)
+        .and_then(|_| std::fs::rename(&tmp, &path))
+        .map_err(|e| format!("Failed to write test outcomes: {}", e))
+}
+
+/// A run's test outcomes from `tests.json`, falling back to its retained
+/// results. `None` when the run has neither.
+pub fn load_outcomes(run_dir: &Path) -> Option<Vec<TestOutcome>> {
+    if let Some(outcomes) = std::fs::read(tests_path(run_dir))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+    {
+        return Some(outcomes);
+    }
+    let results = retained_results_dir(run_dir);
+    results.is_dir().then(|| read_results(&results))
+}
+
+/// Like [`load_outcomes`], but prefers the retained results so failure traces
+/// are available.
+pub fn load_outcomes_with_traces(run_dir: &Path) -> Option<Vec<TestOutcome>> {
+    let results = retained_results_dir(run_dir);
+    if results.is_dir() {
+        return Some(read_results(&results));
+    }
+    load_outcomes(run_dir)
+}
@@ -0,0 +1,134 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+use utoipa::ToSchema;
+
+use crate::helpers::fs_helper::report_run_dirs;
+use crate::helpers::history::{read_lines, summarize};
+use crate::helpers::quarantine::Quarantine;
+use crate::helpers::run_summary::{RunSummary, load_or_build, pass_rate};
+
+/// One point of a report's trend, i.e. one run.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TrendPoint {
+    /// Run id, when known
+    pub run_id: Option<u32>,
+    /// When the run finished, epoch milliseconds
+    pub timestamp: Option<i64>,
+    pub total: usize,
+    pub statuses: BTreeMap<String, usize>,
+    pub pass_rate: Option<f64>,
+    /// Wall-clock duration of the run
+    pub duration_ms: Option<i64>,
+    /// Sum of the individual test durations
+    pub tests_duration_ms: i64,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Trend {
+    /// `runs` when built from run summaries, `history` when from `history.jsonl`
+    pub source: String,
+    pub points: Vec<TrendPoint>,
+}
+
+fn from_summary(summary: RunSummary, url: String) -> TrendPoint {
+    TrendPoint {
+        run_id: Some(summary.run_id),
+        timestamp: summary.finished_at,
+        total: summary.total,
+        statuses: summary.statuses,
+        pass_rate: summary.pass_rate,
+        duration_ms: summary.duration_ms,
+        tests_duration_ms: summary.tests_duration_ms,
+        url: Some(url),
+    }
+}
+
+/// Run id from a run URL such as `/project/branch/report/42/index.html`.
+fn run_id_from_url(url: &str) -> Option<u32> {
+    url.split('/').rev().find_map(|s| s.parse::<u32>().ok())
+}
+
+fn from_history_line(line: &str) -> TrendPoint {
+    let entry = summarize(0, line, true);
+    let mut tests_duration_ms = 0;
+    let mut started_at: Option<i64> = None;
+    let mut finished_at: Option<i64> = None;
+    if let Some(results) = entry
+        .entry
+        .as_ref()
+        .and_then(|v| v.get("testResults"))
+        .and_then(Value::as_object)
+    {
+        for result in results.values() {
+            let field = |key: &str| result.get(key).and_then(Value::as_i64);
+            tests_duration_ms += field("duration").unwrap_or(0);
+            if let Some(start) = field("start") {
+                started_at = Some(started_at.map_or(start, |s| s.min(start)));
+            }
+            if let Some(stop) = field("stop") {
+                finished_at = Some(finished_at.map_or(stop, |s| s.max(stop)));
+            }
+        }
+    }
+
+    TrendPoint {
+        run_id: entry.url.as_deref().and_then(run_id_from_url),
+        timestamp: entry.timestamp.or(finished_at),
+        total: entry.tests,
+        pass_rate: pass_rate(&entry.statuses),
+        statuses: entry.statuses,
+        duration_ms: finished_at
+            .zip(started_at)
+            .map(|(stop, start)| stop - start),
+        tests_duration_ms,
+        url: entry.url,
+    }
+}
+
+/// Builds a report's trend, oldest first. Run summaries are used when any run
+/// has one; otherwise the points come from `history.jsonl`. Points outside
+/// `from..=to` (epoch ms) are dropped, then only the last `limit` are kept.
+pub fn build_trend(
+    report_dir: &Path,
+    url_prefix: &str,
+    quarantine: &Quarantine,
+    from: Option<i64>,
+    to: Option<i64>,
+    limit: Option<usize>,
+) -> Result<Trend, String> {
+    let mut source = "runs";
+    let mut points: Vec<TrendPoint> = report_run_dirs(report_dir)
+        .into_iter()
+        .filter_map(|(id, dir)| {
+            load_or_build(id, &dir, quarantine)
+                .map(|s| from_summary(s, format!("{}/{}/index.html", url_prefix, id)))
+        })
+        .collect();
+    if points.is_empty() {
+        source = "history";
+        points = read_lines(report_dir)?
+            .iter()
+            .map(|l| from_history_line(l))
+            .collect();
+    }
+
+    if from.is_some() || to.is_some() {
+        points.retain(|p| {
+            p.timestamp
+                .is_some_and(|ts| from.is_none_or(|f| ts >= f) && to.is_none_or(|t| ts <= t))
+        });
+    }
+    if let Some(limit) = limit
+        && points.len() > limit
+    {
+        points.drain(..points.len() - limit);
+    }
+
+    Ok(Trend {
+        source: source.to_string(),
+        points,
+    })
+}
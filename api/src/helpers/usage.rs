@@ -0,0 +1,270 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use utoipa::ToSchema;
+
+use crate::helpers::fs_helper::{RunLocation, list_run_dirs};
+use crate::helpers::metrics;
+
+/// Index file persisted at the root of DATA_DIR.
+pub const USAGE_FILE_NAME: &str = ".usage.json";
+
+/// Lock file serializing index updates of server processes sharing DATA_DIR.
+pub const USAGE_LOCK_NAME: &str = ".usage.lock";
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema, Clone, Copy)]
+pub struct Usage {
+    pub bytes: u64,
+    pub files: u64,
+}
+
+impl Usage {
+    fn add(&mut self, other: Usage) {
+        self.bytes += other.bytes;
+        self.files += other.files;
+    }
+}
+
+/// Per-run usage keyed by the run's path relative to DATA_DIR.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UsageIndex {
+    runs: BTreeMap<String, Usage>,
+}
+
+/// This process's copy of the index. Changes are merged into the file under
+/// [`USAGE_LOCK_NAME`], so runs recorded by other processes are kept.
+static INDEX: LazyLock<Mutex<UsageIndex>> = LazyLock::new(|| Mutex::new(UsageIndex::default()));
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ReportUsage {
+    #[serde(flatten)]
+    pub usage: Usage,
+    pub runs: BTreeMap<String, Usage>,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct BranchUsage {
+    #[serde(flatten)]
+    pub usage: Usage,
+    pub reports: BTreeMap<String, ReportUsage>,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ProjectUsage {
+    #[serde(flatten)]
+    pub usage: Usage,
+    pub branches: BTreeMap<String, BranchUsage>,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct UsageReport {
+    #[serde(flatten)]
+    pub usage: Usage,
+    pub projects: BTreeMap<String, ProjectUsage>,
+}
+
+fn run_key(run: &RunLocation) -> String {
+    if run.raw {
+        format!(
+            "{}/{}/{}/raw/{}",
+            run.project, run.branch, run.report, run.id
+        )
+    } else {
+        format!("{}/{}/{}/{}", run.project, run.branch, run.report, run.id)
+    }
+}
+
+/// Splits a run key into project, branch, report and run label (`42` or `raw/42`).
+fn split_key(key: &str) -> Option<(&str, &str, &str, &str)> {
+    let mut parts = key.splitn(4, '/');
+    Some((parts.next()?, parts.next()?, parts.next()?, parts.next()?))
+}
+
+fn index_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(USAGE_FILE_NAME)
+}
+
+/// Apparent size of every file below `dir`. Hardlinked files count fully,
+/// dedup savings are reported separately by the dedup store.
+pub fn measure_dir(dir: &Path) -> Usage {
+    let mut usage = Usage::default();
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let Ok(ft) = entry.file_type() else { continue };
+            if ft.is_dir() {
+                usage.add(measure_dir(&entry.path()));
+            } else if ft.is_file()
+                && let Ok(meta) = entry.metadata()
+            {
+                usage.bytes += meta.len();
+                usage.files += 1;
+            }
+        }
+    }
+    usage
+}
+
+fn persist(data_dir: &Path, index: &UsageIndex) {
+    let path = index_path(data_dir);
+    let tmp = path.with_extension("json.tmp");
+    match serde_json::to_vec(index) {
+        Ok(bytes) => {
+            if let Err(e) = std::fs::write(&tmp, bytes).and_then(|_| std::fs::rename(&tmp, &path)) {
+                tracing::warn!("usage: failed to persist {:?}: {}", path, e);
+            }
+        }
+        Err(e) => tracing::warn!("usage: failed to serialize index: {}", e),
+    }
+}
+
+fn publish_metrics(index: &UsageIndex) {
+    let mut per_branch: BTreeMap<(&str, &str), Usage> = BTreeMap::new();
+    for (key, usage) in &index.runs {
+        if let Some((project, branch, _, _)) = split_key(key) {
+            per_branch.entry((project, branch)).or_default().add(*usage);
+        }
+    }
+
+    metrics::gauge_reset("allure_host_usage_bytes");
+    metrics::gauge_reset("allure_host_usage_files");
+    for ((project, branch), usage) in per_branch {
+        let labels = [("project", project), ("branch", branch)];
+        metrics::gauge_set("allure_host_usage_bytes", &labels, usage.bytes as f64);
+        metrics::gauge_set("allure_host_usage_files", &labels, usage.files as f64);
+    }
+}
+
+/// Loads the persisted index, or builds it with a one-time scan of DATA_DIR.
+pub fn init(data_dir: &Path) {
+    metrics::describe(
+        "allure_host_usage_bytes",
+        "gauge",
+        "Bytes stored per project and branch",
+    );
+    metrics::describe(
+        "allure_host_usage_files",
+        "gauge",
+        "Files stored per project and branch",
+    );
+
+    match load(data_dir) {
+        Some(index) => {
+            publish_metrics(&index);
+            *INDEX.lock().unwrap() = index;
+        }
+        None => {
+            tracing::info!("usage: no index found, scanning {:?}", data_dir);
+            rescan(data_dir);
+        }
+    }
+}
+
+fn load(data_dir: &Path) -> Option<UsageIndex> {
+    std::fs::read(index_path(data_dir))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<UsageIndex>(&bytes).ok())
+}
+
+/// Applies `change` to the latest persisted index while holding the usage file
+/// lock, then persists it. Blocking.
+fn update(data_dir: &Path, change: impl FnOnce(&mut UsageIndex)) {
+    let mut current = INDEX.lock().unwrap();
+    let lock_path = data_dir.join(USAGE_LOCK_NAME);
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .and_then(|file| file.lock().map(|_| file));
+    if let Err(e) = &lock {
+        tracing::warn!("usage: updating without lock {:?}: {}", lock_path, e);
+    }
+
+    let mut index = load(data_dir).unwrap_or_else(|| std::mem::take(&mut *current));
+    change(&mut index);
+    persist(data_dir, &index);
+    publish_metrics(&index);
+    *current = index;
+    if let Ok(file) = lock {
+        let _ = file.unlock();
+    }
+}
+
+/// Rebuilds the index from disk.
+pub fn rescan(data_dir: &Path) -> Usage {
+    let mut fresh = UsageIndex::default();
+    for run in list_run_dirs(data_dir) {
+        fresh.runs.insert(run_key(&run), measure_dir(&run.path));
+    }
+    let mut total = Usage::default();
+    for usage in fresh.runs.values() {
+        total.add(*usage);
+    }
+    update(data_dir, |index| *index = fresh);
+    total
+}
+
+/// Re-measures a single run after it was created or changed (upload, archive, rehydrate).
+pub fn record_run(data_dir: &Path, run: &RunLocation) {
+    let usage = measure_dir(&run.path);
+    update(data_dir, |index| {
+        index.runs.insert(run_key(run), usage);
+    });
+}
+
+fn key_in_prefix(key: &str, prefix: &str) -> bool {
+    key.strip_prefix(prefix)
+        .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Drops every run below a moved or deleted directory (`project[/branch[/report]]`).
+pub fn forget_prefix(data_dir: &Path, prefix: &str) {
+    update(data_dir, |index| {
+        index.runs.retain(|key, _| !key_in_prefix(key, prefix));
+    });
+}
+
+/// Measures every run below a new or changed directory (`project[/branch[/report]]`).
+pub fn record_prefix(data_dir: &Path, prefix: &str) {
+    let runs: Vec<(String, Usage)> = list_run_dirs(data_dir)
+        .into_iter()
+        .map(|run| (run_key(&run), run))
+        .filter(|(key, _)| key_in_prefix(key, prefix))
+        .map(|(key, run)| (key, measure_dir(&run.path)))
+        .collect();
+
+    update(data_dir, |index| {
+        index.runs.retain(|key, _| !key_in_prefix(key, prefix));
+        index.runs.extend(runs);
+    });
+}
+
+/// Aggregates the index into a project/branch/report/run tree. Re-reads the
+/// file so runs recorded by other processes are included. Blocking.
+pub fn report(data_dir: &Path, project: Option<&str>) -> UsageReport {
+    let mut index = INDEX.lock().unwrap();
+    if let Some(latest) = load(data_dir) {
+        publish_metrics(&latest);
+        *index = latest;
+    }
+    let mut out = UsageReport::default();
+    for (key, usage) in &index.runs {
+        let Some((p, b, r, run)) = split_key(key) else {
+            continue;
+        };
+        if project.is_some_and(|wanted| wanted != p) {
+            continue;
+        }
+        out.usage.add(*usage);
+        let project = out.projects.entry(p.to_string()).or_default();
+        project.usage.add(*usage);
+        let branch = project.branches.entry(b.to_string()).or_default();
+        branch.usage.add(*usage);
+        let report = branch.reports.entry(r.to_string()).or_default();
+        report.usage.add(*usage);
+        report.runs.insert(run.to_string(), *usage);
+    }
+    out
+}
@@ -0,0 +1,320 @@
+use argon2::password_hash::SaltString;
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, Uri, header},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use percent_encoding::{NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+use std::sync::{Arc, LazyLock};
+
+use crate::helpers::access_control::AccessControl;
+use crate::helpers::api_keys::constant_time_eq;
+use crate::helpers::quarantine::now_ms;
+use crate::helpers::share_link::SharedRun;
+
+pub const SESSION_COOKIE: &str = "report_session";
+
+/// Route listing every project, see `handlers::manifest`.
+pub const MANIFEST_PATH: &str = "/manifest.json";
+
+/// A viewer account of the VIEWER_USERS_FILE.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ViewerUser {
+    pub username: String,
+    /// Argon2 PHC string, e.g. `$argon2id$v=19$m=19456,t=2,p=1$...`
+    pub password_hash: String,
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UsersFile {
+    #[serde(default)]
+    users: Vec<ViewerUser>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionClaims {
+    /// Username
+    u: String,
+    /// Expiry, epoch seconds
+    exp: i64,
+}
+
+/// Hash checked for unknown usernames, so they take as long as wrong passwords.
+static DUMMY_HASH: LazyLock<String> = LazyLock::new(|| {
+    let salt = SaltString::encode_b64(b"unknown-viewer").expect("salt is valid");
+    Argon2::default()
+        .hash_password(b"", &salt)
+        .expect("default argon2 parameters are valid")
+        .to_string()
+});
+
+/// Checks a password against an argon2 PHC string; `None` verifies against a
+/// dummy hash and fails. Slow on purpose.
+pub fn verify_password_hash(phc: Option<&str>, password: &str) -> bool {
+    let Some(phc) = phc else {
+        let _ = PasswordHash::new(&DUMMY_HASH)
+            .map(|hash| Argon2::default().verify_password(password.as_bytes(), &hash));
+        return false;
+    };
+    PasswordHash::new(phc).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+/// Reads and validates the users file.
+pub fn load_users(path: &Path) -> Result<Vec<ViewerUser>, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let file: UsersFile = serde_json::from_slice(&bytes)
+        .map_err(|e| format!("Invalid users file {:?}: {}", path, e))?;
+    for (i, user) in file.users.iter().enumerate() {
+        if user.username.is_empty() {
+            return Err(format!("user #{} has no username", i + 1));
+        }
+        if file.users[..i].iter().any(|u| u.username == user.username) {
+            return Err(format!("duplicate user '{}'", user.username));
+        }
+        if PasswordHash::new(&user.password_hash).is_err() {
+            return Err(format!(
+                "user '{}': password_hash must be an argon2 PHC string",
+                user.username
+            ));
+        }
+    }
+    Ok(file.users)
+}
+
+/// Key signing session cookies, from SESSION_SECRET. Without it a random key is
+/// used and sessions end when the server restarts.
+pub fn session_key() -> Vec<u8> {
+    match env::var("SESSION_SECRET") {
+        Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+        _ => {
+            tracing::warn!("viewer_auth: SESSION_SECRET not set, sessions end on restart");
+            format!("{}{}", uuid::Uuid::new_v4(), uuid::Uuid::new_v4()).into_bytes()
+        }
+    }
+}
+
+/// Session lifetime in seconds (SESSION_TTL_HOURS, default 12).
+pub fn session_ttl() -> i64 {
+    env::var("SESSION_TTL_HOURS")
+        .ok()
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .filter(|h| *h > 0)
+        .unwrap_or(12)
+        * 3600
+}
+
+/// The logged-in viewer of a request, added by `viewer_session`.
+#[derive(Clone)]
+pub struct ViewerSession {
+    pub user: ViewerUser,
+    auth: Arc<ViewerAuth>,
+}
+
+impl ViewerSession {
+    pub fn may_view(&self, project: &str) -> bool {
+        self.auth.may_view(&self.user, Some(project))
+    }
+}
+
+pub struct ViewerAuth {
+    users: HashMap<String, ViewerUser>,
+    key: Vec<u8>,
+    ttl_secs: i64,
+    secure: bool,
+    access: Arc<AccessControl>,
+}
+
+impl ViewerAuth {
+    pub fn new(
+        users: Vec<ViewerUser>,
+        key: Vec<u8>,
+        ttl_secs: i64,
+        access: Arc<AccessControl>,
+    ) -> Self {
+        Self {
+            users: users.into_iter().map(|u| (u.username.clone(), u)).collect(),
+            key,
+            ttl_secs,
+            secure: env::var("RUST_ENV").unwrap_or_default() == "production",
+            access,
+        }
+    }
+
+    /// Checks a password against the user's argon2 hash. Slow on purpose, and
+    /// as slow for unknown users so they can't be told apart.
+    pub fn verify_password(&self, username: &str, password: &str) -> bool {
+        let phc = self.users.get(username).map(|u| u.password_hash.as_str());
+        verify_password_hash(phc, password)
+    }
+
+    fn sign(&self, payload: &str) -> Vec<u8> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC takes any key size");
+        mac.update(payload.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    /// Signed cookie value `{claims}.{signature}` for a new session.
+    pub fn issue(&self, username: &str, now_secs: i64) -> String {
+        let claims = SessionClaims {
+            u: username.to_string(),
+            exp: now_secs + self.ttl_secs,
+        };
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).unwrap_or_default());
+        let signature = URL_SAFE_NO_PAD.encode(self.sign(&payload));
+        format!("{}.{}", payload, signature)
+    }
+
+    /// The user of a valid, unexpired session cookie who still exists.
+    pub fn session(&self, value: &str, now_secs: i64) -> Option<&ViewerUser> {
+        let (payload, signature) = value.split_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        if !constant_time_eq(&self.sign(payload), &signature) {
+            return None;
+        }
+        let claims: SessionClaims =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+        if claims.exp <= now_secs {
+            return None;
+        }
+        self.users.get(&claims.u)
+    }
+
+    /// Whether the user's groups may view a project.
+    pub fn may_view(&self, user: &ViewerUser, project: Option<&str>) -> bool {
+        self.access.groups_allowed(project, &user.groups)
+    }
+
+    pub fn session_cookie(&self, value: &str) -> String {
+        format!(
+            "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}{}",
+            SESSION_COOKIE,
+            value,
+            self.ttl_secs,
+            if self.secure { "; Secure" } else { "" }
+        )
+    }
+
+    pub fn clear_cookie(&self) -> String {
+        format!(
+            "{}=; Path=/; HttpOnly; SameSite=Lax; Max-Age=0{}",
+            SESSION_COOKIE,
+            if self.secure { "; Secure" } else { "" }
+        )
+    }
+}
+
+pub fn now_secs() -> i64 {
+    now_ms() / 1000
+}
+
+/// Value of the session cookie in a request.
+pub fn session_cookie_value(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value)
+}
+
+/// Only local paths are followed after login, never other hosts: `next` must
+/// be a path-only URI without control characters, which browsers would strip
+/// (turning `/\t/evil.example` into `//evil.example`).
+pub fn safe_next(next: Option<&str>) -> String {
+    next.filter(|n| {
+        n.starts_with('/')
+            && !n.starts_with("//")
+            && !n.contains('\\')
+            && !n.chars().any(|c| c.is_ascii_control())
+            && n.parse::<Uri>()
+                .is_ok_and(|uri| uri.scheme().is_none() && uri.authority().is_none())
+    })
+    .map(|n| n.to_string())
+    .unwrap_or_else(|| "/".to_string())
+}
+
+/// Middleware requiring a viewer session when VIEWER_USERS_FILE is configured.
+/// Browsers are sent to the login page, other clients get 401.
+pub async fn viewer_session(
+    State(viewer): State<Option<Arc<ViewerAuth>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(viewer) = viewer else {
+        return next.run(request).await;
+    };
+    if request.extensions().get::<SharedRun>().is_some() {
+        return next.run(request).await;
+    }
+
+    // The manifest spans projects and filters them by the session itself
+    let project = Some(request.uri().path())
+        .filter(|path| *path != MANIFEST_PATH)
+        .and_then(|path| path.split('/').find(|s| !s.is_empty()))
+        .map(|s| percent_decode_str(s).decode_utf8_lossy().into_owned());
+    let user =
+        session_cookie_value(request.headers()).and_then(|value| viewer.session(value, now_secs()));
+
+    match user {
+        Some(user) if viewer.may_view(user, project.as_deref()) => {
+            let session = ViewerSession {
+                user: user.clone(),
+                auth: viewer.clone(),
+            };
+            let mut request = request;
+            request.extensions_mut().insert(session);
+            next.run(request).await
+        }
+        Some(user) => {
+            tracing::warn!(
+                "viewer_auth: '{}' may not view project {:?}",
+                user.username,
+                project
+            );
+            (StatusCode::FORBIDDEN, "Forbidden").into_response()
+        }
+        None => {
+            let wants_html = request
+                .headers()
+                .get(header::ACCEPT)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| v.contains("text/html"));
+            if request.method() == Method::GET && wants_html {
+                let target = request
+                    .uri()
+                    .path_and_query()
+                    .map(|pq| pq.as_str())
+                    .unwrap_or("/");
+                let location = format!(
+                    "/login?next={}",
+                    utf8_percent_encode(target, NON_ALPHANUMERIC)
+                );
+                Redirect::to(&location).into_response()
+            } else {
+                let mut response = (StatusCode::UNAUTHORIZED, "Login required").into_response();
+                response
+                    .headers_mut()
+                    .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+                response
+            }
+        }
+    }
+}
@@ -4,21 +4,69 @@ pub mod handlers {
 
     #[path = "manifest.rs"]
     pub mod manifest;
+
+    #[path = "login.rs"]
+    pub mod login;
+
+    #[path = "admin_handler.rs"]
+    pub mod admin_handler;
+
+    #[path = "run_handler.rs"]
+    pub mod run_handler;
+
+    #[path = "history_handler.rs"]
+    pub mod history_handler;
+
+    #[path = "project_handler.rs"]
+    pub mod project_handler;
+
+    #[path = "analysis_handler.rs"]
+    pub mod analysis_handler;
 }
 
 pub mod models {
     #[path = "report.rs"]
     pub mod report;
+
+    #[path = "history.rs"]
+    pub mod history;
+
+    #[path = "project.rs"]
+    pub mod project;
+
+    #[path = "quarantine.rs"]
+    pub mod quarantine;
 }
 
 pub mod routes {
     #[path = "report_route.rs"]
     pub mod report_route;
+
+    #[path = "admin_route.rs"]
+    pub mod admin_route;
+
+    #[path = "project_route.rs"]
+    pub mod project_route;
 }
 
 pub mod services {
     #[path = "report_service.rs"]
     pub mod report_service;
+
+    #[path = "admin_service.rs"]
+    pub mod admin_service;
+
+    #[path = "run_service.rs"]
+    pub mod run_service;
+
+    #[path = "history_service.rs"]
+    pub mod history_service;
+
+    #[path = "project_service.rs"]
+    pub mod project_service;
+
+    #[path = "analysis_service.rs"]
+    pub mod analysis_service;
 }
 
 pub mod helpers {
@@ -36,6 +84,90 @@ pub mod helpers {
 
     #[path = "access_control.rs"]
     pub mod access_control;
+
+    #[path = "dedup.rs"]
+    pub mod dedup;
+
+    #[path = "env_helper.rs"]
+    pub mod env_helper;
+
+    #[path = "precompress.rs"]
+    pub mod precompress;
+
+    #[path = "archive.rs"]
+    pub mod archive;
+
+    #[path = "metrics.rs"]
+    pub mod metrics;
+
+    #[path = "usage.rs"]
+    pub mod usage;
+
+    #[path = "relocate.rs"]
+    pub mod relocate;
+
+    #[path = "history_lock.rs"]
+    pub mod history_lock;
+
+    #[path = "history.rs"]
+    pub mod history;
+
+    #[path = "project_settings.rs"]
+    pub mod project_settings;
+
+    #[path = "test_results.rs"]
+    pub mod test_results;
+
+    #[path = "run_summary.rs"]
+    pub mod run_summary;
+
+    #[path = "trend.rs"]
+    pub mod trend;
+
+    #[path = "flaky.rs"]
+    pub mod flaky;
+
+    #[path = "test_history.rs"]
+    pub mod test_history;
+
+    #[path = "compare.rs"]
+    pub mod compare;
+
+    #[path = "clusters.rs"]
+    pub mod clusters;
+
+    #[path = "quality_gate.rs"]
+    pub mod quality_gate;
+
+    #[path = "quarantine.rs"]
+    pub mod quarantine;
+
+    #[path = "glob.rs"]
+    pub mod glob;
+
+    #[path = "api_keys.rs"]
+    pub mod api_keys;
+
+    #[path = "jwt.rs"]
+    pub mod jwt;
+
+    #[path = "auth.rs"]
+    pub mod auth;
+
+    #[path = "viewer_auth.rs"]
+    pub mod viewer_auth;
+
+    #[path = "share_link.rs"]
+    pub mod share_link;
+
+    #[path = "proxy_protocol.rs"]
+    pub mod proxy_protocol;
+
+    #[path = "rate_limit.rs"]
+    pub mod rate_limit;
+
+    #[path = "audit.rs"]
+    pub mod audit;
 }
 
 pub mod route;
@@ -1,5 +1,26 @@
-use api::handlers::manifest::get_manifest;
-use api::helpers::access_control::{AccessControl, access_control};
+use api::handlers::login::{login, login_page, logout};
+use api::handlers::manifest::{ManifestState, get_manifest};
+use api::helpers::access_control::{
+    AccessControl, ForwardedHeader, access_control, load_project_access,
+};
+use api::helpers::api_keys::{
+    has_keys, load_keys, reload_if_changed, reload_interval, reload_keys,
+};
+use api::helpers::archive::{archive_after, archive_interval, archive_old_runs, serve_archived};
+use api::helpers::audit::{self, audit};
+use api::helpers::auth::{MetricsAccess, auth, metrics_auth};
+use api::helpers::fs_helper::StorageOptions;
+use api::helpers::history_lock;
+use api::helpers::jwt;
+use api::helpers::metrics::metrics_handler;
+use api::helpers::precompress::{CachePolicy, cache_headers};
+use api::helpers::proxy_protocol;
+use api::helpers::rate_limit::{self, limit_by_ip};
+use api::helpers::relocate::{load_redirects, redirect_moved};
+use api::helpers::usage;
+use api::helpers::viewer_auth::{
+    MANIFEST_PATH, ViewerAuth, load_users, session_key, session_ttl, viewer_session,
+};
 use axum::{
     Json, Router,
     extract::{DefaultBodyLimit, Request},
@@ -23,12 +44,82 @@ use utoipa_swagger_ui::SwaggerUi;
 #[derive(OpenApi)]
 #[openapi(
     paths(
-        api::handlers::report_handler::upload_report
+        api::handlers::report_handler::upload_report,
+        api::handlers::admin_handler::dedup_stats,
+        api::handlers::admin_handler::dedup_gc,
+        api::handlers::admin_handler::archive_now,
+        api::handlers::admin_handler::rehydrate,
+        api::handlers::admin_handler::usage,
+        api::handlers::admin_handler::usage_rescan,
+        api::handlers::admin_handler::relocate,
+        api::handlers::admin_handler::list_redirects,
+        api::handlers::admin_handler::delete_redirect,
+        api::handlers::admin_handler::list_keys,
+        api::handlers::admin_handler::reload_keys,
+        api::handlers::admin_handler::audit_log,
+        api::handlers::history_handler::list_history,
+        api::handlers::history_handler::trim_history,
+        api::handlers::history_handler::remove_history_entries,
+        api::handlers::history_handler::reset_history,
+        api::handlers::history_handler::rebuild_history,
+        api::handlers::project_handler::get_settings,
+        api::handlers::project_handler::put_settings,
+        api::handlers::run_handler::get_run_summary,
+        api::handlers::run_handler::share_run,
+        api::handlers::analysis_handler::trend,
+        api::handlers::analysis_handler::flaky_tests,
+        api::handlers::analysis_handler::test_history,
+        api::handlers::analysis_handler::compare_runs,
+        api::handlers::analysis_handler::failure_clusters,
+        api::handlers::analysis_handler::quality_gate,
+        api::handlers::project_handler::list_quarantine,
+        api::handlers::project_handler::add_quarantine,
+        api::handlers::project_handler::remove_quarantine
     ),
     components(
         schemas(
             api::models::report::ReportType,
-            api::models::report::FileUploadRequest
+            api::models::report::FileUploadRequest,
+            api::models::report::RunRef,
+            api::helpers::dedup::DedupStats,
+            api::helpers::archive::ArchiveSummary,
+            api::helpers::usage::Usage,
+            api::helpers::usage::UsageReport,
+            api::helpers::usage::ProjectUsage,
+            api::helpers::usage::BranchUsage,
+            api::helpers::usage::ReportUsage,
+            api::models::report::TreeRef,
+            api::models::report::RelocateMode,
+            api::models::report::RelocateRequest,
+            api::helpers::relocate::RelocateResult,
+            api::helpers::relocate::Redirect,
+            api::helpers::api_keys::Scope,
+            api::helpers::api_keys::KeyInfo,
+            api::helpers::api_keys::SecretInfo,
+            api::helpers::audit::AuditEntry,
+            api::helpers::audit::AuditResult,
+            api::models::history::TrimHistoryRequest,
+            api::models::history::RemoveHistoryEntriesRequest,
+            api::helpers::history::HistoryEntrySummary,
+            api::models::project::ProjectSettings,
+            api::helpers::run_summary::RunSummary,
+            api::helpers::trend::TrendPoint,
+            api::helpers::flaky::FlakyTest,
+            api::helpers::flaky::RunOutcome,
+            api::helpers::test_history::TestHistory,
+            api::helpers::test_history::TestRun,
+            api::helpers::compare::Comparison,
+            api::helpers::compare::ComparedRun,
+            api::helpers::compare::ComparedTest,
+            api::helpers::compare::DurationChange,
+            api::helpers::clusters::FailureCluster,
+            api::helpers::clusters::ClusterExample,
+            api::helpers::quality_gate::GateVerdict,
+            api::helpers::quality_gate::GateCheck,
+            api::models::project::QualityGateRules,
+            api::models::quarantine::QuarantineEntry,
+            api::models::quarantine::AddQuarantineRequest,
+            api::helpers::quarantine::QuarantinedTest
         )
     ),
     modifiers(&SecurityAddon),
@@ -58,12 +149,60 @@ async fn main() {
         dotenv().ok();
     }
 
-    if env::var("API_SECRET").is_err() || env::var("API_SECRET").unwrap().is_empty() {
-        panic!("CRITICAL ERROR: API_SECRET environment variable is not set or is empty.");
-    }
-
     tracing_subscriber::fmt::init();
 
+    if let Ok(keys_file) = env::var("API_KEYS_FILE")
+        && !keys_file.is_empty()
+    {
+        match load_keys(std::path::Path::new(&keys_file)) {
+            Ok(count) => tracing::info!("auth: loaded {} API keys from {}", count, keys_file),
+            Err(e) => panic!("CRITICAL ERROR: {}", e),
+        }
+    }
+    if let Some(source) = jwt::source_from_env() {
+        let loaded =
+            tokio::task::spawn_blocking(move || jwt::configure(source, jwt::config_from_env()))
+                .await
+                .unwrap();
+        match loaded {
+            Ok(count) => tracing::info!("auth: loaded {} JWKS keys for bearer tokens", count),
+            Err(e) => panic!("CRITICAL ERROR: {}", e),
+        }
+    }
+    if env::var("API_KEYS_FILE").is_ok_and(|f| !f.is_empty()) {
+        if let Some(every) = reload_interval() {
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(every);
+                loop {
+                    interval.tick().await;
+                    match tokio::task::spawn_blocking(reload_if_changed).await {
+                        Ok(Some(Ok(count))) => tracing::info!("auth: reloaded {} API keys", count),
+                        Ok(Some(Err(e))) => tracing::error!("auth: keeping previous keys: {}", e),
+                        Ok(None) => {}
+                        Err(e) => tracing::error!("auth: key reload panicked: {}", e),
+                    }
+                }
+            });
+        }
+        tokio::spawn(async move {
+            let Ok(mut hangup) =
+                tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+            else {
+                return;
+            };
+            while hangup.recv().await.is_some() {
+                match tokio::task::spawn_blocking(reload_keys).await {
+                    Ok(Ok(count)) => tracing::info!("auth: reloaded {} API keys on SIGHUP", count),
+                    Ok(Err(e)) => tracing::error!("auth: keeping previous keys: {}", e),
+                    Err(e) => tracing::error!("auth: key reload panicked: {}", e),
+                }
+            }
+        });
+    }
+    if !has_keys() {
+        panic!("CRITICAL ERROR: neither API_SECRET nor API_KEYS_FILE provides an API key.");
+    }
+
     let data_dir = env::var("DATA_DIR").unwrap_or_else(|_| "../data".to_string());
 
     let allowed_proxies: Vec<String> = std::env::var("ALLOWED_PROXY_IPS")
@@ -80,26 +219,183 @@ async fn main() {
         .filter(|s| !s.is_empty())
         .collect();
 
-    let ac = Arc::new(AccessControl::new(allowed_ips, allowed_proxies));
+    let trusted_hops = env::var("TRUSTED_PROXY_HOPS")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let trusted_balancers = proxy_protocol::trusted_from_env();
+    if proxy_protocol::is_enabled() && trusted_balancers.is_empty() {
+        panic!(
+            "CRITICAL ERROR: PROXY_PROTOCOL needs the balancer addresses in PROXY_PROTOCOL_TRUSTED or ALLOWED_PROXY_IPS."
+        );
+    }
+
+    let forwarded_header = match env::var("FORWARDED_HEADER") {
+        Ok(header) if !header.trim().is_empty() => match header.parse::<ForwardedHeader>() {
+            Ok(header) => header,
+            Err(e) => panic!("CRITICAL ERROR: {}", e),
+        },
+        _ => ForwardedHeader::default(),
+    };
+
+    let mut ac = AccessControl::new(allowed_ips, allowed_proxies)
+        .with_trusted_hops(trusted_hops)
+        .with_forwarded_header(forwarded_header);
+    if let Ok(access_file) = env::var("PROJECT_ACCESS_FILE")
+        && !access_file.is_empty()
+    {
+        match load_project_access(std::path::Path::new(&access_file)) {
+            Ok(projects) => {
+                tracing::info!("access_control: rules for {} projects", projects.len());
+                ac = ac.with_projects(projects);
+            }
+            Err(e) => panic!("CRITICAL ERROR: {}", e),
+        }
+    }
+    let ac = Arc::new(ac);
+
+    let metrics_ips: Vec<String> = std::env::var("METRICS_ALLOWED_IPS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    let metrics_access = Arc::new(MetricsAccess::new(ac.clone(), metrics_ips));
+
+    let viewer = match env::var("VIEWER_USERS_FILE") {
+        Ok(users_file) if !users_file.is_empty() => {
+            match load_users(std::path::Path::new(&users_file)) {
+                Ok(users) => {
+                    tracing::info!("viewer_auth: login required, {} users", users.len());
+                    Some(Arc::new(ViewerAuth::new(
+                        users,
+                        session_key(),
+                        session_ttl(),
+                        ac.clone(),
+                    )))
+                }
+                Err(e) => panic!("CRITICAL ERROR: {}", e),
+            }
+        }
+        _ => None,
+    };
+
+    if let Err(e) = rate_limit::configure_from_env() {
+        panic!("CRITICAL ERROR: {}", e);
+    }
+    if rate_limit::is_enabled() {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(rate_limit::PRUNE_INTERVAL);
+            loop {
+                interval.tick().await;
+                rate_limit::prune_all();
+            }
+        });
+    }
+
+    let audit_path = audit::path_from_env(std::path::Path::new(&data_dir));
+    if let Some(path) = &audit_path {
+        tracing::info!("audit: logging operations to {:?}", path);
+    }
+    audit::configure(audit_path);
+
+    let usage_root = std::path::PathBuf::from(&data_dir);
+    tokio::task::spawn_blocking(move || usage::init(&usage_root))
+        .await
+        .unwrap();
+    load_redirects(std::path::Path::new(&data_dir));
+    history_lock::describe_metrics();
+
+    if let Some(max_age) = archive_after() {
+        let archive_root = std::path::PathBuf::from(&data_dir);
+        let storage = StorageOptions::from_env();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(archive_interval());
+            loop {
+                interval.tick().await;
+                let root = archive_root.clone();
+                match tokio::task::spawn_blocking(move || archive_old_runs(&root, max_age, storage))
+                    .await
+                {
+                    Ok(summary) if summary.runs_archived > 0 || !summary.errors.is_empty() => {
+                        tracing::info!(
+                            "archive: packed {} runs ({} -> {} bytes), {} errors",
+                            summary.runs_archived,
+                            summary.bytes_before,
+                            summary.bytes_after,
+                            summary.errors.len()
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::error!("archive: background pass panicked: {}", e),
+                }
+            }
+        });
+    }
 
     let api_routes = Router::new()
         .nest("/api", api::route::create_api_router())
         .route_layer(middleware::from_fn(auth))
+        .route_layer(middleware::from_fn_with_state(ac.clone(), audit))
         .layer(middleware::from_fn(check_content_length))
         .layer(DefaultBodyLimit::max(MAX_UPLOAD_SIZE_BYTES));
 
-    let public_routes = Router::new().route("/manifest.json", get(get_manifest));
+    let public_routes = Router::new()
+        .route(MANIFEST_PATH, get(get_manifest))
+        .with_state(ManifestState {
+            access: ac.clone(),
+            data_dir: std::path::PathBuf::from(&data_dir),
+        })
+        .layer(middleware::from_fn_with_state(
+            viewer.clone(),
+            viewer_session,
+        ))
+        .layer(middleware::from_fn_with_state(ac.clone(), limit_by_ip));
+
+    let login_routes = Router::new()
+        .route("/login", get(login_page).post(login))
+        .route("/logout", get(logout).post(logout))
+        .with_state(viewer.clone())
+        .layer(middleware::from_fn_with_state(ac.clone(), limit_by_ip));
+
+    let metrics_routes = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .layer(middleware::from_fn_with_state(metrics_access, metrics_auth));
 
     let swagger_routes =
         SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi());
 
+    let cache_policy = CachePolicy {
+        access: ac.clone(),
+        viewer_login: viewer.is_some(),
+    };
     let static_reports = Router::new()
-        .nest_service("/", ServeDir::new(&data_dir))
+        .nest_service(
+            "/",
+            ServeDir::new(&data_dir)
+                .precompressed_br()
+                .precompressed_gzip(),
+        )
+        .layer(middleware::from_fn(serve_archived))
+        .layer(middleware::from_fn_with_state(cache_policy, cache_headers))
+        .layer(middleware::from_fn_with_state(
+            std::path::PathBuf::from(&data_dir),
+            redirect_moved,
+        ))
+        .layer(middleware::from_fn(deny_hidden_paths))
+        .layer(middleware::from_fn_with_state(
+            viewer.clone(),
+            viewer_session,
+        ))
+        .layer(middleware::from_fn_with_state(ac.clone(), limit_by_ip))
         .layer(middleware::from_fn_with_state(ac.clone(), access_control));
 
     let app = Router::new()
         .merge(swagger_routes)
         .merge(public_routes)
+        .merge(login_routes)
+        .merge(metrics_routes)
         .merge(api_routes)
         .fallback_service(static_reports);
 
@@ -107,12 +403,18 @@ async fn main() {
     println!("Listening on {}", addr);
     println!("Max upload size: {}MB", MAX_UPLOAD_SIZE_MB);
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .await
-    .unwrap();
+    if proxy_protocol::is_enabled() {
+        println!("Expecting PROXY protocol headers");
+        proxy_protocol::describe_metrics();
+        proxy_protocol::serve(listener, app, trusted_balancers).await;
+    } else {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap();
+    }
 }
 
 /// Middleware to check Content-Length before processing the request
@@ -143,11 +445,16 @@ async fn check_content_length(
     Ok(next.run(request).await)
 }
 
-async fn auth(headers: HeaderMap, request: Request, next: Next) -> Result<Response, StatusCode> {
-    let api_key = env::var("API_SECRET").expect("API_SECRET must be set");
-
-    match headers.get("x-api-key") {
-        Some(key) if key.to_str().unwrap_or_default() == api_key => Ok(next.run(request).await),
-        _ => Err(StatusCode::UNAUTHORIZED),
+/// Middleware to keep internal files (dedup store, metadata) out of static serving
+async fn deny_hidden_paths(request: Request, next: Next) -> Result<Response, StatusCode> {
+    if request
+        .uri()
+        .path()
+        .split('/')
+        .any(|segment| segment.starts_with('.') || segment.to_lowercase().starts_with("%2e"))
+    {
+        return Err(StatusCode::NOT_FOUND);
     }
+
+    Ok(next.run(request).await)
 }
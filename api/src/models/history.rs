@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct TrimHistoryRequest {
+    /// Number of most recent entries to keep
+    #[schema(example = 20)]
+    pub keep: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct RemoveHistoryEntriesRequest {
+    /// `uuid`s of the history entries to delete
+    pub uuids: Vec<String>,
+}
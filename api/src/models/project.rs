@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Per-project settings stored in `{DATA_DIR}/{project}/.project.json`.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema, Clone)]
+#[serde(default)]
+pub struct ProjectSettings {
+    /// Branch whose history seeds reports on new branches when the upload
+    /// does not name a `base_branch`
+    #[schema(example = "main")]
+    pub default_base_branch: Option<String>,
+    /// Rules a run must meet to pass the quality gate
+    pub quality_gate: QualityGateRules,
+}
+
+/// Quality gate rules. Rules left unset are not checked.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema, Clone)]
+#[serde(default)]
+pub struct QualityGateRules {
+    /// Minimum pass rate between 0 and 1 (passed over non-skipped tests)
+    #[schema(example = 0.98)]
+    pub min_pass_rate: Option<f64>,
+    /// Fail on tests that fail in the run but not in the latest run of the
+    /// same report on this branch (the previous run when it is the run's own branch),
+    /// including failing tests that run does not have
+    #[schema(example = "main")]
+    pub no_new_failures_vs: Option<String>,
+    /// Maximum number of failed tests
+    pub max_failed: Option<usize>,
+    /// Maximum number of broken tests
+    pub max_broken: Option<usize>,
+}
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A quarantined test, or set of tests when matched by pattern.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QuarantineEntry {
+    pub id: String,
+    /// Allure `historyId` of the test
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history_id: Option<String>,
+    /// Full name of the test
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_name: Option<String>,
+    /// Glob on the full name, `*` matching any characters and `?` one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "checkout.*Payment*")]
+    pub pattern: Option<String>,
+    #[schema(example = "jane@example.com")]
+    pub owner: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Last day (UTC) the entry applies, `YYYY-MM-DD`
+    #[schema(example = "2026-12-31")]
+    pub expires: String,
+    /// Epoch milliseconds
+    pub created_at: i64,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AddQuarantineRequest {
+    /// Exactly one of `history_id`, `full_name` and `pattern` must be set
+    pub history_id: Option<String>,
+    pub full_name: Option<String>,
+    pub pattern: Option<String>,
+    pub owner: String,
+    pub reason: Option<String>,
+    /// Last day (UTC) the entry applies, `YYYY-MM-DD`
+    pub expires: String,
+}
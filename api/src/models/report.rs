@@ -19,6 +19,14 @@ pub struct FileUploadRequest {
     report_name: String,
     #[schema(default = "allure", example = "allure")]
     r#type: Option<ReportType>,
+    /// Branch whose history seeds this report when it has no history yet.
+    /// Falls back to the project's `default_base_branch`
+    #[schema(example = "main")]
+    base_branch: Option<String>,
+    /// Evaluate the project's quality gate against the new run and include
+    /// the verdict in the response as `gate`
+    #[schema(default = false)]
+    evaluate_gate: Option<bool>,
     #[schema(value_type = String, format = Binary)]
     file: Vec<u8>,
 }
@@ -39,3 +47,56 @@ pub struct ReportResponse {
     pub message: String,
     pub status: String,
 }
+
+/// Identifies a single run of a report.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct RunRef {
+    #[schema(example = "my-project")]
+    pub project_name: String,
+    #[schema(example = "qa")]
+    pub branch: String,
+    #[schema(example = "daily-test")]
+    pub report_name: String,
+    #[schema(example = 42)]
+    pub run_id: u32,
+    /// Whether the run was uploaded with `type=raw`
+    #[serde(default)]
+    pub raw: bool,
+}
+
+/// A project, branch or report directory. Branch and report are optional so the
+/// same shape addresses every level of the tree.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct TreeRef {
+    #[schema(example = "my-project")]
+    pub project_name: String,
+    #[serde(default)]
+    #[schema(example = "feature-x")]
+    pub branch: Option<String>,
+    #[serde(default)]
+    #[schema(example = "daily-test")]
+    pub report_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RelocateMode {
+    #[default]
+    Move,
+    Copy,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct RelocateRequest {
+    pub from: TreeRef,
+    pub to: TreeRef,
+    #[serde(default)]
+    pub mode: RelocateMode,
+    /// Keep old URLs working by redirecting them to the new location (move only)
+    #[serde(default = "default_true")]
+    pub redirect: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
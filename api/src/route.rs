@@ -1,6 +1,9 @@
-use crate::routes::report_route;
+use crate::routes::{admin_route, project_route, report_route};
 use axum::Router;
 
 pub fn create_api_router() -> Router {
-    Router::new().merge(report_route::router())
+    Router::new()
+        .merge(report_route::router())
+        .merge(admin_route::router())
+        .merge(project_route::router())
 }
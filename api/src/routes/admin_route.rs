@@ -0,0 +1,23 @@
+use crate::handlers::admin_handler;
+use axum::{
+    Router,
+    routing::{get, post},
+};
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/admin/dedup", get(admin_handler::dedup_stats))
+        .route("/admin/dedup/gc", post(admin_handler::dedup_gc))
+        .route("/admin/archive", post(admin_handler::archive_now))
+        .route("/admin/archive/rehydrate", post(admin_handler::rehydrate))
+        .route("/usage", get(admin_handler::usage))
+        .route("/admin/usage/rescan", post(admin_handler::usage_rescan))
+        .route("/admin/relocate", post(admin_handler::relocate))
+        .route(
+            "/admin/redirects",
+            get(admin_handler::list_redirects).delete(admin_handler::delete_redirect),
+        )
+        .route("/admin/keys", get(admin_handler::list_keys))
+        .route("/admin/keys/reload", post(admin_handler::reload_keys))
+        .route("/admin/audit", get(admin_handler::audit_log))
+}
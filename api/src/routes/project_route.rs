@@ -0,0 +1,71 @@
+use crate::handlers::{analysis_handler, history_handler, project_handler, run_handler};
+use axum::{
+    Router,
+    routing::{delete, get, post},
+};
+
+const REPORT_PATH: &str = "/projects/:project/branches/:branch/reports/:report";
+
+pub fn router() -> Router {
+    Router::new()
+        .route(
+            "/projects/:project/settings",
+            get(project_handler::get_settings).put(project_handler::put_settings),
+        )
+        .route(
+            "/projects/:project/quarantine",
+            get(project_handler::list_quarantine).post(project_handler::add_quarantine),
+        )
+        .route(
+            "/projects/:project/quarantine/:id",
+            delete(project_handler::remove_quarantine),
+        )
+        .route(
+            "/projects/:project/compare",
+            get(analysis_handler::compare_runs),
+        )
+        .route(
+            "/projects/:project/branches/:branch/tests/history",
+            get(analysis_handler::test_history),
+        )
+        .route(
+            &format!("{}/runs/:run_id/share", REPORT_PATH),
+            post(run_handler::share_run),
+        )
+        .route(
+            &format!("{}/runs/:run_id/summary", REPORT_PATH),
+            get(run_handler::get_run_summary),
+        )
+        .route(
+            &format!("{}/runs/:run_id/clusters", REPORT_PATH),
+            get(analysis_handler::failure_clusters),
+        )
+        .route(
+            &format!("{}/runs/:run_id/gate", REPORT_PATH),
+            get(analysis_handler::quality_gate),
+        )
+        .route(
+            &format!("{}/trend", REPORT_PATH),
+            get(analysis_handler::trend),
+        )
+        .route(
+            &format!("{}/flaky", REPORT_PATH),
+            get(analysis_handler::flaky_tests),
+        )
+        .route(
+            &format!("{}/history", REPORT_PATH),
+            get(history_handler::list_history).delete(history_handler::reset_history),
+        )
+        .route(
+            &format!("{}/history/trim", REPORT_PATH),
+            post(history_handler::trim_history),
+        )
+        .route(
+            &format!("{}/history/remove", REPORT_PATH),
+            post(history_handler::remove_history_entries),
+        )
+        .route(
+            &format!("{}/history/rebuild", REPORT_PATH),
+            post(history_handler::rebuild_history),
+        )
+}
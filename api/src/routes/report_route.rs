@@ -1,6 +1,9 @@
 use crate::handlers::report_handler;
-use axum::{Router, routing::post};
+use crate::helpers::rate_limit::limit_uploads;
+use axum::{Router, middleware, routing::post};
 
 pub fn router() -> Router {
-    Router::new().route("/reports/upload", post(report_handler::upload_report))
+    Router::new()
+        .route("/reports/upload", post(report_handler::upload_report))
+        .route_layer(middleware::from_fn(limit_uploads))
 }
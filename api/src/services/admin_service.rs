@@ -0,0 +1,252 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use serde_json::json;
+
+use crate::helpers::api_keys;
+use crate::helpers::archive::{archive_after, archive_old_runs, is_archived, rehydrate_run};
+use crate::helpers::audit::{self, AuditFilter};
+use crate::helpers::dedup;
+use crate::helpers::fs_helper::{RunLocation, StorageOptions, data_dir};
+use crate::helpers::history_lock;
+use crate::helpers::relocate;
+use crate::helpers::usage;
+use crate::models::report::{RelocateRequest, RunRef};
+
+pub async fn dedup_stats() -> impl IntoResponse {
+    let data_dir = data_dir();
+    match tokio::task::spawn_blocking(move || dedup::stats(&data_dir)).await {
+        Ok(stats) => (StatusCode::OK, Json(json!(stats))).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Failed to compute dedup stats: {}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn dedup_gc() -> impl IntoResponse {
+    let data_dir = data_dir();
+    match tokio::task::spawn_blocking(move || dedup::gc(&data_dir)).await {
+        Ok((removed, freed)) => (
+            StatusCode::OK,
+            Json(json!({
+                "removed_entries": removed,
+                "freed_bytes": freed
+            })),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Dedup gc failed: {}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn archive_now(older_than_days: Option<u64>) -> impl IntoResponse {
+    let max_age = match older_than_days {
+        Some(days) => std::time::Duration::from_secs(days * 24 * 60 * 60),
+        None => match archive_after() {
+            Some(age) => age,
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({
+                        "error": "ARCHIVE_AFTER_DAYS is not set; pass older_than_days explicitly."
+                    })),
+                )
+                    .into_response();
+            }
+        },
+    };
+
+    let data_dir = data_dir();
+    match tokio::task::spawn_blocking(move || {
+        archive_old_runs(&data_dir, max_age, StorageOptions::from_env())
+    })
+    .await
+    {
+        Ok(summary) => (StatusCode::OK, Json(json!(summary))).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Archiving panic: {}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn rehydrate(run: RunRef) -> impl IntoResponse {
+    let data_dir = data_dir();
+    let location = match RunLocation::resolve(
+        &data_dir,
+        &run.project_name,
+        &run.branch,
+        &run.report_name,
+        run.raw,
+        run.run_id,
+    ) {
+        Ok(location) => location,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response(),
+    };
+
+    if !is_archived(&location.path) {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Run does not exist or is not archived." })),
+        )
+            .into_response();
+    }
+
+    let result = tokio::task::spawn_blocking(move || {
+        let res = rehydrate_run(&data_dir, &location.path, StorageOptions::from_env());
+        usage::record_run(&data_dir, &location);
+        res
+    })
+    .await;
+
+    match result {
+        Ok(Ok(files)) => (
+            StatusCode::OK,
+            Json(json!({
+                "message": "Run rehydrated",
+                "files_restored": files
+            })),
+        )
+            .into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e })),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Rehydration panic: {}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn usage(project: Option<String>) -> impl IntoResponse {
+    let data_dir = data_dir();
+    match tokio::task::spawn_blocking(move || usage::report(&data_dir, project.as_deref())).await {
+        Ok(report) => (StatusCode::OK, Json(json!(report))).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Usage report panic: {}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn usage_rescan() -> impl IntoResponse {
+    let data_dir = data_dir();
+    match tokio::task::spawn_blocking(move || usage::rescan(&data_dir)).await {
+        Ok(total) => (StatusCode::OK, Json(json!(total))).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Usage rescan panic: {}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn relocate(request: RelocateRequest) -> impl IntoResponse {
+    let data_dir = data_dir();
+    // Held until the move or copy and its metadata rewrite are done
+    let mut locks = Vec::new();
+    for dir in relocate::source_report_dirs(&data_dir, &request.from) {
+        match history_lock::acquire(&dir).await {
+            Ok(lock) => locks.push(lock),
+            Err(e) => {
+                return (StatusCode::SERVICE_UNAVAILABLE, Json(json!({ "error": e })))
+                    .into_response();
+            }
+        }
+    }
+    let result = tokio::task::spawn_blocking(move || {
+        relocate::relocate(
+            &data_dir,
+            &request.from,
+            &request.to,
+            request.mode,
+            request.redirect,
+        )
+    })
+    .await;
+    drop(locks);
+
+    match result {
+        Ok(Ok(res)) => (StatusCode::OK, Json(json!(res))).into_response(),
+        Ok(Err(e)) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Relocate panic: {}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn list_redirects() -> impl IntoResponse {
+    (StatusCode::OK, Json(json!(relocate::list_redirects()))).into_response()
+}
+
+pub async fn delete_redirect(from: String) -> impl IntoResponse {
+    match relocate::remove_redirect(&data_dir(), from.trim_matches('/')) {
+        Ok(true) => (
+            StatusCode::OK,
+            Json(json!({ "message": "Redirect removed" })),
+        )
+            .into_response(),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "No redirect for this path." })),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn list_keys() -> impl IntoResponse {
+    (StatusCode::OK, Json(json!(api_keys::list_keys()))).into_response()
+}
+
+pub async fn reload_keys() -> impl IntoResponse {
+    match tokio::task::spawn_blocking(api_keys::reload_keys).await {
+        Ok(Ok(count)) => (StatusCode::OK, Json(json!({ "keys": count }))).into_response(),
+        Ok(Err(e)) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Key reload panic: {}", e) })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn audit_log(filter: AuditFilter) -> impl IntoResponse {
+    let Some(path) = audit::log_path() else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Audit log is disabled" })),
+        )
+            .into_response();
+    };
+    match tokio::task::spawn_blocking(move || audit::query(&path, &filter)).await {
+        Ok(Ok(entries)) => (StatusCode::OK, Json(json!(entries))).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Failed to read audit log: {}", e) })),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Audit query panic: {}", e) })),
+        )
+            .into_response(),
+    }
+}
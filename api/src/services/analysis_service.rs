@@ -0,0 +1,294 @@
+use axum::{
+    http::{StatusCode, header},
+    response::{IntoResponse, Json},
+};
+use serde_json::json;
+use std::path::PathBuf;
+
+use crate::helpers::clusters::cluster_failures;
+use crate::helpers::compare::{ComparedRun, DurationThreshold, compare, select_run};
+use crate::helpers::flaky;
+use crate::helpers::fs_helper::{RunLocation, data_dir, resolve_report_dir, validate_path_segment};
+use crate::helpers::quality_gate;
+use crate::helpers::quarantine::Quarantine;
+use crate::helpers::run_summary;
+use crate::helpers::test_history::{TestKey, test_history as collect_test_history};
+use crate::helpers::test_results::load_outcomes_with_traces;
+use crate::helpers::trend::build_trend;
+
+type ServiceError = (StatusCode, String);
+
+fn error_response((status, error): ServiceError) -> axum::response::Response {
+    (status, Json(json!({ "error": error }))).into_response()
+}
+
+fn report_dir_or_error(project: &str, branch: &str, report: &str) -> Result<PathBuf, ServiceError> {
+    let dir = resolve_report_dir(&data_dir(), project, branch, report)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    if !dir.is_dir() {
+        return Err((StatusCode::NOT_FOUND, "Report not found.".to_string()));
+    }
+    Ok(dir)
+}
+
+pub async fn trend(
+    project: String,
+    branch: String,
+    report: String,
+    from: Option<i64>,
+    to: Option<i64>,
+    limit: Option<usize>,
+) -> impl IntoResponse {
+    let report_dir = match report_dir_or_error(&project, &branch, &report) {
+        Ok(dir) => dir,
+        Err(e) => return error_response(e),
+    };
+    let url_prefix = format!("/{}/{}/{}", project, branch, report);
+    let quarantine = Quarantine::load(&data_dir(), &project);
+
+    let result = tokio::task::spawn_blocking(move || {
+        build_trend(&report_dir, &url_prefix, &quarantine, from, to, limit)
+    })
+    .await;
+    match result {
+        Ok(Ok(trend)) => (
+            StatusCode::OK,
+            Json(json!({
+                "project_name": project,
+                "branch": branch,
+                "report_name": report,
+                "source": trend.source,
+                "points": trend.points
+            })),
+        )
+            .into_response(),
+        Ok(Err(e)) => error_response((StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(e) => error_response((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+pub async fn flaky_tests(
+    project: String,
+    branch: String,
+    report: String,
+    runs: Option<usize>,
+    min_flips: Option<usize>,
+) -> impl IntoResponse {
+    let report_dir = match report_dir_or_error(&project, &branch, &report) {
+        Ok(dir) => dir,
+        Err(e) => return error_response(e),
+    };
+    let window = runs.unwrap_or_else(flaky::flaky_window).max(2);
+    let min_flips = min_flips.unwrap_or(flaky::DEFAULT_MIN_FLIPS);
+    let quarantine = Quarantine::load(&data_dir(), &project);
+
+    let result = tokio::task::spawn_blocking(move || {
+        flaky::detect(&report_dir, window, min_flips, &quarantine)
+    })
+    .await;
+    match result {
+        Ok(tests) => (
+            StatusCode::OK,
+            Json(json!({
+                "project_name": project,
+                "branch": branch,
+                "report_name": report,
+                "runs": window,
+                "count": tests.len(),
+                "tests": tests
+            })),
+        )
+            .into_response(),
+        Err(e) => error_response((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+pub async fn test_history(
+    project: String,
+    branch: String,
+    report: Option<String>,
+    history_id: Option<String>,
+    full_name: Option<String>,
+) -> impl IntoResponse {
+    let key = match (history_id, full_name) {
+        (Some(id), None) => TestKey::HistoryId(id),
+        (None, Some(name)) => TestKey::FullName(name),
+        _ => {
+            return error_response((
+                StatusCode::BAD_REQUEST,
+                "Provide exactly one of history_id or full_name".to_string(),
+            ));
+        }
+    };
+    let validated = (|| {
+        let project = validate_path_segment(&project, "project_name")?;
+        let branch = validate_path_segment(&branch, "branch")?;
+        if let Some(report) = &report {
+            validate_path_segment(report, "report_name")?;
+        }
+        Ok::<_, String>(data_dir().join(project).join(branch))
+    })();
+    let branch_dir = match validated {
+        Ok(dir) if dir.is_dir() => dir,
+        Ok(_) => return error_response((StatusCode::NOT_FOUND, "Branch not found.".to_string())),
+        Err(e) => return error_response((StatusCode::BAD_REQUEST, e)),
+    };
+    let url_prefix = format!("/{}/{}", project, branch);
+    let quarantine = Quarantine::load(&data_dir(), &project);
+
+    let result = tokio::task::spawn_blocking(move || {
+        let mut history = collect_test_history(&branch_dir, report.as_deref(), &url_prefix, &key);
+        if let Some(history_id) = &history.history_id {
+            history.quarantine = quarantine
+                .find(
+                    history_id,
+                    history.full_name.as_deref(),
+                    history.name.as_deref().unwrap_or_default(),
+                )
+                .cloned();
+        }
+        history
+    })
+    .await;
+    match result {
+        Ok(history) if history.runs.is_empty() => error_response((
+            StatusCode::NOT_FOUND,
+            "No runs of this test found.".to_string(),
+        )),
+        Ok(history) => (StatusCode::OK, Json(json!(history))).into_response(),
+        Err(e) => error_response((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+/// One side of a comparison: a report on a branch and an optional run id.
+pub struct RunSelector {
+    pub branch: String,
+    pub report: String,
+    pub run_id: Option<u32>,
+}
+
+pub async fn compare_runs(
+    project: String,
+    base: RunSelector,
+    head: RunSelector,
+    threshold: DurationThreshold,
+    markdown: bool,
+) -> impl IntoResponse {
+    let base_dir = match report_dir_or_error(&project, &base.branch, &base.report) {
+        Ok(dir) => dir,
+        Err(e) => return error_response(e),
+    };
+    let head_dir = match report_dir_or_error(&project, &head.branch, &head.report) {
+        Ok(dir) => dir,
+        Err(e) => return error_response(e),
+    };
+
+    let quarantine = Quarantine::load(&data_dir(), &project);
+
+    let result = tokio::task::spawn_blocking(move || {
+        let (head_id, head_tests) = select_run(&head_dir, head.run_id, None)?;
+        // Within one report the default base is the run before head
+        let before = (base_dir == head_dir).then_some(head_id);
+        let (base_id, base_tests) = select_run(&base_dir, base.run_id, before)?;
+        let run = |selector: RunSelector, run_id: u32| ComparedRun {
+            url: format!(
+                "/{}/{}/{}/{}/index.html",
+                project, selector.branch, selector.report, run_id
+            ),
+            branch: selector.branch,
+            report_name: selector.report,
+            run_id,
+        };
+        let mut comparison = compare(
+            run(base, base_id),
+            &base_tests,
+            run(head, head_id),
+            &head_tests,
+            &threshold,
+        );
+        comparison.mark_quarantined(&quarantine);
+        Ok::<_, String>(comparison)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(comparison)) if markdown => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "text/markdown; charset=utf-8")],
+            comparison.to_markdown(),
+        )
+            .into_response(),
+        Ok(Ok(comparison)) => (StatusCode::OK, Json(json!(comparison))).into_response(),
+        Ok(Err(e)) => error_response((StatusCode::NOT_FOUND, e)),
+        Err(e) => error_response((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+pub async fn failure_clusters(
+    project: String,
+    branch: String,
+    report: String,
+    run_id: u32,
+) -> impl IntoResponse {
+    let location =
+        match RunLocation::resolve(&data_dir(), &project, &branch, &report, false, run_id) {
+            Ok(location) if location.path.is_dir() => location,
+            Ok(_) => return error_response((StatusCode::NOT_FOUND, "Run not found.".to_string())),
+            Err(e) => return error_response((StatusCode::BAD_REQUEST, e)),
+        };
+    let run_url = format!("/{}/{}/{}/{}/index.html", project, branch, report, run_id);
+
+    // Runs uploaded before clustering existed get their clusters computed once
+    let result = tokio::task::spawn_blocking(move || {
+        let run_dir = location.path;
+        let quarantine = Quarantine::load(&data_dir(), &project);
+        let mut summary = run_summary::load_or_build(run_id, &run_dir, &quarantine)?;
+        if summary.clusters.is_none() {
+            let (outcomes, _) = quarantine.partition(&load_outcomes_with_traces(&run_dir)?);
+            summary.clusters = Some(cluster_failures(&outcomes, &run_url));
+            if let Err(e) = run_summary::save(&run_dir, &summary) {
+                tracing::warn!("clusters: {:?}: {}", run_dir, e);
+            }
+        }
+        summary.clusters
+    })
+    .await;
+
+    match result {
+        Ok(Some(clusters)) => (
+            StatusCode::OK,
+            Json(json!({
+                "run_id": run_id,
+                "failures": clusters.iter().map(|c| c.count).sum::<usize>(),
+                "count": clusters.len(),
+                "clusters": clusters
+            })),
+        )
+            .into_response(),
+        Ok(None) => error_response((
+            StatusCode::NOT_FOUND,
+            "No test outcomes stored for this run.".to_string(),
+        )),
+        Err(e) => error_response((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+pub async fn quality_gate(
+    project: String,
+    branch: String,
+    report: String,
+    run_id: u32,
+) -> impl IntoResponse {
+    if let Err(e) = report_dir_or_error(&project, &branch, &report) {
+        return error_response(e);
+    }
+    let result = tokio::task::spawn_blocking(move || {
+        quality_gate::evaluate(&data_dir(), &project, &branch, &report, run_id)
+    })
+    .await;
+    match result {
+        Ok(Ok(verdict)) => (StatusCode::OK, Json(json!(verdict))).into_response(),
+        Ok(Err(e)) => error_response((StatusCode::NOT_FOUND, e)),
+        Err(e) => error_response((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
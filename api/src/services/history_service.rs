@@ -0,0 +1,168 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use serde_json::json;
+
+use crate::helpers::fs_helper::{data_dir, report_run_dirs, resolve_report_dir};
+use crate::helpers::history;
+use crate::helpers::history_lock;
+
+type ServiceError = (StatusCode, String);
+
+fn error_response((status, error): ServiceError) -> axum::response::Response {
+    (status, Json(json!({ "error": error }))).into_response()
+}
+
+fn report_dir_or_error(
+    project: &str,
+    branch: &str,
+    report: &str,
+) -> Result<std::path::PathBuf, ServiceError> {
+    let dir = resolve_report_dir(&data_dir(), project, branch, report)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    if !dir.is_dir() {
+        return Err((StatusCode::NOT_FOUND, "Report not found.".to_string()));
+    }
+    Ok(dir)
+}
+
+pub async fn list_history(
+    project: String,
+    branch: String,
+    report: String,
+    full: bool,
+) -> impl IntoResponse {
+    let report_dir = match report_dir_or_error(&project, &branch, &report) {
+        Ok(dir) => dir,
+        Err(e) => return error_response(e),
+    };
+
+    match history::read_lines(&report_dir) {
+        Ok(lines) => {
+            let entries: Vec<_> = lines
+                .iter()
+                .enumerate()
+                .map(|(i, line)| history::summarize(i, line, full))
+                .collect();
+            (
+                StatusCode::OK,
+                Json(json!({
+                    "project_name": project,
+                    "branch": branch,
+                    "report_name": report,
+                    "count": entries.len(),
+                    "entries": entries
+                })),
+            )
+                .into_response()
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e })),
+        )
+            .into_response(),
+    }
+}
+
+/// Runs `op` on the report directory while holding its history lock.
+async fn with_history_lock<T, F>(
+    project: &str,
+    branch: &str,
+    report: &str,
+    op: F,
+) -> Result<T, ServiceError>
+where
+    T: Send + 'static,
+    F: FnOnce(&std::path::Path) -> Result<T, String> + Send + 'static,
+{
+    let report_dir = report_dir_or_error(project, branch, report)?;
+    let _lock = history_lock::acquire(&report_dir)
+        .await
+        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))?;
+
+    match tokio::task::spawn_blocking(move || op(&report_dir)).await {
+        Ok(Ok(v)) => Ok(v),
+        Ok(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("History operation panic: {}", e),
+        )),
+    }
+}
+
+pub async fn trim_history(
+    project: String,
+    branch: String,
+    report: String,
+    keep: usize,
+) -> impl IntoResponse {
+    match with_history_lock(&project, &branch, &report, move |dir| {
+        history::trim(dir, keep)
+    })
+    .await
+    {
+        Ok(removed) => (
+            StatusCode::OK,
+            Json(json!({ "message": "History trimmed", "removed": removed, "kept_at_most": keep })),
+        )
+            .into_response(),
+        Err(e) => error_response(e),
+    }
+}
+
+pub async fn remove_history_entries(
+    project: String,
+    branch: String,
+    report: String,
+    uuids: Vec<String>,
+) -> impl IntoResponse {
+    match with_history_lock(&project, &branch, &report, move |dir| {
+        history::remove_entries(dir, &uuids)
+    })
+    .await
+    {
+        Ok(removed) => (
+            StatusCode::OK,
+            Json(json!({ "message": "History entries removed", "removed": removed })),
+        )
+            .into_response(),
+        Err(e) => error_response(e),
+    }
+}
+
+pub async fn reset_history(project: String, branch: String, report: String) -> impl IntoResponse {
+    match with_history_lock(&project, &branch, &report, history::reset).await {
+        Ok(true) => (StatusCode::OK, Json(json!({ "message": "History reset" }))).into_response(),
+        Ok(false) => (
+            StatusCode::OK,
+            Json(json!({ "message": "Report had no history" })),
+        )
+            .into_response(),
+        Err(e) => error_response(e),
+    }
+}
+
+pub async fn rebuild_history(project: String, branch: String, report: String) -> impl IntoResponse {
+    let (p, b, r) = (project.clone(), branch.clone(), report.clone());
+    let result = with_history_lock(&project, &branch, &report, move |dir| {
+        let runs: Vec<_> = report_run_dirs(dir)
+            .into_iter()
+            .map(|(id, path)| {
+                let url = format!("/{}/{}/{}/{}/index.html", p, b, r, id);
+                (id, path, url)
+            })
+            .collect();
+        history::rebuild(dir, &runs)
+    })
+    .await;
+
+    match result {
+        Ok(replayed) => (
+            StatusCode::OK,
+            Json(json!({ "message": "History rebuilt", "runs_replayed": replayed })),
+        )
+            .into_response(),
+        Err(e) => error_response(e),
+    }
+}
@@ -0,0 +1,115 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use serde_json::{Value, json};
+
+use crate::helpers::fs_helper::{data_dir, validate_path_segment};
+use crate::helpers::project_settings;
+use crate::helpers::quarantine::{self, is_active, now_ms};
+use crate::models::quarantine::AddQuarantineRequest;
+
+pub async fn get_settings(project: String) -> impl IntoResponse {
+    let project = match validate_path_segment(&project, "project_name") {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response(),
+    };
+    let settings = project_settings::load(&data_dir(), &project);
+    (StatusCode::OK, Json(json!(settings))).into_response()
+}
+
+pub async fn put_settings(project: String, update: Value) -> impl IntoResponse {
+    let project = match validate_path_segment(&project, "project_name") {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response(),
+    };
+    let data_dir = data_dir();
+    let current = project_settings::load(&data_dir, &project);
+    let settings = match project_settings::merge(&current, update) {
+        Ok(s) => s,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response(),
+    };
+    match project_settings::save(&data_dir, &project, &settings) {
+        Ok(_) => (StatusCode::OK, Json(json!(settings))).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn list_quarantine(project: String) -> impl IntoResponse {
+    let project = match validate_path_segment(&project, "project_name") {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response(),
+    };
+    let now = now_ms();
+    let entries: Vec<_> = quarantine::list(&data_dir(), &project)
+        .into_iter()
+        .map(|entry| {
+            let expired = !is_active(&entry, now);
+            let mut value = json!(entry);
+            value["expired"] = json!(expired);
+            value
+        })
+        .collect();
+    (
+        StatusCode::OK,
+        Json(json!({
+            "project_name": project,
+            "count": entries.len(),
+            "entries": entries
+        })),
+    )
+        .into_response()
+}
+
+pub async fn add_quarantine(project: String, request: AddQuarantineRequest) -> impl IntoResponse {
+    let project = match validate_path_segment(&project, "project_name") {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response(),
+    };
+    let data_dir = data_dir();
+    let _lock = match quarantine::lock(&data_dir, &project).await {
+        Ok(lock) => lock,
+        Err(e) => {
+            return (StatusCode::SERVICE_UNAVAILABLE, Json(json!({ "error": e }))).into_response();
+        }
+    };
+    match quarantine::add(&data_dir, &project, request) {
+        Ok(entry) => (StatusCode::CREATED, Json(json!(entry))).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response(),
+    }
+}
+
+pub async fn remove_quarantine(project: String, id: String) -> impl IntoResponse {
+    let project = match validate_path_segment(&project, "project_name") {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response(),
+    };
+    let data_dir = data_dir();
+    let _lock = match quarantine::lock(&data_dir, &project).await {
+        Ok(lock) => lock,
+        Err(e) => {
+            return (StatusCode::SERVICE_UNAVAILABLE, Json(json!({ "error": e }))).into_response();
+        }
+    };
+    match quarantine::remove(&data_dir, &project, &id) {
+        Ok(true) => (
+            StatusCode::OK,
+            Json(json!({ "message": "Quarantine entry removed", "id": id })),
+        )
+            .into_response(),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Quarantine entry not found." })),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e })),
+        )
+            .into_response(),
+    }
+}
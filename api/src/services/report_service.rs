@@ -5,23 +5,46 @@ use axum::{
 };
 use serde_json::json;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::helpers::allure_config::ensure_allure_config;
 use crate::helpers::allure_generator::{collect_history, generate_report, sync_history};
+use crate::helpers::api_keys::Principal;
+use crate::helpers::audit::AuditTarget;
+use crate::helpers::clusters::cluster_failures;
+use crate::helpers::dedup::dedup_dir;
+use crate::helpers::flaky::{self, DEFAULT_MIN_FLIPS, flaky_window};
 use crate::helpers::fs_helper::{
-    allocate_next_id_dir, find_results_dir, move_directory_contents, validate_path_segment,
+    RunLocation, StorageOptions, allocate_next_id_dir, find_results_dir, move_directory_contents,
+    validate_path_segment, validate_project_name,
 };
+use crate::helpers::history::{
+    HISTORY_FILE_NAME, history_path, retain_results_enabled, retained_results_dir, seed_from,
+};
+use crate::helpers::history_lock;
+use crate::helpers::precompress::precompress_dir;
+use crate::helpers::project_settings;
+use crate::helpers::quality_gate;
+use crate::helpers::quarantine::Quarantine;
+use crate::helpers::run_summary;
+use crate::helpers::test_results::{read_results, save_outcomes};
+use crate::helpers::usage;
 use crate::helpers::zip_helper::extract_zip;
 
 const MAX_ZIP_SIZE_BYTES: u64 = 500 * 1024 * 1024; // 500MB
 const MAX_ZIP_SIZE_MB: u64 = MAX_ZIP_SIZE_BYTES / (1024 * 1024);
 
-pub async fn upload_report(mut multipart: Multipart) -> impl IntoResponse {
+pub async fn upload_report(
+    principal: Principal,
+    mut multipart: Multipart,
+    storage: StorageOptions,
+) -> impl IntoResponse {
     let mut project_name: Option<String> = None;
     let mut branch: Option<String> = None;
     let mut report_name: Option<String> = None;
     let mut report_type: String = "allure".to_string();
+    let mut base_branch: Option<String> = None;
+    let mut evaluate_gate = false;
     let mut zip_data: Option<Vec<u8>> = None;
     let mut zip_size: u64;
 
@@ -69,6 +92,18 @@ pub async fn upload_report(mut multipart: Multipart) -> impl IntoResponse {
                     report_name = Some(val);
                 }
             }
+            "base_branch" => {
+                if let Ok(val) = field.text().await
+                    && !val.is_empty()
+                {
+                    base_branch = Some(val);
+                }
+            }
+            "evaluate_gate" => {
+                if let Ok(val) = field.text().await {
+                    evaluate_gate = matches!(val.trim(), "true" | "1" | "yes");
+                }
+            }
             "type" | "report_type" => {
                 if let Ok(val) = field.text().await
                     && !val.is_empty()
@@ -142,13 +177,12 @@ pub async fn upload_report(mut multipart: Multipart) -> impl IntoResponse {
     }
 
     // Sanitize path segments
-    let project_name_safe =
-        match validate_path_segment(project_name.as_ref().unwrap(), "project_name") {
-            Ok(v) => v,
-            Err(e) => {
-                return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response();
-            }
-        };
+    let project_name_safe = match validate_project_name(project_name.as_ref().unwrap()) {
+        Ok(v) => v,
+        Err(e) => {
+            return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response();
+        }
+    };
     let branch_safe = match validate_path_segment(branch.as_ref().unwrap(), "branch") {
         Ok(v) => v,
         Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response(),
@@ -159,6 +193,25 @@ pub async fn upload_report(mut multipart: Multipart) -> impl IntoResponse {
         Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response(),
     };
 
+    if let Some(base) = &base_branch
+        && let Err(e) = validate_path_segment(base, "base_branch")
+    {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response();
+    }
+
+    if !principal.allows(&project_name_safe, Some(&branch_safe)) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": format!(
+                    "API key '{}' may not upload to {}/{}",
+                    principal.key_id, project_name_safe, branch_safe
+                )
+            })),
+        )
+            .into_response();
+    }
+
     project_name = Some(project_name_safe);
     branch = Some(branch_safe);
     report_name = Some(report_name_safe);
@@ -184,6 +237,22 @@ pub async fn upload_report(mut multipart: Multipart) -> impl IntoResponse {
             .into_response();
     }
 
+    // Hold the report's history lock until history is collected, so concurrent
+    // uploads to the same report don't overwrite each other's history entries.
+    // Taken before the run id is allocated so ids follow the history order and
+    // a timeout leaves nothing behind.
+    let history_lock = if report_type == "allure" {
+        match history_lock::acquire(&parent_dir).await {
+            Ok(lock) => Some(lock),
+            Err(e) => {
+                return (StatusCode::SERVICE_UNAVAILABLE, Json(json!({ "error": e })))
+                    .into_response();
+            }
+        }
+    } else {
+        None
+    };
+
     // Create report dir atomically to avoid race conditions
     let (next_id, report_dir) = match allocate_next_id_dir(&parent_dir).await {
         Ok(v) => v,
@@ -198,6 +267,7 @@ pub async fn upload_report(mut multipart: Multipart) -> impl IntoResponse {
         }
     };
     let report_id = next_id.to_string();
+    let mut history_seeded_from: Option<String> = None;
 
     let extract_dir = if report_type == "allure" {
         report_dir.join("allure-results")
@@ -256,6 +326,26 @@ pub async fn upload_report(mut multipart: Multipart) -> impl IntoResponse {
             eprintln!("Warning: Failed to create allurerc.json: {}", e);
         }
 
+        // A report without history yet starts from its base branch's history
+        if !history_path(&parent_dir).exists() {
+            history_seeded_from = seed_history(
+                &base_path,
+                project_name.as_ref().unwrap(),
+                branch.as_ref().unwrap(),
+                report_name.as_ref().unwrap(),
+                base_branch.as_deref(),
+                &parent_dir,
+            );
+        }
+
+        // Quarantined tests are marked in the report with a tag
+        let quarantine =
+            Quarantine::load(&PathBuf::from(&base_path), project_name.as_ref().unwrap());
+        let tagged = quarantine.tag_results(&actual_input_dir);
+        if tagged > 0 {
+            println!("Tagged {} quarantined results", tagged);
+        }
+
         // Sync history before generation
         sync_history(&parent_dir, &actual_input_dir, &report_dir).await;
 
@@ -292,6 +382,32 @@ pub async fn upload_report(mut multipart: Multipart) -> impl IntoResponse {
             }
         }
 
+        // Summarize the run's results for the trend and analysis APIs
+        let results_dir = actual_input_dir.clone();
+        let run_dir = report_dir.clone();
+        let report_root = parent_dir.clone();
+        let run_url = format!(
+            "/{}/{}/{}/{}/index.html",
+            project_name.as_ref().unwrap(),
+            branch.as_ref().unwrap(),
+            report_name.as_ref().unwrap(),
+            next_id
+        );
+        let summary = tokio::task::spawn_blocking(move || {
+            let outcomes = read_results(&results_dir);
+            save_outcomes(&run_dir, &outcomes)?;
+            let mut summary = run_summary::build(next_id, &outcomes, &quarantine);
+            summary.flaky =
+                flaky::detect(&report_root, flaky_window(), DEFAULT_MIN_FLIPS, &quarantine);
+            let (counted, _) = quarantine.partition(&outcomes);
+            summary.clusters = Some(cluster_failures(&counted, &run_url));
+            run_summary::save(&run_dir, &summary)
+        })
+        .await;
+        if let Ok(Err(e)) = summary {
+            eprintln!("Warning: Failed to write run summary: {}", e);
+        }
+
         // Move awesome directory contents to report_dir root
         let awesome_dir = report_dir.join("awesome");
         if awesome_dir.exists() {
@@ -303,6 +419,12 @@ pub async fn upload_report(mut multipart: Multipart) -> impl IntoResponse {
             eprintln!("Warning: awesome directory not found at {:?}", awesome_dir);
         }
 
+        // Keep the raw results so history can be rebuilt later. They carry the
+        // quarantine tags added above, which a rebuild strips again.
+        if retain_results_enabled() {
+            retain_results(&actual_input_dir, &report_dir).await;
+        }
+
         // Clean up awesome directory if it still exists
         if awesome_dir.exists() {
             if let Err(e) = tokio::fs::remove_dir(&awesome_dir).await {
@@ -318,6 +440,50 @@ pub async fn upload_report(mut multipart: Multipart) -> impl IntoResponse {
         // Collect history after generation
         collect_history(&parent_dir, &actual_input_dir, &report_dir).await;
     }
+    drop(history_lock);
+
+    // Write .gz/.br siblings so static serving can skip on-the-fly compression
+    if storage.precompress {
+        let run_dir = report_dir.clone();
+        match tokio::task::spawn_blocking(move || precompress_dir(&run_dir)).await {
+            Ok(Ok(res)) => println!(
+                "Precompressed {} files: {} bytes -> gzip {} / brotli {}",
+                res.files_compressed, res.original_bytes, res.gzip_bytes, res.brotli_bytes
+            ),
+            Ok(Err(e)) => eprintln!("Warning: Precompression failed: {}", e),
+            Err(e) => eprintln!("Warning: Precompression panic: {}", e),
+        }
+    }
+
+    // Hardlink identical assets (including compressed siblings) against the content-addressed store
+    if storage.dedup {
+        let data_dir = PathBuf::from(&base_path);
+        let run_dir = report_dir.clone();
+        match tokio::task::spawn_blocking(move || dedup_dir(&data_dir, &run_dir)).await {
+            Ok(Ok(res)) => println!(
+                "Dedup: scanned {} files, linked {}, saved {} bytes",
+                res.files_scanned, res.files_linked, res.bytes_saved
+            ),
+            Ok(Err(e)) => eprintln!("Warning: Dedup failed: {}", e),
+            Err(e) => eprintln!("Warning: Dedup panic: {}", e),
+        }
+    }
+
+    // Account the new run's disk usage
+    let location = RunLocation {
+        project: project_name.clone().unwrap(),
+        branch: branch.clone().unwrap(),
+        report: report_name.clone().unwrap(),
+        raw: report_type == "raw",
+        id: next_id,
+        path: report_dir.clone(),
+    };
+    let usage_root = PathBuf::from(&base_path);
+    if let Err(e) =
+        tokio::task::spawn_blocking(move || usage::record_run(&usage_root, &location)).await
+    {
+        eprintln!("Warning: Usage accounting panic: {}", e);
+    }
 
     // Build URL based on report type
     let url = if report_type == "raw" {
@@ -338,7 +504,29 @@ pub async fn upload_report(mut multipart: Multipart) -> impl IntoResponse {
         )
     };
 
-    (
+    // Evaluate the project's quality gate against the new run when asked to
+    let gate = if evaluate_gate && report_type == "allure" {
+        let (p, b, r) = (
+            project_name.clone().unwrap(),
+            branch.clone().unwrap(),
+            report_name.clone().unwrap(),
+        );
+        let data_dir = PathBuf::from(&base_path);
+        match tokio::task::spawn_blocking(move || {
+            quality_gate::evaluate(&data_dir, &p, &b, &r, next_id)
+        })
+        .await
+        {
+            Ok(Ok(verdict)) => Some(json!(verdict)),
+            Ok(Err(e)) => Some(json!({ "passed": false, "reasons": [e] })),
+            Err(e) => Some(json!({ "passed": false, "reasons": [e.to_string()] })),
+        }
+    } else {
+        None
+    };
+
+    let target = url.trim_end_matches("index.html").to_string();
+    let mut response = (
         StatusCode::OK,
         Json(json!({
             "message": format!("Report uploaded successfully (Type: {})", report_type),
@@ -347,8 +535,69 @@ pub async fn upload_report(mut multipart: Multipart) -> impl IntoResponse {
             "report_name": report_name,
             "report_id": report_id,
             "report_type": report_type,
+            "history_seeded_from": history_seeded_from,
+            "gate": gate,
             "url": url
         })),
     )
-        .into_response()
+        .into_response();
+    response.extensions_mut().insert(AuditTarget(target));
+    response
+}
+
+/// Copies the history of the same report on the base branch (the upload's
+/// `base_branch`, else the project's default) into `report_dir`.
+/// Returns the base branch if anything was copied.
+fn seed_history(
+    base_path: &str,
+    project: &str,
+    branch: &str,
+    report: &str,
+    base_branch: Option<&str>,
+    report_dir: &Path,
+) -> Option<String> {
+    let data_dir = PathBuf::from(base_path);
+    let base = match base_branch {
+        Some(b) => b.to_string(),
+        None => project_settings::load(&data_dir, project).default_base_branch?,
+    };
+    if base == branch {
+        return None;
+    }
+    let base_report_dir = data_dir.join(project).join(&base).join(report);
+    match seed_from(report_dir, &base_report_dir) {
+        Ok(0) => None,
+        Ok(n) => {
+            tracing::info!(
+                "Seeded history of {}/{}/{} with {} entries from branch '{}'",
+                project,
+                branch,
+                report,
+                n,
+                base
+            );
+            Some(base)
+        }
+        Err(e) => {
+            tracing::warn!("Failed to seed history from branch '{}': {}", base, e);
+            None
+        }
+    }
+}
+
+/// Moves the uploaded allure-results into the run's metadata directory.
+async fn retain_results(input_dir: &Path, report_dir: &Path) {
+    let target = retained_results_dir(report_dir);
+    if let Some(parent) = target.parent()
+        && let Err(e) = tokio::fs::create_dir_all(parent).await
+    {
+        eprintln!("Warning: Failed to create run metadata directory: {}", e);
+        return;
+    }
+    if let Err(e) = tokio::fs::rename(input_dir, &target).await {
+        eprintln!("Warning: Failed to retain allure-results: {}", e);
+        return;
+    }
+    // The staged history copy is not part of the run's results
+    let _ = tokio::fs::remove_file(target.join(HISTORY_FILE_NAME)).await;
 }
@@ -0,0 +1,102 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use serde_json::json;
+
+use crate::helpers::fs_helper::{RunLocation, data_dir};
+use crate::helpers::quarantine::{Quarantine, now_ms};
+use crate::helpers::run_summary;
+use crate::helpers::share_link::{DEFAULT_SHARE_HOURS, MAX_SHARE_HOURS, share_path};
+
+pub async fn get_run_summary(
+    project: String,
+    branch: String,
+    report: String,
+    run_id: u32,
+) -> impl IntoResponse {
+    let location =
+        match RunLocation::resolve(&data_dir(), &project, &branch, &report, false, run_id) {
+            Ok(location) => location,
+            Err(e) => {
+                return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response();
+            }
+        };
+    if !location.path.is_dir() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Run not found." })),
+        )
+            .into_response();
+    }
+
+    let path = location.path.clone();
+    let quarantine = Quarantine::load(&data_dir(), &project);
+    match tokio::task::spawn_blocking(move || {
+        run_summary::load_or_build(run_id, &path, &quarantine)
+    })
+    .await
+    {
+        Ok(Some(summary)) => (StatusCode::OK, Json(json!(summary))).into_response(),
+        _ => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "No summary or retained results for this run." })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn share_run(
+    project: String,
+    branch: String,
+    report: String,
+    run_id: u32,
+    raw: bool,
+    expires_in_hours: Option<u64>,
+    base_url: Option<String>,
+) -> impl IntoResponse {
+    let hours = expires_in_hours.unwrap_or(DEFAULT_SHARE_HOURS);
+    if hours == 0 || hours > MAX_SHARE_HOURS {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": format!("expires_in_hours must be between 1 and {}", MAX_SHARE_HOURS)
+            })),
+        )
+            .into_response();
+    }
+    let location = match RunLocation::resolve(&data_dir(), &project, &branch, &report, raw, run_id)
+    {
+        Ok(location) => location,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response(),
+    };
+    if !location.path.is_dir() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "Run not found." })),
+        )
+            .into_response();
+    }
+
+    let mut run_path = format!(
+        "{}/{}/{}",
+        location.project, location.branch, location.report
+    );
+    if raw {
+        run_path.push_str("/raw");
+    }
+    run_path.push_str(&format!("/{}", run_id));
+    let expires_at = now_ms() / 1000 + (hours * 3600) as i64;
+    let path = share_path(&run_path, expires_at);
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "run_path": run_path,
+            "path": path,
+            "url": base_url.map(|base| format!("{}{}", base, path)),
+            "expires_at": expires_at
+        })),
+    )
+        .into_response()
+}
@@ -0,0 +1,282 @@
+use api::helpers::access_control::{AccessControl, ForwardedHeader, Peer, load_project_access};
+use argon2::password_hash::SaltString;
+use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
+use axum::http::HeaderMap;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::net::IpAddr;
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+fn basic(user: &str, password: &str) -> String {
+    format!(
+        "Basic {}",
+        STANDARD.encode(format!("{}:{}", user, password))
+    )
+}
+
+/// Cheap parameters keep the tests fast; verification reads them from the hash.
+fn argon2_hash(password: &str) -> String {
+    let salt = SaltString::encode_b64(b"report-host-salt").unwrap();
+    let params = Params::new(1024, 1, 1, None).unwrap();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password(password.as_bytes(), &salt)
+        .unwrap()
+        .to_string()
+}
+
+fn access_control(dir: &std::path::Path) -> AccessControl {
+    let path = dir.join("access.json");
+    let file = serde_json::json!({
+        "projects": {
+            "confidential": { "allowed_ips": ["10.1.0.0/16"] },
+            "customer": {
+                "viewers": [{ "username": "acme", "password_hash": argon2_hash("s3cret") }]
+            }
+        }
+    });
+    std::fs::write(&path, file.to_string()).unwrap();
+    AccessControl::new(vec!["192.168.0.0/16".to_string()], Vec::new())
+        .with_projects(load_project_access(&path).unwrap())
+}
+
+#[test]
+fn project_allowlists_replace_the_global_list() {
+    let dir = std::env::temp_dir().join(format!("access-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let ac = access_control(&dir);
+
+    // Projects without rules use ALLOWED_IPS
+    assert!(
+        ac.is_allowed_for(Some("web"), ip("192.168.1.5"), &HeaderMap::new())
+            .0
+    );
+    assert!(
+        !ac.is_allowed_for(Some("web"), ip("10.1.2.3"), &HeaderMap::new())
+            .0
+    );
+    assert!(
+        ac.is_allowed_for(None, ip("192.168.1.5"), &HeaderMap::new())
+            .0
+    );
+
+    assert!(
+        ac.is_allowed_for(Some("confidential"), ip("10.1.2.3"), &HeaderMap::new())
+            .0
+    );
+    assert!(
+        !ac.is_allowed_for(Some("confidential"), ip("192.168.1.5"), &HeaderMap::new())
+            .0
+    );
+
+    // Viewer-only rules keep the global list
+    assert!(
+        ac.is_allowed_for(Some("customer"), ip("192.168.1.5"), &HeaderMap::new())
+            .0
+    );
+    assert!(
+        !ac.is_allowed_for(Some("customer"), ip("10.1.2.3"), &HeaderMap::new())
+            .0
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn viewer_credentials_are_required_where_configured() {
+    let dir = std::env::temp_dir().join(format!("access-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let ac = access_control(&dir);
+
+    assert!(ac.viewer_allowed(Some("web"), None));
+    assert!(ac.viewer_allowed(Some("confidential"), None));
+    assert!(!ac.viewer_allowed(Some("customer"), None));
+    assert!(!ac.viewer_allowed(Some("customer"), Some(&basic("acme", "wrong"))));
+    assert!(!ac.viewer_allowed(Some("customer"), Some(&basic("other", "s3cret"))));
+    assert!(ac.viewer_allowed(Some("customer"), Some(&basic("acme", "s3cret"))));
+    // Verified credentials are remembered, other passwords still fail
+    assert!(ac.viewer_allowed(Some("customer"), Some(&basic("acme", "s3cret"))));
+    assert!(!ac.viewer_allowed(Some("customer"), Some(&basic("acme", "wrong"))));
+
+    // The manifest lists only projects the client could open
+    let none = HeaderMap::new();
+    let acme = basic("acme", "s3cret");
+    assert!(ac.may_list("web", ip("192.168.1.5"), &none, None));
+    assert!(!ac.may_list("web", ip("10.1.2.3"), &none, None));
+    assert!(ac.may_list("confidential", ip("10.1.2.3"), &none, None));
+    assert!(!ac.may_list("confidential", ip("192.168.1.5"), &none, None));
+    assert!(!ac.may_list("customer", ip("192.168.1.5"), &none, None));
+    assert!(ac.may_list("customer", ip("192.168.1.5"), &none, Some(&acme)));
+    assert!(!ac.may_list("customer", ip("10.1.2.3"), &none, Some(&acme)));
+
+    let bad = dir.join("bad.json");
+    std::fs::write(
+        &bad,
+        r#"{"projects":{"x":{"viewers":[{"username":"a","password_hash":"plain"}]}}}"#,
+    )
+    .unwrap();
+    assert!(load_project_access(&bad).is_err());
+    // Unsalted SHA-256 digests are no longer accepted
+    std::fs::write(
+        &bad,
+        format!(
+            r#"{{"projects":{{"x":{{"viewers":[{{"username":"a","password_hash":"sha256:{}"}}]}}}}}}"#,
+            "0".repeat(64)
+        ),
+    )
+    .unwrap();
+    assert!(load_project_access(&bad).is_err());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.append(*name, value.parse().unwrap());
+    }
+    headers
+}
+
+#[test]
+fn client_ip_walks_the_chain_from_the_right() {
+    let proxy = ip("10.0.0.2");
+    let ac = AccessControl::new(
+        vec!["203.0.113.0/24".to_string()],
+        vec!["10.0.0.0/24".to_string()],
+    );
+
+    // A forged left-most entry is ignored
+    let forged = headers(&[("x-forwarded-for", "203.0.113.9, 198.51.100.7")]);
+    assert_eq!(ac.client_ip(proxy, &forged), Some(ip("198.51.100.7")));
+    assert!(!ac.is_allowed(proxy, &forged).0);
+
+    // Chained proxies are skipped
+    let chained = headers(&[("x-forwarded-for", "203.0.113.9, 10.0.0.7")]);
+    assert_eq!(ac.client_ip(proxy, &chained), Some(ip("203.0.113.9")));
+    assert!(ac.is_allowed(proxy, &chained).0);
+
+    // Requests that bypass the proxy are refused
+    assert!(!ac.is_allowed(ip("203.0.113.9"), &chained).0);
+
+    // Only the configured header is read
+    let forwarded = headers(&[(
+        "forwarded",
+        r#"for=198.51.100.7, for="[2001:db8::1]:4711";proto=https, for=203.0.113.9:5000"#,
+    )]);
+    assert_eq!(ac.client_ip(proxy, &forwarded), None);
+    let by_forwarded = ac.clone().with_forwarded_header(ForwardedHeader::Forwarded);
+    assert_eq!(
+        by_forwarded.client_ip(proxy, &forwarded),
+        Some(ip("203.0.113.9"))
+    );
+    let forwarded = headers(&[
+        ("forwarded", "for=unknown"),
+        ("x-forwarded-for", "203.0.113.9"),
+    ]);
+    assert_eq!(by_forwarded.client_ip(proxy, &forwarded), None);
+
+    let real_ip = headers(&[("x-real-ip", "203.0.113.4")]);
+    let by_real_ip = ac.clone().with_forwarded_header(ForwardedHeader::XRealIp);
+    assert_eq!(
+        by_real_ip.client_ip(proxy, &real_ip),
+        Some(ip("203.0.113.4"))
+    );
+    assert_eq!(by_real_ip.client_ip(proxy, &HeaderMap::new()), None);
+    assert!("X-Real-IP".parse::<ForwardedHeader>().is_ok());
+    assert!("true-client-ip".parse::<ForwardedHeader>().is_err());
+}
+
+#[test]
+fn client_supplied_headers_other_than_the_configured_one_are_ignored() {
+    let proxy = ip("10.0.0.2");
+    let ac = AccessControl::new(
+        vec!["203.0.113.0/24".to_string()],
+        vec!["10.0.0.0/24".to_string()],
+    );
+
+    // The proxy appends to X-Forwarded-For and passes Forwarded through
+    let spoofed = headers(&[
+        ("forwarded", "for=203.0.113.9"),
+        ("x-real-ip", "203.0.113.9"),
+        ("x-forwarded-for", "198.51.100.7"),
+    ]);
+    assert_eq!(ac.client_ip(proxy, &spoofed), Some(ip("198.51.100.7")));
+    assert!(!ac.is_allowed(proxy, &spoofed).0);
+
+    // A proxy writing only X-Real-IP leaves X-Forwarded-For to the client
+    let by_real_ip = ac.with_forwarded_header(ForwardedHeader::XRealIp);
+    let spoofed = headers(&[
+        ("x-forwarded-for", "203.0.113.9"),
+        ("x-real-ip", "198.51.100.7"),
+    ]);
+    assert_eq!(
+        by_real_ip.client_ip(proxy, &spoofed),
+        Some(ip("198.51.100.7"))
+    );
+    assert!(!by_real_ip.is_allowed(proxy, &spoofed).0);
+}
+
+#[test]
+fn trusted_hops_limit_the_walk() {
+    let chain = headers(&[("x-forwarded-for", "203.0.113.9, 198.51.100.7, 10.0.0.7")]);
+
+    // Only the load balancer in front is trusted
+    let one_hop =
+        AccessControl::new(Vec::new(), vec!["10.0.0.0/24".to_string()]).with_trusted_hops(1);
+    assert_eq!(
+        one_hop.client_ip(ip("10.0.0.2"), &chain),
+        Some(ip("10.0.0.7"))
+    );
+
+    // Without a proxy list, hops are trusted by count
+    let by_count = AccessControl::new(Vec::new(), Vec::new()).with_trusted_hops(2);
+    assert_eq!(
+        by_count.client_ip(ip("192.0.2.1"), &chain),
+        Some(ip("198.51.100.7"))
+    );
+
+    // Neither configured: forwarding headers are ignored
+    let direct = AccessControl::new(Vec::new(), Vec::new());
+    assert_eq!(
+        direct.client_ip(ip("192.0.2.1"), &chain),
+        Some(ip("192.0.2.1"))
+    );
+}
+
+#[test]
+fn proxied_clients_still_pass_the_balancer_check() {
+    let ac = AccessControl::new(
+        vec!["203.0.113.0/24".to_string()],
+        vec!["10.0.0.0/24".to_string()],
+    );
+    let through = |addr: &str, client: &str| Peer {
+        addr: ip(addr),
+        proxied_for: Some(ip(client)),
+    };
+
+    // The PROXY header source is the client; the balancer must be trusted
+    let peer = through("10.0.0.2", "203.0.113.9");
+    assert_eq!(
+        ac.client_ip(peer, &HeaderMap::new()),
+        Some(ip("203.0.113.9"))
+    );
+    assert!(ac.is_allowed(peer, &HeaderMap::new()).0);
+    assert!(
+        !ac.is_allowed(through("10.0.0.2", "198.51.100.7"), &HeaderMap::new())
+            .0
+    );
+    assert!(
+        !ac.is_allowed(through("192.0.2.1", "203.0.113.9"), &HeaderMap::new())
+            .0
+    );
+
+    // Forged forwarding headers from the client are not trusted
+    let forged = headers(&[("x-forwarded-for", "203.0.113.9")]);
+    assert!(
+        !ac.is_allowed(through("10.0.0.2", "198.51.100.7"), &forged)
+            .0
+    );
+}
@@ -0,0 +1,365 @@
+use api::helpers::clusters::{cluster_failures, normalize};
+use api::helpers::compare::{ComparedRun, ComparedTest, DurationThreshold, compare, select_run};
+use api::helpers::flaky::detect;
+use api::helpers::history::retained_results_dir;
+use api::helpers::project_settings;
+use api::helpers::quality_gate::evaluate;
+use api::helpers::quarantine::{self, Quarantine};
+use api::helpers::run_summary::build;
+use api::helpers::test_history::{TestKey, test_history};
+use api::helpers::test_results::read_results;
+use api::helpers::trend::build_trend;
+use api::models::project::{ProjectSettings, QualityGateRules};
+use api::models::quarantine::AddQuarantineRequest;
+use serde_json::json;
+use std::path::Path;
+
+fn write_result(results: &Path, name: &str, status: &str, start: i64, stop: i64) {
+    std::fs::create_dir_all(results).unwrap();
+    let result = json!({
+        "uuid": uuid::Uuid::new_v4().to_string(),
+        "historyId": name,
+        "name": name,
+        "fullName": format!("suite.{}", name),
+        "status": status,
+        "start": start,
+        "stop": stop
+    });
+    std::fs::write(
+        results.join(format!("{}-result.json", uuid::Uuid::new_v4())),
+        result.to_string(),
+    )
+    .unwrap();
+}
+
+#[test]
+fn trend_is_built_from_run_results_then_filtered() {
+    let report = std::env::temp_dir().join(format!("trend-test-{}", uuid::Uuid::new_v4()));
+    for (id, failing) in [(1, "failed"), (2, "passed"), (3, "skipped")] {
+        let results = retained_results_dir(&report.join(id.to_string()));
+        let base = id as i64 * 10_000;
+        write_result(&results, "a", "passed", base, base + 100);
+        write_result(&results, "b", failing, base + 50, base + 400);
+    }
+
+    let trend = build_trend(&report, "/p/b/r", &Quarantine::default(), None, None, None).unwrap();
+    assert_eq!(trend.source, "runs");
+    assert_eq!(trend.points.len(), 3);
+    let first = &trend.points[0];
+    assert_eq!(first.run_id, Some(1));
+    assert_eq!(first.pass_rate, Some(0.5));
+    assert_eq!(first.duration_ms, Some(400));
+    assert_eq!(first.tests_duration_ms, 450);
+    assert_eq!(trend.points[2].pass_rate, Some(1.0));
+
+    let trend = build_trend(
+        &report,
+        "/p/b/r",
+        &Quarantine::default(),
+        Some(20_000),
+        None,
+        Some(1),
+    )
+    .unwrap();
+    assert_eq!(trend.points.len(), 1);
+    assert_eq!(trend.points[0].run_id, Some(3));
+    assert_eq!(trend.points[0].url.as_deref(), Some("/p/b/r/3/index.html"));
+
+    std::fs::remove_dir_all(&report).unwrap();
+}
+
+#[test]
+fn trend_falls_back_to_history() {
+    let report = std::env::temp_dir().join(format!("trend-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&report).unwrap();
+    let entry = json!({
+        "uuid": "h1",
+        "timestamp": 5000,
+        "url": "/p/b/r/7/index.html",
+        "testResults": {
+            "a": { "status": "passed", "duration": 30 },
+            "b": { "status": "broken", "duration": 70 }
+        }
+    });
+    std::fs::write(report.join("history.jsonl"), format!("{}\n", entry)).unwrap();
+
+    let trend = build_trend(&report, "/p/b/r", &Quarantine::default(), None, None, None).unwrap();
+    assert_eq!(trend.source, "history");
+    assert_eq!(trend.points[0].run_id, Some(7));
+    assert_eq!(trend.points[0].tests_duration_ms, 100);
+    assert_eq!(trend.points[0].pass_rate, Some(0.5));
+
+    std::fs::remove_dir_all(&report).unwrap();
+}
+
+#[test]
+fn flaky_tests_need_repeated_flips_within_the_window() {
+    let report = std::env::temp_dir().join(format!("flaky-test-{}", uuid::Uuid::new_v4()));
+    let flaky = ["passed", "failed", "passed", "broken", "passed"];
+    let regressed = ["passed", "passed", "failed", "failed", "failed"];
+    for id in 1..=5u32 {
+        let results = retained_results_dir(&report.join(id.to_string()));
+        let base = id as i64 * 1000;
+        write_result(&results, "flaky", flaky[id as usize - 1], base, base + 10);
+        write_result(
+            &results,
+            "regressed",
+            regressed[id as usize - 1],
+            base,
+            base + 10,
+        );
+        write_result(&results, "skipped", "skipped", base, base + 10);
+    }
+
+    let tests = detect(&report, 10, 2, &Quarantine::default());
+    assert_eq!(tests.len(), 1);
+    assert_eq!(tests[0].history_id, "flaky");
+    assert_eq!(tests[0].flips, 4);
+    assert_eq!(tests[0].flip_rate, 1.0);
+    assert_eq!(tests[0].failures, 2);
+    assert_eq!(tests[0].last_outcomes.len(), 5);
+
+    // The regression only shows up once its passing run is in the window
+    assert!(
+        detect(&report, 2, 1, &Quarantine::default())
+            .iter()
+            .all(|t| t.history_id != "regressed")
+    );
+    assert_eq!(detect(&report, 4, 1, &Quarantine::default()).len(), 2);
+
+    std::fs::remove_dir_all(&report).unwrap();
+}
+
+#[test]
+fn test_history_spans_reports_and_finds_failing_streak() {
+    let branch = std::env::temp_dir().join(format!("test-history-{}", uuid::Uuid::new_v4()));
+    let runs = [
+        ("smoke", 1, "passed", 1000),
+        ("nightly", 1, "passed", 2000),
+        ("smoke", 2, "failed", 3000),
+        ("nightly", 2, "broken", 4000),
+    ];
+    for (report, id, status, start) in runs {
+        let results = retained_results_dir(&branch.join(report).join(id.to_string()));
+        write_result(&results, "login", status, start, start + 5);
+        write_result(&results, "other", "passed", start, start + 5);
+    }
+
+    let history = test_history(
+        &branch,
+        None,
+        "/p/b",
+        &TestKey::FullName("suite.login".to_string()),
+    );
+    assert_eq!(history.history_id.as_deref(), Some("login"));
+    let order: Vec<_> = history
+        .runs
+        .iter()
+        .map(|r| (r.report_name.as_str(), r.run_id))
+        .collect();
+    assert_eq!(
+        order,
+        [("smoke", 1), ("nightly", 1), ("smoke", 2), ("nightly", 2)]
+    );
+    let since = history.failing_since.unwrap();
+    assert_eq!((since.report_name.as_str(), since.run_id), ("smoke", 2));
+    assert!(since.url.starts_with("/p/b/smoke/2/index.html#testresult/"));
+
+    let history = test_history(
+        &branch,
+        Some("nightly"),
+        "/p/b",
+        &TestKey::HistoryId("login".to_string()),
+    );
+    assert_eq!(history.runs.len(), 2);
+
+    std::fs::remove_dir_all(&branch).unwrap();
+}
+
+#[test]
+fn compare_classifies_tests_between_runs() {
+    let report = std::env::temp_dir().join(format!("compare-test-{}", uuid::Uuid::new_v4()));
+    let base = retained_results_dir(&report.join("41"));
+    write_result(&base, "breaks", "passed", 0, 10);
+    write_result(&base, "heals", "failed", 0, 10);
+    write_result(&base, "stuck", "broken", 0, 10);
+    write_result(&base, "slows", "passed", 0, 1000);
+    write_result(&base, "gone", "passed", 0, 10);
+    let head = retained_results_dir(&report.join("42"));
+    write_result(&head, "breaks", "failed", 0, 10);
+    write_result(&head, "heals", "passed", 0, 10);
+    write_result(&head, "stuck", "failed", 0, 10);
+    write_result(&head, "slows", "passed", 0, 3000);
+    write_result(&head, "new", "passed", 0, 10);
+
+    let (head_id, head_tests) = select_run(&report, None, None).unwrap();
+    let (base_id, base_tests) = select_run(&report, None, Some(head_id)).unwrap();
+    assert_eq!((base_id, head_id), (41, 42));
+
+    let run = |run_id| ComparedRun {
+        branch: "main".into(),
+        report_name: "e2e".into(),
+        run_id,
+        url: format!("/p/main/e2e/{}/index.html", run_id),
+    };
+    let threshold = DurationThreshold {
+        min_change: 0.5,
+        min_delta_ms: 1000,
+    };
+    let result = compare(run(41), &base_tests, run(42), &head_tests, &threshold);
+    let ids = |tests: &[ComparedTest]| {
+        tests
+            .iter()
+            .map(|t| t.history_id.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ids(&result.new_failures), ["breaks"]);
+    assert_eq!(ids(&result.fixed), ["heals"]);
+    assert_eq!(ids(&result.still_failing), ["stuck"]);
+    assert_eq!(ids(&result.added), ["new"]);
+    assert_eq!(ids(&result.removed), ["gone"]);
+    assert_eq!(result.duration_changes.len(), 1);
+    assert_eq!(result.duration_changes[0].change, 2.0);
+    assert!(result.to_markdown().contains("1 new failures"));
+
+    std::fs::remove_dir_all(&report).unwrap();
+}
+
+#[test]
+fn failures_cluster_by_normalized_message() {
+    assert_eq!(
+        normalize(
+            "Timeout 30000ms at 2024-06-20T10:15:30.123Z for id 3f2a9c1e-8b7d-4e2f-9a1b-2c3d4e5f6a7b"
+        ),
+        normalize(
+            "Timeout 5000ms at 2025-01-02T00:00:01.999Z for id 0a1b2c3d-4e5f-6a7b-8c9d-0e1f2a3b4c5d"
+        )
+    );
+    assert_ne!(
+        normalize("Connection refused"),
+        normalize("Element not found")
+    );
+
+    let results = std::env::temp_dir().join(format!("cluster-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&results).unwrap();
+    for i in 0..4 {
+        let message = if i < 3 {
+            format!("connect ECONNREFUSED 127.0.0.1:{}", 5000 + i)
+        } else {
+            "expected 1 to equal 2".to_string()
+        };
+        let result = json!({
+            "uuid": format!("u{}", i),
+            "historyId": format!("t{}", i),
+            "name": format!("t{}", i),
+            "status": "failed",
+            "statusDetails": { "message": message, "trace": format!("at db.js:{}:7", 10 + i) }
+        });
+        std::fs::write(
+            results.join(format!("{}-result.json", i)),
+            result.to_string(),
+        )
+        .unwrap();
+    }
+
+    let clusters = cluster_failures(&read_results(&results), "/p/b/r/1/index.html");
+    assert_eq!(clusters.len(), 2);
+    assert_eq!(clusters[0].count, 3);
+    assert_eq!(clusters[0].examples.len(), 3);
+    assert_eq!(clusters[1].count, 1);
+
+    std::fs::remove_dir_all(&results).unwrap();
+}
+
+#[test]
+fn quality_gate_reports_failed_rules() {
+    let data = std::env::temp_dir().join(format!("gate-test-{}", uuid::Uuid::new_v4()));
+    let main = retained_results_dir(&data.join("p/main/e2e/1"));
+    write_result(&main, "a", "passed", 0, 10);
+    write_result(&main, "b", "failed", 0, 10);
+    let pr = retained_results_dir(&data.join("p/pr-1/e2e/1"));
+    write_result(&pr, "a", "failed", 0, 10);
+    write_result(&pr, "b", "failed", 0, 10);
+    write_result(&pr, "c", "broken", 0, 10);
+    write_result(&pr, "d", "passed", 0, 10);
+
+    // Without rules every run passes
+    assert!(evaluate(&data, "p", "pr-1", "e2e", 1).unwrap().passed);
+
+    let settings = ProjectSettings {
+        quality_gate: QualityGateRules {
+            min_pass_rate: Some(0.98),
+            no_new_failures_vs: Some("main".into()),
+            max_failed: Some(5),
+            max_broken: Some(0),
+        },
+        ..Default::default()
+    };
+    project_settings::save(&data, "p", &settings).unwrap();
+
+    let verdict = evaluate(&data, "p", "pr-1", "e2e", 1).unwrap();
+    assert!(!verdict.passed);
+    let failed: Vec<_> = verdict
+        .checks
+        .iter()
+        .filter(|c| !c.passed)
+        .map(|c| c.rule.as_str())
+        .collect();
+    assert_eq!(
+        failed,
+        ["min_pass_rate", "max_broken", "no_new_failures_vs"]
+    );
+    // `a` regressed and `c` is new and broken; `b` already failed on main
+    assert!(verdict.reasons[2].starts_with("2 new failures"));
+    assert!(verdict.reasons[2].contains("suite.a"));
+    assert!(verdict.reasons[2].contains("suite.c"));
+    assert!(!verdict.reasons[2].contains("suite.b"));
+    assert!(!verdict.reasons[2].contains("suite.d"));
+
+    std::fs::remove_dir_all(&data).unwrap();
+}
+
+#[test]
+fn quarantined_tests_are_left_out_of_the_gate() {
+    let data = std::env::temp_dir().join(format!("quarantine-test-{}", uuid::Uuid::new_v4()));
+    let results = retained_results_dir(&data.join("p/main/e2e/1"));
+    write_result(&results, "stable", "passed", 0, 10);
+    write_result(&results, "checkout_flaky", "failed", 0, 10);
+
+    let settings = ProjectSettings {
+        quality_gate: QualityGateRules {
+            max_failed: Some(0),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    project_settings::save(&data, "p", &settings).unwrap();
+    assert!(!evaluate(&data, "p", "main", "e2e", 1).unwrap().passed);
+
+    let request = |pattern: &str, expires: &str| AddQuarantineRequest {
+        history_id: None,
+        full_name: None,
+        pattern: Some(pattern.to_string()),
+        owner: "qa-team".to_string(),
+        reason: None,
+        expires: expires.to_string(),
+    };
+    assert!(quarantine::add(&data, "p", request("suite.*", "2000-01-01")).is_err());
+    let entry = quarantine::add(&data, "p", request("suite.checkout_*", "2999-12-31")).unwrap();
+
+    assert!(evaluate(&data, "p", "main", "e2e", 1).unwrap().passed);
+    let summary = build(1, &read_results(&results), &Quarantine::load(&data, "p"));
+    assert_eq!(summary.total, 1);
+    assert_eq!(summary.pass_rate, Some(1.0));
+    assert_eq!(summary.quarantined[0].entry_id, entry.id);
+
+    // Retained results get the upload-time tag stripped again on rebuild
+    assert_eq!(Quarantine::load(&data, "p").tag_results(&results), 1);
+    assert_eq!(quarantine::untag_results(&results), 1);
+    assert_eq!(quarantine::untag_results(&results), 0);
+
+    assert!(quarantine::remove(&data, "p", &entry.id).unwrap());
+    assert!(!evaluate(&data, "p", "main", "e2e", 1).unwrap().passed);
+
+    std::fs::remove_dir_all(&data).unwrap();
+}
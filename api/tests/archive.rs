@@ -0,0 +1,50 @@
+use api::helpers::archive::{ARCHIVE_FILE_NAME, archive_run, is_archived, rehydrate_run};
+use api::helpers::fs_helper::StorageOptions;
+use std::fs;
+
+#[test]
+fn archive_and_rehydrate_round_trip() {
+    let data_dir = std::env::temp_dir().join(format!("archive-test-{}", uuid::Uuid::new_v4()));
+    let run = data_dir.join("p/b/r/1");
+    fs::create_dir_all(run.join("data/test-results")).unwrap();
+    fs::create_dir_all(run.join(".meta")).unwrap();
+    fs::write(run.join("index.html"), "<html></html>").unwrap();
+    fs::write(
+        run.join("data/test-results/a.json"),
+        "{\"status\":\"passed\"}",
+    )
+    .unwrap();
+    fs::write(run.join(".meta/summary.json"), "{}").unwrap();
+    // Hidden below the top level is report content, not run metadata
+    fs::write(run.join("data/.attachments-index"), "a").unwrap();
+    fs::write(run.join("data/export.csv"), "a,b").unwrap();
+    fs::write(run.join("data/export.csv.gz"), "not generated").unwrap();
+
+    let storage = StorageOptions {
+        precompress: false,
+        dedup: false,
+    };
+    let (files, _) = archive_run(&run, storage).unwrap();
+    assert_eq!(files, 5);
+    assert!(!run.join(format!("{}.tmp", ARCHIVE_FILE_NAME)).exists());
+    assert!(is_archived(&run));
+    assert!(!run.join("index.html").exists());
+    assert!(run.join(".meta/summary.json").exists());
+
+    assert_eq!(rehydrate_run(&data_dir, &run, storage).unwrap(), 5);
+    assert_eq!(
+        fs::read_to_string(run.join("data/.attachments-index")).unwrap(),
+        "a"
+    );
+    assert_eq!(
+        fs::read_to_string(run.join("data/export.csv.gz")).unwrap(),
+        "not generated"
+    );
+    assert!(!run.join(ARCHIVE_FILE_NAME).exists());
+    assert_eq!(
+        fs::read_to_string(run.join("data/test-results/a.json")).unwrap(),
+        "{\"status\":\"passed\"}"
+    );
+
+    fs::remove_dir_all(&data_dir).unwrap();
+}
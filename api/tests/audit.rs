@@ -0,0 +1,144 @@
+use api::helpers::access_control::AccessControl;
+use api::helpers::audit::{
+    self, AuditEntry, AuditFilter, AuditResult, UNKNOWN_KEY_ID, append_to, audit, query,
+};
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+    middleware,
+    routing::post,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+fn temp_log() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("audit-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(".audit.jsonl")
+}
+
+fn entry(timestamp: i64, key_id: &str, action: &str, target: &str, status: u16) -> AuditEntry {
+    AuditEntry {
+        timestamp,
+        key_id: key_id.to_string(),
+        subject: None,
+        client_ip: Some("203.0.113.9".to_string()),
+        action: action.to_string(),
+        target: target.to_string(),
+        status,
+        result: AuditResult::from_status(status),
+    }
+}
+
+#[test]
+fn queries_filter_and_return_newest_first() {
+    let log = temp_log();
+    assert!(query(&log, &AuditFilter::default()).unwrap().is_empty());
+
+    append_to(
+        &log,
+        &entry(
+            1_000,
+            "ci",
+            "POST /api/reports/upload",
+            "/web/main/e2e/1/",
+            200,
+        ),
+    )
+    .unwrap();
+    append_to(
+        &log,
+        &entry(
+            2_000,
+            "ci",
+            "POST /api/reports/upload",
+            "/api/reports/upload",
+            429,
+        ),
+    )
+    .unwrap();
+    append_to(
+        &log,
+        &entry(
+            3_000,
+            "ops",
+            "DELETE /api/projects/:project",
+            "/api/projects/web",
+            200,
+        ),
+    )
+    .unwrap();
+    // A torn line from a crash does not break queries
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&log)
+        .and_then(|mut f| std::io::Write::write_all(&mut f, b"{\"timestamp\":4"))
+        .unwrap();
+
+    let all = query(&log, &AuditFilter::default()).unwrap();
+    assert_eq!(
+        all.iter().map(|e| e.timestamp).collect::<Vec<_>>(),
+        [3_000, 2_000, 1_000]
+    );
+
+    let denied = AuditFilter {
+        result: Some(AuditResult::Denied),
+        ..Default::default()
+    };
+    assert_eq!(query(&log, &denied).unwrap()[0].status, 429);
+
+    let by_key = AuditFilter {
+        key_id: Some("ci".to_string()),
+        since: Some(1_500),
+        ..Default::default()
+    };
+    assert_eq!(query(&log, &by_key).unwrap().len(), 1);
+
+    let deletes = AuditFilter {
+        action: Some("DELETE".to_string()),
+        target: Some("/api/projects/".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(query(&log, &deletes).unwrap()[0].key_id, "ops");
+
+    let latest = AuditFilter {
+        limit: Some(1),
+        ..Default::default()
+    };
+    assert_eq!(query(&log, &latest).unwrap()[0].timestamp, 3_000);
+
+    std::fs::remove_dir_all(log.parent().unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn requests_refused_without_a_key_are_logged() {
+    let log = temp_log();
+    audit::configure(Some(log.clone()));
+    // Stands in for `auth` turning away a wrong API key
+    let app = Router::new()
+        .route(
+            "/api/projects/:project_name",
+            post(|| async { StatusCode::UNAUTHORIZED }),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            Arc::new(AccessControl::new(Vec::new(), Vec::new())),
+            audit,
+        ));
+
+    let request = Request::post("/api/projects/web")
+        .header("x-api-key", "wrong")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let entries = query(&log, &AuditFilter::default()).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].key_id, UNKNOWN_KEY_ID);
+    assert_eq!(entries[0].subject, None);
+    assert_eq!(entries[0].action, "POST /api/projects/:project_name");
+    assert_eq!(entries[0].target, "/api/projects/web");
+    assert_eq!(entries[0].result, AuditResult::Denied);
+}
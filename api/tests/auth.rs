@@ -0,0 +1,148 @@
+use api::helpers::api_keys::{
+    Principal, Scope, authenticate, hash_secret, list_keys, load_keys, reload_if_changed,
+    reload_keys,
+};
+use api::helpers::auth::{authorize, required_scope};
+use axum::http::Method;
+
+fn principal(scopes: &[Scope], projects: &[&str], branches: &[&str]) -> Principal {
+    Principal {
+        key_id: "test".to_string(),
+        scopes: scopes.to_vec(),
+        projects: projects.iter().map(|s| s.to_string()).collect(),
+        branches: branches.iter().map(|s| s.to_string()).collect(),
+        subject: None,
+    }
+}
+
+#[test]
+fn scopes_follow_method_and_path() {
+    let report = "/projects/web/branches/main/reports/e2e";
+    assert_eq!(
+        required_scope(&Method::POST, "/reports/upload"),
+        Scope::Upload
+    );
+    assert_eq!(
+        required_scope(&Method::GET, &format!("{}/trend", report)),
+        Scope::Read
+    );
+    assert_eq!(
+        required_scope(&Method::DELETE, &format!("{}/runs/3", report)),
+        Scope::Delete
+    );
+    assert_eq!(
+        required_scope(&Method::POST, &format!("{}/history/trim", report)),
+        Scope::Delete
+    );
+    assert_eq!(
+        required_scope(&Method::PUT, "/projects/web/settings"),
+        Scope::Admin
+    );
+    assert_eq!(required_scope(&Method::GET, "/usage"), Scope::Admin);
+    assert_eq!(required_scope(&Method::GET, "/admin/dedup"), Scope::Admin);
+}
+
+#[test]
+fn keys_are_limited_to_their_projects_and_branches() {
+    let ci = principal(
+        &[Scope::Upload, Scope::Read],
+        &["web-*"],
+        &["main", "release/*"],
+    );
+    assert!(authorize(&ci, &Method::POST, "/reports/upload"));
+    assert!(authorize(
+        &ci,
+        &Method::GET,
+        "/projects/web-shop/branches/main/reports/e2e/trend"
+    ));
+    assert!(authorize(&ci, &Method::GET, "/projects/web-shop/settings"));
+    assert!(!authorize(
+        &ci,
+        &Method::GET,
+        "/projects/api/branches/main/reports/e2e/trend"
+    ));
+    assert!(!authorize(
+        &ci,
+        &Method::GET,
+        "/projects/web-shop/branches/feature/reports/e2e/trend"
+    ));
+    assert!(!authorize(
+        &ci,
+        &Method::DELETE,
+        "/projects/web-shop/branches/main/reports/e2e/runs/1"
+    ));
+    assert!(ci.allows("web-shop", Some("release/2.0")));
+    assert!(!ci.allows("web-shop", Some("dev")));
+
+    // Restricted keys never reach server-wide endpoints, even as admin
+    let project_admin = principal(&[Scope::Admin], &["web-*"], &[]);
+    assert!(authorize(
+        &project_admin,
+        &Method::PUT,
+        "/projects/web-shop/settings"
+    ));
+    assert!(!authorize(&project_admin, &Method::GET, "/usage"));
+    let admin = principal(&[Scope::Admin], &[], &[]);
+    assert!(authorize(&admin, &Method::POST, "/admin/dedup/gc"));
+}
+
+#[test]
+fn key_file_rotation_and_reload() {
+    let dir = std::env::temp_dir().join(format!("keys-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("keys.json");
+
+    std::fs::write(
+        &path,
+        r#"{"keys":[{"id":"ci","hash":"secret","scopes":["upload"]}]}"#,
+    )
+    .unwrap();
+    assert!(load_keys(&path).is_err());
+
+    let file = serde_json::json!({
+        "keys": [
+            { "id": "ci", "hash": format!("sha256:{}", hash_secret("ci-secret")),
+              "scopes": ["upload"], "projects": ["web"] },
+            { "id": "reader", "hash": hash_secret("read-secret"), "scopes": ["read"] }
+        ]
+    });
+    std::fs::write(&path, serde_json::to_vec(&file).unwrap()).unwrap();
+    assert_eq!(load_keys(&path).unwrap(), 2);
+
+    let ci = authenticate("ci-secret").unwrap();
+    assert_eq!(ci.key_id, "ci");
+    assert!(ci.has_scope(Scope::Upload) && !ci.has_scope(Scope::Read));
+    assert_eq!(authenticate("read-secret").unwrap().key_id, "reader");
+    assert!(authenticate("wrong").is_none());
+    assert!(authenticate("").is_none());
+    assert!(reload_if_changed().is_none());
+
+    // Rotation: the new secret is added next to the old one, which then expires
+    let file = serde_json::json!({
+        "keys": [
+            { "id": "ci", "scopes": ["upload"], "secrets": [
+                { "hash": hash_secret("ci-secret"), "expires": "2000-01-01" },
+                { "hash": hash_secret("ci-secret-2") }
+            ] }
+        ]
+    });
+    std::fs::write(&path, serde_json::to_vec(&file).unwrap()).unwrap();
+    assert_eq!(reload_keys().unwrap(), 1);
+    assert!(authenticate("ci-secret").is_none());
+    assert_eq!(authenticate("ci-secret-2").unwrap().key_id, "ci");
+    assert!(authenticate("read-secret").is_none());
+    let keys = list_keys();
+    assert_eq!(keys[0].secrets.len(), 2);
+    assert!(keys[0].secrets[0].expired && !keys[0].secrets[1].expired);
+    assert_eq!(
+        keys[0].secrets[1].fingerprint,
+        hash_secret("ci-secret-2")[..8]
+    );
+
+    // A broken file keeps the previous keys active
+    std::fs::write(&path, "{").unwrap();
+    assert!(reload_keys().is_err());
+    assert_eq!(authenticate("ci-secret-2").unwrap().key_id, "ci");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
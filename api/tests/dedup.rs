@@ -0,0 +1,48 @@
+use api::helpers::dedup::{STORE_DIR_NAME, dedup_dir, gc, stats};
+use api::helpers::fs_helper::validate_path_segment;
+use std::fs;
+
+#[test]
+fn identical_files_share_a_blob_and_gc_keeps_referenced_ones() {
+    let data_dir = std::env::temp_dir().join(format!("dedup-test-{}", uuid::Uuid::new_v4()));
+    let run1 = data_dir.join("p/b/r/1");
+    let run2 = data_dir.join("p/b/r/2");
+    fs::create_dir_all(&run1).unwrap();
+    fs::create_dir_all(&run2).unwrap();
+    fs::write(run1.join("app.js"), "console.log(1)").unwrap();
+    fs::write(run2.join("app.js"), "console.log(1)").unwrap();
+    fs::write(run2.join("data.json"), "{}").unwrap();
+
+    dedup_dir(&data_dir, &run1).unwrap();
+    let res = dedup_dir(&data_dir, &run2).unwrap();
+    assert_eq!(res.files_linked, 1);
+    assert_eq!(res.bytes_saved, 14);
+
+    let s = stats(&data_dir);
+    assert_eq!(s.entries, 2);
+    assert_eq!(s.saved_bytes, 14);
+
+    fs::remove_dir_all(&run1).unwrap();
+    assert_eq!(gc(&data_dir), (0, 0));
+    assert_eq!(
+        fs::read_to_string(run2.join("app.js")).unwrap(),
+        "console.log(1)"
+    );
+
+    fs::remove_dir_all(&run2).unwrap();
+    assert_eq!(gc(&data_dir).0, 2);
+
+    fs::remove_dir_all(&data_dir).unwrap();
+}
+
+#[test]
+fn hidden_names_cannot_reach_the_store() {
+    for name in [STORE_DIR_NAME, ".usage.json", ".hidden"] {
+        assert!(validate_path_segment(name, "project_name").is_err());
+        assert!(validate_path_segment(name, "branch").is_err());
+    }
+    assert_eq!(
+        validate_path_segment(" v1.2 ", "report_name").unwrap(),
+        "v1.2"
+    );
+}
@@ -0,0 +1,94 @@
+use api::helpers::history::{
+    history_path, read_lines, rebuild, remove_entries, reset, trim, write_lines,
+};
+use std::fs;
+use std::path::PathBuf;
+
+fn temp_report() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("history-test-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn entry(uuid: &str) -> String {
+    format!("{{\"uuid\":\"{}\",\"testResults\":{{}}}}", uuid)
+}
+
+#[test]
+fn trim_keeps_the_newest_entries() {
+    let report = temp_report();
+    let lines: Vec<String> = ["a", "b", "c", "d"].iter().map(|u| entry(u)).collect();
+    write_lines(&report, &lines).unwrap();
+
+    assert_eq!(trim(&report, 10).unwrap(), 0);
+    assert_eq!(trim(&report, 2).unwrap(), 2);
+    assert_eq!(read_lines(&report).unwrap(), [entry("c"), entry("d")]);
+    assert_eq!(trim(&report, 0).unwrap(), 2);
+    assert!(read_lines(&report).unwrap().is_empty());
+    fs::remove_dir_all(&report).unwrap();
+}
+
+#[test]
+fn remove_entries_matches_uuids_and_keeps_other_lines() {
+    let report = temp_report();
+    let lines = vec![
+        entry("a"),
+        "not json".to_string(),
+        entry("b"),
+        "{\"name\":\"no uuid\"}".to_string(),
+        entry("c"),
+    ];
+    write_lines(&report, &lines).unwrap();
+
+    let removed = remove_entries(&report, &["a".to_string(), "c".to_string()]).unwrap();
+    assert_eq!(removed, 2);
+    assert_eq!(
+        read_lines(&report).unwrap(),
+        ["not json", &entry("b"), "{\"name\":\"no uuid\"}"]
+    );
+    // Unknown uuids leave the file as it is
+    assert_eq!(remove_entries(&report, &["x".to_string()]).unwrap(), 0);
+    assert_eq!(read_lines(&report).unwrap().len(), 3);
+    fs::remove_dir_all(&report).unwrap();
+}
+
+#[test]
+fn reset_reports_whether_there_was_a_history() {
+    let report = temp_report();
+    assert!(!reset(&report).unwrap());
+
+    write_lines(&report, &[entry("a")]).unwrap();
+    assert!(reset(&report).unwrap());
+    assert!(!history_path(&report).exists());
+    assert!(!reset(&report).unwrap());
+    fs::remove_dir_all(&report).unwrap();
+}
+
+#[test]
+fn rebuild_without_retained_results_keeps_the_old_history() {
+    let report = temp_report();
+    let old = vec![entry("a"), "not json".to_string()];
+    write_lines(&report, &old).unwrap();
+    // Runs uploaded before results were retained
+    fs::create_dir_all(report.join("1")).unwrap();
+    fs::create_dir_all(report.join("2/.meta")).unwrap();
+    let runs = vec![
+        (2, report.join("2"), "/p/main/e2e/2/index.html".to_string()),
+        (1, report.join("1"), "/p/main/e2e/1/index.html".to_string()),
+    ];
+
+    let err = rebuild(&report, &runs).unwrap_err();
+    assert!(err.contains("retained results"));
+    assert!(rebuild(&report, &[]).is_err());
+    assert_eq!(read_lines(&report).unwrap(), old);
+    // The scratch directory is cleaned up
+    assert_eq!(
+        fs::read_dir(&report)
+            .unwrap()
+            .flatten()
+            .filter(|e| e.file_name().to_string_lossy().starts_with('.'))
+            .count(),
+        0
+    );
+    fs::remove_dir_all(&report).unwrap();
+}
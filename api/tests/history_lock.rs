@@ -0,0 +1,57 @@
+use api::helpers::history_lock::acquire;
+use api::helpers::quarantine;
+use api::models::quarantine::AddQuarantineRequest;
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn second_acquire_waits_for_first_to_drop() {
+    let dir = std::env::temp_dir().join(format!("history-lock-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let first = acquire(&dir).await.unwrap();
+    let waiter_dir = dir.clone();
+    let waiter = tokio::spawn(async move {
+        let started = Instant::now();
+        let _second = acquire(&waiter_dir).await.unwrap();
+        started.elapsed()
+    });
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    drop(first);
+
+    let waited = waiter.await.unwrap();
+    assert!(waited >= Duration::from_millis(250));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn concurrent_quarantine_changes_keep_every_entry() {
+    let data = std::env::temp_dir().join(format!("quarantine-lock-test-{}", uuid::Uuid::new_v4()));
+    let adds = (0..8).map(|i| {
+        let data = data.clone();
+        tokio::spawn(async move {
+            let _lock = quarantine::lock(&data, "p").await.unwrap();
+            let request = AddQuarantineRequest {
+                history_id: Some(format!("test-{}", i)),
+                full_name: None,
+                pattern: None,
+                owner: "qa-team".to_string(),
+                reason: None,
+                expires: "2999-12-31".to_string(),
+            };
+            quarantine::add(&data, "p", request).unwrap()
+        })
+    });
+    let mut ids = Vec::new();
+    for add in adds.collect::<Vec<_>>() {
+        ids.push(add.await.unwrap().id);
+    }
+    assert_eq!(quarantine::list(&data, "p").len(), 8);
+
+    let _lock = quarantine::lock(&data, "p").await.unwrap();
+    assert!(quarantine::remove(&data, "p", &ids[0]).unwrap());
+    assert_eq!(quarantine::list(&data, "p").len(), 7);
+
+    std::fs::remove_dir_all(&data).unwrap();
+}
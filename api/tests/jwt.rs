@@ -0,0 +1,152 @@
+use api::helpers::api_keys::{Scope, load_keys};
+use api::helpers::auth::authorize;
+use api::helpers::jwt::{self, JwksSource, JwtConfig};
+use axum::http::Method;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use serde_json::{Value, json};
+use std::sync::OnceLock;
+
+const ISSUER: &str = "https://token.actions.githubusercontent.com";
+const AUDIENCE: &str = "report-host";
+
+struct Signer {
+    kid: String,
+    pkcs8: Vec<u8>,
+    public: Vec<u8>,
+}
+
+fn signer(kid: &str) -> Signer {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+    let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+    Signer {
+        kid: kid.to_string(),
+        pkcs8: pkcs8.as_ref().to_vec(),
+        public: pair.public_key().as_ref().to_vec(),
+    }
+}
+
+impl Signer {
+    fn jwk(&self) -> Value {
+        json!({
+            "kty": "OKP", "crv": "Ed25519", "alg": "EdDSA", "use": "sig",
+            "kid": self.kid, "x": URL_SAFE_NO_PAD.encode(&self.public)
+        })
+    }
+
+    fn sign(&self, claims: Value) -> String {
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(self.kid.clone());
+        encode(&header, &claims, &EncodingKey::from_ed_der(&self.pkcs8)).unwrap()
+    }
+}
+
+fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+fn claims(repository: &str, git_ref: &str) -> Value {
+    json!({
+        "iss": ISSUER, "aud": AUDIENCE, "exp": now() + 300, "iat": now(),
+        "sub": format!("repo:{}:ref:{}", repository, git_ref),
+        "repository": repository, "ref": git_ref
+    })
+}
+
+/// Configures a JWKS with one trusted key and the token rules, once per test binary.
+fn setup() -> &'static Signer {
+    static SIGNER: OnceLock<Signer> = OnceLock::new();
+    SIGNER.get_or_init(|| {
+        let dir = std::env::temp_dir().join(format!("jwt-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let trusted = signer("trusted");
+
+        let jwks = dir.join("jwks.json");
+        std::fs::write(&jwks, json!({ "keys": [trusted.jwk()] }).to_string()).unwrap();
+        let rules = dir.join("keys.json");
+        let file = json!({
+            "tokens": [
+                { "id": "gh-web", "claims": { "repository": "acme/web", "ref": "refs/heads/*" },
+                  "scopes": ["upload", "read"], "projects": ["web"], "branches": ["{ref_name}"] },
+                { "id": "gh-acme", "claims": { "repository": "acme/*" },
+                  "scopes": ["read"], "branches": ["{environment}"] }
+            ]
+        });
+        std::fs::write(&rules, file.to_string()).unwrap();
+
+        assert_eq!(load_keys(&rules).unwrap(), 2);
+        let config = JwtConfig {
+            issuer: Some(ISSUER.to_string()),
+            audience: Some(AUDIENCE.to_string()),
+        };
+        assert_eq!(jwt::configure(JwksSource::File(jwks), config).unwrap(), 1);
+        trusted
+    })
+}
+
+#[test]
+fn claims_map_to_projects_and_branches() {
+    let trusted = setup();
+
+    let principal =
+        jwt::authenticate(&trusted.sign(claims("acme/web", "refs/heads/main"))).unwrap();
+    assert_eq!(principal.key_id, "gh-web");
+    assert_eq!(
+        principal.subject.as_deref(),
+        Some("repo:acme/web:ref:refs/heads/main")
+    );
+    assert!(principal.has_scope(Scope::Upload));
+    assert!(principal.allows("web", Some("main")));
+    assert!(!principal.allows("web", Some("other")));
+    assert!(!principal.allows("api", Some("main")));
+    assert!(authorize(&principal, &Method::POST, "/reports/upload"));
+
+    // Later rules apply when earlier ones do not match
+    let principal = jwt::authenticate(&trusted.sign(claims("acme/api", "refs/heads/dev"))).unwrap();
+    assert_eq!(principal.key_id, "gh-acme");
+    assert!(!principal.has_scope(Scope::Upload));
+    // No `environment` claim: the placeholder matches no branch at all
+    assert!(authorize(
+        &principal,
+        &Method::GET,
+        "/projects/api/settings"
+    ));
+    assert!(!authorize(
+        &principal,
+        &Method::GET,
+        "/projects/api/branches/dev/reports/e2e/trend"
+    ));
+
+    assert!(jwt::authenticate(&trusted.sign(claims("other/web", "refs/heads/main"))).is_err());
+}
+
+#[test]
+fn invalid_tokens_are_rejected() {
+    let trusted = setup();
+
+    let mut expired = claims("acme/web", "refs/heads/main");
+    expired["exp"] = json!(now() - 3600);
+    assert!(jwt::authenticate(&trusted.sign(expired)).is_err());
+
+    let mut wrong_audience = claims("acme/web", "refs/heads/main");
+    wrong_audience["aud"] = json!("someone-else");
+    assert!(jwt::authenticate(&trusted.sign(wrong_audience)).is_err());
+
+    let mut wrong_issuer = claims("acme/web", "refs/heads/main");
+    wrong_issuer["iss"] = json!("https://issuer.example");
+    assert!(jwt::authenticate(&trusted.sign(wrong_issuer)).is_err());
+
+    // Same kid, different key
+    let forged = signer("trusted");
+    assert!(jwt::authenticate(&forged.sign(claims("acme/web", "refs/heads/main"))).is_err());
+    let unknown = signer("unknown");
+    assert!(jwt::authenticate(&unknown.sign(claims("acme/web", "refs/heads/main"))).is_err());
+
+    assert!(jwt::authenticate("not-a-jwt").is_err());
+}
@@ -0,0 +1,68 @@
+use api::helpers::access_control::AccessControl;
+use api::helpers::api_keys::{hash_secret, load_keys};
+use api::helpers::auth::{MetricsAccess, metrics_auth};
+use api::helpers::metrics::metrics_handler;
+use axum::{
+    Router,
+    body::Body,
+    extract::ConnectInfo,
+    http::{Request, StatusCode},
+    middleware,
+    routing::get,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+async fn scrape(app: &Router, from: &str, key: Option<&str>) -> StatusCode {
+    let mut request = Request::get("/metrics");
+    if let Some(key) = key {
+        request = request.header("x-api-key", key);
+    }
+    let mut request = request.body(Body::empty()).unwrap();
+    request
+        .extensions_mut()
+        .insert(ConnectInfo(from.parse::<SocketAddr>().unwrap()));
+    app.clone().oneshot(request).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn metrics_need_an_admin_key_or_a_listed_ip() {
+    let keys = std::env::temp_dir().join(format!("metrics-keys-{}", uuid::Uuid::new_v4()));
+    let file = serde_json::json!({
+        "keys": [
+            { "id": "ops", "hash": hash_secret("admin-secret"), "scopes": ["admin"] },
+            { "id": "web-admin", "hash": hash_secret("web-secret"), "scopes": ["admin"],
+              "projects": ["web"] },
+            { "id": "ci", "hash": hash_secret("ci-secret"), "scopes": ["upload", "read"] }
+        ]
+    });
+    std::fs::write(&keys, serde_json::to_vec(&file).unwrap()).unwrap();
+    load_keys(&keys).unwrap();
+
+    let access = Arc::new(AccessControl::new(Vec::new(), Vec::new()));
+    let metrics = Arc::new(MetricsAccess::new(access, vec!["192.0.2.10".to_string()]));
+    let app = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .layer(middleware::from_fn_with_state(metrics, metrics_auth));
+
+    assert_eq!(
+        scrape(&app, "203.0.113.9:4000", None).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(scrape(&app, "192.0.2.10:4000", None).await, StatusCode::OK);
+    assert_eq!(
+        scrape(&app, "203.0.113.9:4000", Some("admin-secret")).await,
+        StatusCode::OK
+    );
+    // Keys limited to some projects would see the others' usage
+    assert_eq!(
+        scrape(&app, "203.0.113.9:4000", Some("web-secret")).await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        scrape(&app, "203.0.113.9:4000", Some("ci-secret")).await,
+        StatusCode::FORBIDDEN
+    );
+    std::fs::remove_file(&keys).unwrap();
+}
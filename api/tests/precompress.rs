@@ -0,0 +1,132 @@
+use api::helpers::access_control::{AccessControl, ProjectAccessConfig};
+use api::helpers::precompress::{
+    CachePolicy, cache_control, cache_headers, is_generated_sibling, precompress_dir,
+};
+use axum::{
+    Router,
+    body::Body,
+    http::{HeaderMap, Request, StatusCode, header},
+    middleware,
+};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+#[test]
+fn writes_smaller_siblings_for_large_text_files_only() {
+    let dir = std::env::temp_dir().join(format!("precompress-test-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(dir.join("data")).unwrap();
+    fs::create_dir_all(dir.join(".meta")).unwrap();
+    let big = "<div>report</div>\n".repeat(200);
+    fs::write(dir.join("index.html"), &big).unwrap();
+    fs::write(dir.join("data/suites.json"), "{}").unwrap();
+    fs::write(dir.join("logo.png"), &big).unwrap();
+    fs::write(dir.join(".meta/summary.json"), &big).unwrap();
+
+    let result = precompress_dir(&dir).unwrap();
+    assert_eq!(result.files_compressed, 1);
+    assert_eq!(result.original_bytes, big.len() as u64);
+    assert!(dir.join("index.html.gz").exists());
+    assert!(dir.join("index.html.br").exists());
+    // Too small, not compressible, or hidden
+    assert!(!dir.join("data/suites.json.gz").exists());
+    assert!(!dir.join("logo.png.gz").exists());
+    assert!(!dir.join(".meta/summary.json.gz").exists());
+
+    let mut decoded = String::new();
+    std::io::Read::read_to_string(
+        &mut flate2::read::GzDecoder::new(fs::File::open(dir.join("index.html.gz")).unwrap()),
+        &mut decoded,
+    )
+    .unwrap();
+    assert_eq!(decoded, big);
+
+    assert!(is_generated_sibling(&dir.join("index.html.gz")));
+    assert!(is_generated_sibling(&dir.join("index.html.br")));
+    // Uploaded archives next to small or binary files are report content
+    assert!(!is_generated_sibling(&dir.join("data/suites.json.gz")));
+    assert!(!is_generated_sibling(&dir.join("logo.png.gz")));
+    assert!(!is_generated_sibling(&dir.join("bundle.tar.gz")));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn only_run_directories_are_cached_forever() {
+    let immutable = "public, max-age=31536000, immutable";
+    assert_eq!(
+        cache_control("/web/main/e2e/12/index.html", false),
+        immutable
+    );
+    assert_eq!(
+        cache_control("/web/main/e2e/raw/3/data/a.json", false),
+        immutable
+    );
+    assert_eq!(
+        cache_control("/web/main/e2e/latest/index.html", false),
+        "no-cache"
+    );
+    assert_eq!(cache_control("/web/main/e2e/12", false), "no-cache");
+    assert_eq!(cache_control("/index.html", false), "no-cache");
+    assert_eq!(
+        cache_control("/web/main/e2e/12/index.html", true),
+        "private, max-age=31536000, immutable"
+    );
+    assert_eq!(cache_control("/index.html", true), "private, no-cache");
+}
+
+async fn cached_headers(policy: &CachePolicy, path: &str) -> HeaderMap {
+    let app =
+        Router::new()
+            .fallback(|| async { StatusCode::OK })
+            .layer(middleware::from_fn_with_state(
+                policy.clone(),
+                cache_headers,
+            ));
+    let request = Request::get(path).body(Body::empty()).unwrap();
+    app.oneshot(request).await.unwrap().headers().clone()
+}
+
+#[tokio::test]
+async fn protected_projects_stay_out_of_shared_caches() {
+    let projects = HashMap::from([(
+        "secret".to_string(),
+        ProjectAccessConfig {
+            allowed_ips: vec!["10.0.0.0/8".to_string()],
+            ..Default::default()
+        },
+    )]);
+    let policy = CachePolicy {
+        access: Arc::new(AccessControl::new(Vec::new(), Vec::new()).with_projects(projects)),
+        viewer_login: false,
+    };
+
+    let open = cached_headers(&policy, "/web/main/e2e/12/index.html").await;
+    assert_eq!(
+        open[header::CACHE_CONTROL],
+        "public, max-age=31536000, immutable"
+    );
+    assert_eq!(open[header::VARY], "accept-encoding");
+
+    let protected = cached_headers(&policy, "/secret/main/e2e/12/index.html").await;
+    assert_eq!(
+        protected[header::CACHE_CONTROL],
+        "private, max-age=31536000, immutable"
+    );
+    assert_eq!(
+        protected[header::VARY],
+        "accept-encoding, authorization, cookie"
+    );
+
+    // With viewer login every report needs a session
+    let login = CachePolicy {
+        viewer_login: true,
+        ..policy
+    };
+    let headers = cached_headers(&login, "/web/main/e2e/12/index.html").await;
+    assert_eq!(
+        headers[header::CACHE_CONTROL],
+        "private, max-age=31536000, immutable"
+    );
+}
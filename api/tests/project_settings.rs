@@ -0,0 +1,73 @@
+use api::helpers::history::{history_path, read_lines, seed_from};
+use api::helpers::project_settings::{self, merge};
+use api::models::project::{ProjectSettings, QualityGateRules};
+use serde_json::json;
+use std::fs;
+
+#[test]
+fn settings_updates_merge_into_stored_fields() {
+    let data = std::env::temp_dir().join(format!("settings-test-{}", uuid::Uuid::new_v4()));
+    let stored = ProjectSettings {
+        default_base_branch: None,
+        quality_gate: QualityGateRules {
+            min_pass_rate: Some(0.9),
+            max_broken: Some(0),
+            ..Default::default()
+        },
+    };
+    project_settings::save(&data, "p", &stored).unwrap();
+
+    // Setting the base branch keeps the gate
+    let current = project_settings::load(&data, "p");
+    let updated = merge(&current, json!({ "default_base_branch": "main" })).unwrap();
+    assert_eq!(updated.default_base_branch.as_deref(), Some("main"));
+    assert_eq!(updated.quality_gate.min_pass_rate, Some(0.9));
+    assert_eq!(updated.quality_gate.max_broken, Some(0));
+
+    // Gate rules merge one by one; null clears a rule
+    let updated = merge(
+        &updated,
+        json!({ "quality_gate": { "max_failed": 3, "min_pass_rate": null } }),
+    )
+    .unwrap();
+    assert_eq!(updated.quality_gate.max_failed, Some(3));
+    assert_eq!(updated.quality_gate.min_pass_rate, None);
+    assert_eq!(updated.quality_gate.max_broken, Some(0));
+    assert_eq!(updated.default_base_branch.as_deref(), Some("main"));
+
+    assert!(merge(&updated, json!({ "quality_gate": { "min_pass_rate": 2 } })).is_err());
+    assert!(merge(&updated, json!({ "default_base_branch": "../x" })).is_err());
+    assert!(merge(&updated, json!({ "quality_gate": { "max_failed": "ten" } })).is_err());
+    assert!(merge(&updated, json!(["main"])).is_err());
+
+    project_settings::save(&data, "p", &updated).unwrap();
+    let reloaded = project_settings::load(&data, "p");
+    assert_eq!(reloaded.quality_gate.max_failed, Some(3));
+    fs::remove_dir_all(&data).unwrap();
+}
+
+#[test]
+fn history_is_seeded_from_complete_base_lines() {
+    let data = std::env::temp_dir().join(format!("seed-test-{}", uuid::Uuid::new_v4()));
+    let base = data.join("p/main/e2e");
+    let report = data.join("p/pr-1/e2e");
+    fs::create_dir_all(&base).unwrap();
+    fs::create_dir_all(&report).unwrap();
+
+    // Nothing to seed from yet
+    assert_eq!(seed_from(&report, &base).unwrap(), 0);
+    assert!(!history_path(&report).exists());
+
+    // A line being appended concurrently is left out
+    fs::write(
+        history_path(&base),
+        "{\"uuid\":\"a\"}\n{\"uuid\":\"b\"}\n{\"uuid\":",
+    )
+    .unwrap();
+    assert_eq!(seed_from(&report, &base).unwrap(), 2);
+    assert_eq!(
+        read_lines(&report).unwrap(),
+        ["{\"uuid\":\"a\"}", "{\"uuid\":\"b\"}"]
+    );
+    fs::remove_dir_all(&data).unwrap();
+}
@@ -0,0 +1,62 @@
+use api::helpers::proxy_protocol::{parse, read_header};
+use std::net::SocketAddr;
+
+fn addr(s: &str) -> Option<SocketAddr> {
+    Some(s.parse().unwrap())
+}
+
+#[test]
+fn parses_v1_and_v2_headers() {
+    let v1 = b"PROXY TCP4 203.0.113.9 10.0.0.5 51234 8080\r\nGET / HTTP/1.1\r\n";
+    let header = parse(v1).unwrap().unwrap();
+    assert_eq!(header.source, addr("203.0.113.9:51234"));
+    assert_eq!(&v1[header.len..header.len + 3], b"GET");
+
+    let v1 = b"PROXY TCP6 2001:db8::1 2001:db8::2 4711 443\r\n";
+    assert_eq!(
+        parse(v1).unwrap().unwrap().source,
+        addr("[2001:db8::1]:4711")
+    );
+    assert_eq!(parse(b"PROXY UNKNOWN\r\n").unwrap().unwrap().source, None);
+
+    let mut v2 = b"\r\n\r\n\0\r\nQUIT\n".to_vec();
+    v2.extend_from_slice(&[0x21, 0x11, 0, 12]);
+    v2.extend_from_slice(&[198, 51, 100, 7, 10, 0, 0, 5, 0x1f, 0x90, 0x01, 0xbb]);
+    let header = parse(&v2).unwrap().unwrap();
+    assert_eq!(header.source, addr("198.51.100.7:8080"));
+    assert_eq!(header.len, 28);
+
+    // LOCAL health checks carry no client
+    let mut local = b"\r\n\r\n\0\r\nQUIT\n".to_vec();
+    local.extend_from_slice(&[0x20, 0x00, 0, 0]);
+    assert_eq!(parse(&local).unwrap().unwrap().source, None);
+
+    // Incomplete headers wait for more bytes
+    assert_eq!(parse(b"PROX").unwrap(), None);
+    assert_eq!(parse(b"PROXY TCP4 203.0.113.9").unwrap(), None);
+    assert_eq!(parse(&v2[..20]).unwrap(), None);
+
+    assert!(parse(b"GET / HTTP/1.1\r\n").is_err());
+    assert!(parse(b"PROXY TCP4 2001:db8::1 10.0.0.5 1 2\r\n").is_err());
+    assert!(parse(b"PROXY TCP4 203.0.113.9 10.0.0.5 99999 8080\r\n").is_err());
+    assert!(parse(&[b"PROXY TCP4 ".as_slice(), &[b'1'; 120]].concat()).is_err());
+}
+
+#[tokio::test]
+async fn read_header_leaves_the_request_buffered() {
+    let stream = b"PROXY TCP4 203.0.113.9 10.0.0.5 51234 8080\r\nGET / HTTP/1.1\r\n";
+    // A tiny buffer makes the header arrive in several chunks
+    let mut reader = tokio::io::BufReader::with_capacity(8, stream.as_slice());
+    assert_eq!(
+        read_header(&mut reader).await.unwrap(),
+        addr("203.0.113.9:51234")
+    );
+    let mut rest = String::new();
+    tokio::io::AsyncReadExt::read_to_string(&mut reader, &mut rest)
+        .await
+        .unwrap();
+    assert_eq!(rest, "GET / HTTP/1.1\r\n");
+
+    let mut reader = tokio::io::BufReader::new(b"GET / HTTP/1.1\r\n".as_slice());
+    assert!(read_header(&mut reader).await.is_err());
+}
@@ -0,0 +1,79 @@
+use api::helpers::rate_limit::{Limit, RateLimiter, parse_rate};
+use std::time::{Duration, Instant};
+
+#[test]
+fn buckets_allow_bursts_then_refill() {
+    let limiter = RateLimiter::new(
+        "test",
+        Limit {
+            per_sec: 0.5,
+            burst: 2.0,
+        },
+    );
+    let start = Instant::now();
+    assert_eq!(limiter.check_at("ci", start), Ok(1.0));
+    assert_eq!(limiter.check_at("ci", start), Ok(0.0));
+    let wait = limiter.check_at("ci", start).unwrap_err();
+    assert_eq!(wait, Duration::from_secs(2));
+
+    // Other keys have their own bucket
+    assert!(limiter.check_at("nightly", start).is_ok());
+
+    assert!(
+        limiter
+            .check_at("ci", start + Duration::from_secs(1))
+            .is_err()
+    );
+    assert!(
+        limiter
+            .check_at("ci", start + Duration::from_secs(2))
+            .is_ok()
+    );
+    // Idle time never adds more than the burst
+    let later = start + Duration::from_secs(600);
+    assert!(limiter.check_at("ci", later).is_ok());
+    assert!(limiter.check_at("ci", later).is_ok());
+    assert!(limiter.check_at("ci", later).is_err());
+}
+
+#[test]
+fn pruning_drops_only_refilled_buckets() {
+    let limiter = RateLimiter::new(
+        "test",
+        Limit {
+            per_sec: 1.0,
+            burst: 2.0,
+        },
+    );
+    let start = Instant::now();
+    assert!(limiter.check_at("idle", start).is_ok());
+    assert!(
+        limiter
+            .check_at("busy", start + Duration::from_secs(1))
+            .is_ok()
+    );
+    assert!(
+        limiter
+            .check_at("busy", start + Duration::from_secs(1))
+            .is_ok()
+    );
+    // "idle" has refilled, "busy" is still waiting for its tokens
+    assert_eq!(limiter.prune(start + Duration::from_secs(1)), 1);
+    assert_eq!(limiter.prune(start + Duration::from_secs(2)), 1);
+    assert_eq!(limiter.prune(start + Duration::from_secs(3)), 0);
+    // A pruned key starts over with a full burst
+    assert_eq!(
+        limiter.check_at("busy", start + Duration::from_secs(3)),
+        Ok(1.0)
+    );
+}
+
+#[test]
+fn parses_rates() {
+    assert_eq!(parse_rate("30/min"), Some(0.5));
+    assert_eq!(parse_rate(" 5 / s "), Some(5.0));
+    assert_eq!(parse_rate("7200/hour"), Some(2.0));
+    assert_eq!(parse_rate("0/min"), None);
+    assert_eq!(parse_rate("30"), None);
+    assert_eq!(parse_rate("30/day"), None);
+}
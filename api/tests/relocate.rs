@@ -0,0 +1,201 @@
+use api::helpers::relocate::{self, list_redirects, redirect_moved, source_report_dirs};
+use api::models::report::{RelocateMode, TreeRef};
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    middleware,
+};
+use serde_json::{Value, json};
+use std::fs;
+use std::path::Path;
+use tower::ServiceExt;
+
+fn tree(project: &str, branch: Option<&str>) -> TreeRef {
+    TreeRef {
+        project_name: project.to_string(),
+        branch: branch.map(|b| b.to_string()),
+        report_name: None,
+    }
+}
+
+fn temp_data_dir() -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("relocate-test-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::canonicalize(dir).unwrap()
+}
+
+/// A report with one run, its config and a history entry pointing at the run.
+fn write_report(data_dir: &Path, project: &str, branch: &str) {
+    let report = data_dir.join(project).join(branch).join("e2e");
+    fs::create_dir_all(report.join("1")).unwrap();
+    fs::write(report.join("1/index.html"), "<html>run 1</html>").unwrap();
+    let config = json!({
+        "historyPath": report.join("history.jsonl").to_string_lossy(),
+        "appendHistory": true
+    });
+    fs::write(report.join("allurerc.json"), config.to_string()).unwrap();
+    let url = format!("/{}/{}/e2e/1/index.html", project, branch);
+    let entry = json!({
+        "uuid": "a",
+        "name": format!("/{}/{}/e2e/1/ is not a url field", project, branch),
+        "url": url,
+        "testResults": { "t1": { "status": "passed", "url": format!("http://host{}", url) } }
+    });
+    fs::write(
+        report.join("history.jsonl"),
+        format!("{}\nnot json\n", entry),
+    )
+    .unwrap();
+}
+
+fn read_json(path: &Path) -> Value {
+    serde_json::from_slice(&fs::read(path).unwrap()).unwrap()
+}
+
+#[test]
+fn move_rewrites_only_history_path_and_urls() {
+    let data_dir = temp_data_dir();
+    write_report(&data_dir, "moving", "old");
+    let from = tree("moving", Some("old"));
+    assert_eq!(
+        source_report_dirs(&data_dir, &from),
+        vec![data_dir.join("moving/old/e2e")]
+    );
+
+    let result = relocate::relocate(
+        &data_dir,
+        &from,
+        &tree("moving", Some("new")),
+        RelocateMode::Move,
+        false,
+    )
+    .unwrap();
+    assert_eq!((result.runs, result.files_fixed), (1, 2));
+    assert!(!result.redirect);
+    assert!(!data_dir.join("moving/old").exists());
+
+    let report = data_dir.join("moving/new/e2e");
+    assert_eq!(
+        read_json(&report.join("allurerc.json"))["historyPath"],
+        report.join("history.jsonl").to_string_lossy().as_ref()
+    );
+    let history = fs::read_to_string(report.join("history.jsonl")).unwrap();
+    let mut lines = history.lines();
+    let entry: Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+    assert_eq!(entry["url"], "/moving/new/e2e/1/index.html");
+    assert_eq!(
+        entry["testResults"]["t1"]["url"],
+        "http://host/moving/new/e2e/1/index.html"
+    );
+    // Other fields mentioning the old path stay as they were
+    assert_eq!(entry["name"], "/moving/old/e2e/1/ is not a url field");
+    assert_eq!(lines.next(), Some("not json"));
+}
+
+#[test]
+fn copy_keeps_the_source_and_refuses_existing_destinations() {
+    let data_dir = temp_data_dir();
+    write_report(&data_dir, "copying", "main");
+    let result = relocate::relocate(
+        &data_dir,
+        &tree("copying", None),
+        &tree("copied", None),
+        RelocateMode::Copy,
+        true,
+    )
+    .unwrap();
+    assert_eq!(result.runs, 1);
+    // Copies never redirect
+    assert!(!result.redirect);
+    assert!(data_dir.join("copying/main/e2e/1/index.html").is_file());
+    assert_eq!(
+        fs::read_to_string(data_dir.join("copied/main/e2e/1/index.html")).unwrap(),
+        "<html>run 1</html>"
+    );
+    let source_config = read_json(&data_dir.join("copying/main/e2e/allurerc.json"));
+    assert!(
+        source_config["historyPath"]
+            .as_str()
+            .unwrap()
+            .contains("/copying/")
+    );
+    let copy_config = read_json(&data_dir.join("copied/main/e2e/allurerc.json"));
+    assert!(
+        copy_config["historyPath"]
+            .as_str()
+            .unwrap()
+            .contains("/copied/")
+    );
+
+    let again = relocate::relocate(
+        &data_dir,
+        &tree("copying", None),
+        &tree("copied", None),
+        RelocateMode::Copy,
+        false,
+    );
+    assert!(again.unwrap_err().contains("already exists"));
+    let nested = relocate::relocate(
+        &data_dir,
+        &tree("copying", None),
+        &tree("copying", Some("main")),
+        RelocateMode::Move,
+        false,
+    );
+    assert!(nested.is_err());
+    // Reserved top-level paths cannot become project names
+    let reserved = relocate::relocate(
+        &data_dir,
+        &tree("copying", None),
+        &tree("metrics", None),
+        RelocateMode::Copy,
+        false,
+    );
+    assert!(reserved.unwrap_err().contains("reserved"));
+}
+
+#[tokio::test]
+async fn moved_paths_redirect_temporarily_until_recreated() {
+    let data_dir = temp_data_dir();
+    write_report(&data_dir, "redirected", "old");
+    let result = relocate::relocate(
+        &data_dir,
+        &tree("redirected", Some("old")),
+        &tree("redirected", Some("new")),
+        RelocateMode::Move,
+        true,
+    )
+    .unwrap();
+    assert!(result.redirect);
+    assert!(
+        list_redirects()
+            .iter()
+            .any(|r| r.from == "redirected/old" && r.to == "redirected/new")
+    );
+
+    let app = Router::new()
+        .fallback(|| async { StatusCode::NOT_FOUND })
+        .layer(middleware::from_fn_with_state(
+            data_dir.clone(),
+            redirect_moved,
+        ));
+    let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+    let response = app
+        .clone()
+        .oneshot(get("/redirected/old/e2e/1/index.html?x=1"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(
+        response.headers()[header::LOCATION],
+        "/redirected/new/e2e/1/index.html?x=1"
+    );
+    assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+
+    // Once the old path exists again it is served, not redirected
+    fs::create_dir_all(data_dir.join("redirected/old")).unwrap();
+    let response = app.oneshot(get("/redirected/old/")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,106 @@
+use api::handlers::report_handler::upload_report;
+use api::helpers::api_keys::{Principal, Scope};
+use api::helpers::fs_helper::{RESERVED_PROJECT_NAMES, validate_project_name};
+use api::helpers::share_link::{ShareError, share_path, verify};
+use axum::{
+    Extension, Router,
+    body::{Body, to_bytes},
+    http::{Request, StatusCode, header},
+    routing::post,
+};
+use tower::ServiceExt;
+
+const NOW: i64 = 1_700_000_000;
+
+#[test]
+fn links_grant_only_their_run() {
+    let link = share_path("web/main/e2e/3", NOW + 3600);
+    assert!(link.starts_with("/share/"));
+
+    let shared = verify(&format!("{}index.html", link), NOW).unwrap();
+    assert_eq!(shared.run_path, "web/main/e2e/3");
+    assert_eq!(shared.path, "/web/main/e2e/3/index.html");
+    assert_eq!(verify(&link, NOW).unwrap().path, "/web/main/e2e/3/");
+    assert_eq!(
+        verify(&format!("{}data/suites.json", link), NOW)
+            .unwrap()
+            .path,
+        "/web/main/e2e/3/data/suites.json"
+    );
+
+    // Another run under the same signature
+    let other = link.replace("/e2e/3/", "/e2e/4/");
+    assert_eq!(verify(&other, NOW), Err(ShareError::BadSignature));
+    let other = link.replace("/web/main/", "/api/main/");
+    assert_eq!(verify(&other, NOW), Err(ShareError::BadSignature));
+    // Climbing out of the run
+    assert_eq!(
+        verify(&format!("{}../4/index.html", link), NOW),
+        Err(ShareError::Malformed)
+    );
+    // A later expiry needs a new signature
+    let extended = link.replacen(&(NOW + 3600).to_string(), &(NOW + 7200).to_string(), 1);
+    assert_eq!(verify(&extended, NOW), Err(ShareError::BadSignature));
+
+    assert_eq!(verify(&link, NOW + 3600), Err(ShareError::Expired));
+    assert_eq!(
+        verify("/share/abc/def/web/", NOW),
+        Err(ShareError::Malformed)
+    );
+}
+
+#[test]
+fn raw_runs_can_be_shared() {
+    let link = share_path("web/main/e2e/raw/7", NOW + 60);
+    let shared = verify(&format!("{}index.html", link), NOW).unwrap();
+    assert_eq!(shared.run_path, "web/main/e2e/raw/7");
+    assert_eq!(shared.path, "/web/main/e2e/raw/7/index.html");
+}
+
+#[tokio::test]
+async fn uploads_cannot_take_the_share_prefix() {
+    let principal = Principal {
+        key_id: "ci".to_string(),
+        scopes: vec![Scope::Upload],
+        projects: Vec::new(),
+        branches: Vec::new(),
+        subject: None,
+    };
+    let app = Router::new()
+        .route("/api/reports/upload", post(upload_report))
+        .layer(Extension(principal));
+    let boundary = "reserved-boundary";
+    let mut body = String::new();
+    for (name, value) in [
+        ("project_name", "share"),
+        ("branch", "main"),
+        ("report_name", "e2e"),
+    ] {
+        body.push_str(&format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+            boundary, name, value
+        ));
+    }
+    body.push_str(&format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"r.zip\"\r\n\
+         Content-Type: application/zip\r\n\r\nPK\r\n--{}--\r\n",
+        boundary, boundary
+    ));
+    let request = Request::post("/api/reports/upload")
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={}", boundary),
+        )
+        .body(Body::from(body))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert!(String::from_utf8_lossy(&body).contains("reserved"));
+
+    for name in RESERVED_PROJECT_NAMES {
+        assert!(validate_project_name(name).is_err());
+    }
+    assert_eq!(validate_project_name("shared").unwrap(), "shared");
+}
@@ -0,0 +1,56 @@
+use api::helpers::fs_helper::RunLocation;
+use api::helpers::usage::{self, USAGE_FILE_NAME};
+use std::fs;
+use std::path::Path;
+
+fn write_run(data_dir: &Path, report: &str, id: u32, bytes: usize) -> RunLocation {
+    let location = RunLocation::resolve(data_dir, "web", "main", report, false, id).unwrap();
+    fs::create_dir_all(location.path.join("data")).unwrap();
+    fs::write(location.path.join("index.html"), "x".repeat(bytes)).unwrap();
+    fs::write(location.path.join("data/a.json"), "{}").unwrap();
+    location
+}
+
+#[test]
+fn usage_is_maintained_incrementally_and_merged_across_processes() {
+    let data_dir = std::env::temp_dir().join(format!("usage-test-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&data_dir).unwrap();
+    let first = write_run(&data_dir, "e2e", 1, 100);
+    usage::init(&data_dir);
+    assert_eq!(usage::report(&data_dir, None).usage.bytes, 102);
+
+    let second = write_run(&data_dir, "e2e", 2, 10);
+    usage::record_run(&data_dir, &second);
+    let report = usage::report(&data_dir, Some("web"));
+    let e2e = &report.projects["web"].branches["main"].reports["e2e"];
+    assert_eq!(e2e.usage.files, 4);
+    assert_eq!(e2e.runs["2"].bytes, 12);
+    assert!(usage::report(&data_dir, Some("other")).projects.is_empty());
+
+    // Another process sharing DATA_DIR records a run in the file
+    let index_file = data_dir.join(USAGE_FILE_NAME);
+    let mut index: serde_json::Value =
+        serde_json::from_slice(&fs::read(&index_file).unwrap()).unwrap();
+    index["runs"]["web/main/unit/1"] = serde_json::json!({ "bytes": 5, "files": 1 });
+    fs::write(&index_file, serde_json::to_vec(&index).unwrap()).unwrap();
+
+    // Our next change keeps it instead of overwriting the file
+    let third = write_run(&data_dir, "e2e", 3, 1);
+    usage::record_run(&data_dir, &third);
+    let report = usage::report(&data_dir, None);
+    assert_eq!(report.usage.bytes, 102 + 12 + 3 + 5);
+    assert!(
+        report.projects["web"].branches["main"]
+            .reports
+            .contains_key("unit")
+    );
+
+    usage::forget_prefix(&data_dir, "web/main/e2e");
+    let report = usage::report(&data_dir, None);
+    assert_eq!(report.usage.bytes, 5);
+
+    fs::remove_dir_all(&first.path).unwrap();
+    assert_eq!(usage::rescan(&data_dir).bytes, 12 + 3);
+
+    fs::remove_dir_all(&data_dir).unwrap();
+}
@@ -0,0 +1,195 @@
+use api::handlers::manifest::{ManifestState, get_manifest};
+use api::helpers::access_control::{AccessControl, ProjectAccessConfig};
+use api::helpers::viewer_auth::{
+    MANIFEST_PATH, SESSION_COOKIE, ViewerAuth, ViewerUser, load_users, safe_next,
+    verify_password_hash, viewer_session,
+};
+use argon2::password_hash::SaltString;
+use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    extract::ConnectInfo,
+    http::{Request, StatusCode, header},
+    middleware,
+    routing::get,
+};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+/// Cheap parameters keep the tests fast; verification reads them from the hash.
+fn argon2_hash(password: &str) -> String {
+    let salt = SaltString::encode_b64(b"report-host-salt").unwrap();
+    let params = Params::new(1024, 1, 1, None).unwrap();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password(password.as_bytes(), &salt)
+        .unwrap()
+        .to_string()
+}
+
+fn access() -> Arc<AccessControl> {
+    let mut projects = HashMap::new();
+    projects.insert(
+        "confidential".to_string(),
+        ProjectAccessConfig {
+            groups: vec!["staff".to_string()],
+            ..Default::default()
+        },
+    );
+    Arc::new(AccessControl::new(Vec::new(), Vec::new()).with_projects(projects))
+}
+
+fn viewer_auth() -> ViewerAuth {
+    let users = vec![
+        ViewerUser {
+            username: "alice".to_string(),
+            password_hash: argon2_hash("wonderland"),
+            groups: vec!["staff".to_string()],
+        },
+        ViewerUser {
+            username: "bob".to_string(),
+            password_hash: argon2_hash("builder"),
+            groups: vec!["contractors".to_string()],
+        },
+    ];
+    ViewerAuth::new(users, b"test-session-secret".to_vec(), 3600, access())
+}
+
+#[test]
+fn passwords_and_groups() {
+    let auth = viewer_auth();
+    assert!(auth.verify_password("alice", "wonderland"));
+    assert!(!auth.verify_password("alice", "builder"));
+    assert!(!auth.verify_password("carol", "wonderland"));
+    assert!(!verify_password_hash(None, ""));
+
+    let now = 1_700_000_000;
+    let alice = auth.session(&auth.issue("alice", now), now).unwrap();
+    let bob = auth.session(&auth.issue("bob", now), now).unwrap();
+    assert!(auth.may_view(alice, Some("confidential")));
+    assert!(!auth.may_view(bob, Some("confidential")));
+    assert!(auth.may_view(bob, Some("web")));
+    assert!(auth.may_view(bob, None));
+}
+
+#[test]
+fn session_cookies_are_signed_and_expire() {
+    let auth = viewer_auth();
+    let now = 1_700_000_000;
+    let cookie = auth.issue("alice", now);
+
+    assert_eq!(auth.session(&cookie, now + 60).unwrap().username, "alice");
+    assert!(auth.session(&cookie, now + 3600).is_none());
+
+    // Swapping the claims for another user's breaks the signature
+    let (_, signature) = cookie.split_once('.').unwrap();
+    let (bob_claims, _) = auth
+        .issue("bob", now)
+        .split_once('.')
+        .map(|(c, s)| (c.to_string(), s.to_string()))
+        .unwrap();
+    assert!(
+        auth.session(&format!("{}.{}", bob_claims, signature), now)
+            .is_none()
+    );
+    assert!(auth.session("garbage", now).is_none());
+
+    // A key from another server does not validate
+    let other = ViewerAuth::new(
+        Vec::new(),
+        b"other-secret".to_vec(),
+        3600,
+        Arc::new(AccessControl::new(Vec::new(), Vec::new())),
+    );
+    assert!(auth.session(&other.issue("alice", now), now).is_none());
+}
+
+#[test]
+fn users_file_and_redirect_targets() {
+    let dir = std::env::temp_dir().join(format!("viewers-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("users.json");
+    let file = serde_json::json!({
+        "users": [{ "username": "alice", "password_hash": argon2_hash("pw"), "groups": ["staff"] }]
+    });
+    std::fs::write(&path, file.to_string()).unwrap();
+    assert_eq!(load_users(&path).unwrap()[0].groups, vec!["staff"]);
+
+    std::fs::write(
+        &path,
+        r#"{"users":[{"username":"alice","password_hash":"pw"}]}"#,
+    )
+    .unwrap();
+    assert!(load_users(&path).is_err());
+
+    assert_eq!(
+        safe_next(Some("/web/main/e2e/3/index.html")),
+        "/web/main/e2e/3/index.html"
+    );
+    assert_eq!(safe_next(Some("//evil.example/")), "/");
+    assert_eq!(safe_next(Some("https://evil.example/")), "/");
+    // Browsers strip tabs and newlines, which would turn these into //evil.example
+    assert_eq!(safe_next(Some("/\t/evil.example")), "/");
+    assert_eq!(safe_next(Some("/\n/evil.example")), "/");
+    assert_eq!(safe_next(Some("/\r\n/evil.example")), "/");
+    assert_eq!(safe_next(Some("/web\u{7f}")), "/");
+    assert_eq!(safe_next(Some("/\\evil.example")), "/");
+    assert_eq!(
+        safe_next(Some("/web/main/?tab=%09x#top")),
+        "/web/main/?tab=%09x#top"
+    );
+    assert_eq!(safe_next(None), "/");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn manifest_lists_only_projects_the_viewer_may_view() {
+    let data = std::env::temp_dir().join(format!("manifest-test-{}", uuid::Uuid::new_v4()));
+    for project in ["web", "confidential"] {
+        std::fs::create_dir_all(data.join(project).join("main/e2e/1")).unwrap();
+    }
+    let auth = Arc::new(viewer_auth());
+    let app = Router::new()
+        .route(MANIFEST_PATH, get(get_manifest))
+        .with_state(ManifestState {
+            access: access(),
+            data_dir: data.clone(),
+        })
+        .layer(middleware::from_fn_with_state(
+            Some(auth.clone()),
+            viewer_session,
+        ));
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    let projects = |user: &str| {
+        let mut request = Request::get(MANIFEST_PATH)
+            .header(
+                header::COOKIE,
+                format!("{}={}", SESSION_COOKIE, auth.issue(user, now)),
+            )
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))));
+        let app = app.clone();
+        async move {
+            let response = app.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let manifest: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            let mut names: Vec<String> = manifest.as_object().unwrap().keys().cloned().collect();
+            names.sort();
+            names
+        }
+    };
+    assert_eq!(projects("alice").await, ["confidential", "web"]);
+    assert_eq!(projects("bob").await, ["web"]);
+
+    std::fs::remove_dir_all(&data).unwrap();
+}
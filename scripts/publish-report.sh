@@ -7,6 +7,7 @@ set -euo pipefail
 # Configuration
 : "${SERVER_URL:=}"
 : "${REPORT_API_SECRET:=}"
+: "${REPORT_TOKEN:=}"
 : "${MAX_FILE_SIZE_MB:=500}"
 
 usage() {
@@ -20,6 +21,8 @@ dry_run=0
 verbose=0
 tests_passed=""
 tests_failed=""
+base_branch=""
+quality_gate=0
 
 while [[ $# -gt 0 ]]; do
     case "$1" in
@@ -28,10 +31,13 @@ while [[ $# -gt 0 ]]; do
         --verbose|-v) verbose=1; shift ;;
         --url) SERVER_URL="$2"; shift 2 ;;
         --key) REPORT_API_SECRET="$2"; shift 2 ;;
+        --token) REPORT_TOKEN="$2"; shift 2 ;;
         --github-token) GITHUB_TOKEN="$2"; shift 2 ;;
         --test-result) test_result="$2"; shift 2 ;;
         --tests-passed) tests_passed="$2"; shift 2 ;;
         --tests-failed) tests_failed="$2"; shift 2 ;;
+        --base-branch) base_branch="$2"; shift 2 ;;
+        --gate) quality_gate=1; shift ;;
         *) break ;;
     esac
 done
@@ -88,7 +94,10 @@ curl_args=(
     --progress-bar
 )
 
+[[ -n "$base_branch" ]] && curl_args+=(-F "base_branch=$base_branch")
+[[ $quality_gate -eq 1 ]] && curl_args+=(-F "evaluate_gate=true")
 [[ -n "$REPORT_API_SECRET" ]] && curl_args+=(-H "X-API-Key: $REPORT_API_SECRET")
+[[ -n "$REPORT_TOKEN" ]] && curl_args+=(-H "Authorization: Bearer $REPORT_TOKEN")
 [[ $verbose -eq 1 ]] && curl_args+=(-v)
 
 # Summary
@@ -127,6 +136,20 @@ if (( http_code >= 200 && http_code < 300 )); then
         jq . "$response" 2>/dev/null || cat "$response"
     fi
 
+    # Fail the build when the project's quality gate rejects the run
+    if [[ $quality_gate -eq 1 ]]; then
+        if ! command -v jq >/dev/null; then
+            echo "Error: jq is required to evaluate the quality gate" >&2
+            exit 1
+        fi
+        if [[ "$(jq -r '.gate.passed' "$response")" != "true" ]]; then
+            echo -e "\n→ Quality gate failed:" >&2
+            jq -r '.gate.reasons[]? | "  - " + .' "$response" >&2
+            exit 2
+        fi
+        echo "→ Quality gate passed"
+    fi
+
     # if [[ -n "${GITHUB_TOKEN:-}" ]]; then
     #   commit_sha="${GITHUB_SHA:-$(git rev-parse HEAD)}"
     #   context="${report_name}"